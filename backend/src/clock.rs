@@ -0,0 +1,290 @@
+//! Tempo and transport tracking from incoming MIDI clock.
+
+use midi_protocol::{MidiMessage, TransportState, TransportStatus};
+
+/// Timing Clock pulses per quarter note.
+const PPQN: u32 = 24;
+/// Timing Clock pulses per MIDI beat (sixteenth note), the unit of Song
+/// Position Pointer.
+const PULSES_PER_MIDI_BEAT: u32 = 6;
+/// Weight of each new tick interval in the running averages.
+const SMOOTHING: f64 = 0.1;
+/// Tick intervals to average before a tempo is reported.
+const MIN_INTERVALS: u32 = 6;
+
+/// With no tick for this long, the clock source is considered gone.
+pub const CLOCK_TIMEOUT_US: u64 = 500_000;
+
+/// Derives tempo, clock stability and transport state from System Real-Time
+/// and Song Position messages.
+///
+/// Timestamps are microseconds from any monotonic origin.
+#[derive(Debug, Clone, Default)]
+pub struct ClockTracker {
+    state: TransportState,
+    last_tick_us: Option<u64>,
+    /// Exponential moving average of the tick interval.
+    interval_us: Option<f64>,
+    /// Exponential moving average of |interval - mean|.
+    deviation_us: f64,
+    intervals: u32,
+    ticks: u32,
+    pulses: u32,
+}
+
+impl ClockTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn state(&self) -> &TransportState {
+        &self.state
+    }
+
+    /// Feeds a message received at `now_us`.
+    ///
+    /// Returns the new transport state when it changed in a way clients
+    /// should hear about. While clock is running that is once per quarter
+    /// note, so clients aren't sent an update for every tick.
+    pub fn handle(&mut self, message: &MidiMessage, now_us: u64) -> Option<TransportState> {
+        match message {
+            MidiMessage::TimingClock => self.tick(now_us),
+            MidiMessage::Start => {
+                self.state.status = TransportStatus::Playing;
+                self.state.song_position = 0;
+                self.pulses = 0;
+                self.ticks = 0;
+                Some(self.state.clone())
+            }
+            MidiMessage::Continue => {
+                self.state.status = TransportStatus::Playing;
+                Some(self.state.clone())
+            }
+            MidiMessage::Stop => {
+                self.state.status = TransportStatus::Stopped;
+                Some(self.state.clone())
+            }
+            MidiMessage::SongPositionPointer { beats } => {
+                self.state.song_position = *beats;
+                self.pulses = 0;
+                // Keep quarter-note updates on the beat from the new position
+                self.ticks = (*beats as u32 % 4) * PULSES_PER_MIDI_BEAT;
+                Some(self.state.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Marks the clock as lost once no tick has arrived for
+    /// [`CLOCK_TIMEOUT_US`]. Returns the new state if that just happened.
+    pub fn check_timeout(&mut self, now_us: u64) -> Option<TransportState> {
+        match self.last_tick_us {
+            Some(last) if now_us.saturating_sub(last) >= CLOCK_TIMEOUT_US => {
+                self.reset_clock();
+                Some(self.state.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn tick(&mut self, now_us: u64) -> Option<TransportState> {
+        let mut changed = !self.state.clock;
+        self.state.clock = true;
+
+        if let Some(last) = self.last_tick_us {
+            let interval = now_us.saturating_sub(last) as f64;
+            if interval >= CLOCK_TIMEOUT_US as f64 {
+                // A gap this long is a new clock stream, not a slow tempo
+                self.interval_us = None;
+                self.deviation_us = 0.0;
+                self.intervals = 0;
+            } else {
+                match self.interval_us {
+                    None => self.interval_us = Some(interval),
+                    Some(mean) => {
+                        self.deviation_us +=
+                            ((interval - mean).abs() - self.deviation_us) * SMOOTHING;
+                        self.interval_us = Some(mean + (interval - mean) * SMOOTHING);
+                    }
+                }
+                self.intervals += 1;
+            }
+        }
+        self.last_tick_us = Some(now_us);
+
+        if self.state.status == TransportStatus::Playing {
+            self.pulses += 1;
+            if self.pulses == PULSES_PER_MIDI_BEAT {
+                self.pulses = 0;
+                self.state.song_position = self.state.song_position.wrapping_add(1);
+            }
+        }
+
+        match self.interval_us {
+            Some(mean) if self.intervals >= MIN_INTERVALS && mean > 0.0 => {
+                self.state.bpm = Some(60_000_000.0 / (mean * PPQN as f64));
+                self.state.jitter_ms = Some(self.deviation_us / 1000.0);
+            }
+            _ => {
+                self.state.bpm = None;
+                self.state.jitter_ms = None;
+            }
+        }
+
+        self.ticks = (self.ticks + 1) % PPQN;
+        if self.ticks == 0 {
+            changed = true;
+        }
+
+        changed.then(|| self.state.clone())
+    }
+
+    fn reset_clock(&mut self) {
+        self.state.clock = false;
+        self.state.bpm = None;
+        self.state.jitter_ms = None;
+        self.last_tick_us = None;
+        self.interval_us = None;
+        self.deviation_us = 0.0;
+        self.intervals = 0;
+        self.ticks = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tick interval at 120 BPM: 500 ms per quarter note / 24.
+    const TICK_120_BPM: u64 = 20_833;
+
+    fn feed_ticks(tracker: &mut ClockTracker, start_us: u64, interval: u64, count: u64) -> u64 {
+        let mut now = start_us;
+        for _ in 0..count {
+            tracker.handle(&MidiMessage::TimingClock, now);
+            now += interval;
+        }
+        now
+    }
+
+    #[test]
+    fn test_bpm_from_steady_clock() {
+        let mut tracker = ClockTracker::new();
+        feed_ticks(&mut tracker, 0, TICK_120_BPM, 48);
+
+        let state = tracker.state();
+        assert!(state.clock);
+        let bpm = state.bpm.unwrap();
+        assert!((bpm - 120.0).abs() < 0.1, "bpm was {}", bpm);
+        assert!(state.jitter_ms.unwrap() < 0.01);
+    }
+
+    #[test]
+    fn test_bpm_smooths_jitter() {
+        let mut tracker = ClockTracker::new();
+        let mut now = 0;
+        for i in 0..96 {
+            tracker.handle(&MidiMessage::TimingClock, now);
+            // Alternate 1 ms early and late around 120 BPM
+            now += if i % 2 == 0 {
+                TICK_120_BPM - 1000
+            } else {
+                TICK_120_BPM + 1000
+            };
+        }
+
+        let state = tracker.state();
+        assert!((state.bpm.unwrap() - 120.0).abs() < 1.0);
+        let jitter = state.jitter_ms.unwrap();
+        assert!(jitter > 0.5 && jitter < 2.5, "jitter was {}", jitter);
+    }
+
+    #[test]
+    fn test_reports_once_per_quarter_note() {
+        let mut tracker = ClockTracker::new();
+        let mut reports = 0;
+        let mut now = 0;
+        for _ in 0..(PPQN * 4) {
+            if tracker.handle(&MidiMessage::TimingClock, now).is_some() {
+                reports += 1;
+            }
+            now += TICK_120_BPM;
+        }
+        // First tick (clock appeared) plus one per quarter note
+        assert_eq!(reports, 5);
+    }
+
+    #[test]
+    fn test_quarter_note_reports_follow_start_and_position() {
+        let mut tracker = ClockTracker::new();
+        let now = feed_ticks(&mut tracker, 0, TICK_120_BPM, 10);
+
+        // Start realigns to a quarter note: next report is a full 24 ticks on
+        tracker.handle(&MidiMessage::Start, now);
+        let mut now = now;
+        for _ in 0..(PPQN - 1) {
+            assert!(tracker.handle(&MidiMessage::TimingClock, now).is_none());
+            now += TICK_120_BPM;
+        }
+        assert!(tracker.handle(&MidiMessage::TimingClock, now).is_some());
+        now += TICK_120_BPM;
+
+        // Two MIDI beats in: half a quarter note (12 ticks) to the next beat
+        now = feed_ticks(&mut tracker, now, TICK_120_BPM, 5);
+        tracker.handle(&MidiMessage::SongPositionPointer { beats: 6 }, now);
+        for _ in 0..(PPQN / 2 - 1) {
+            assert!(tracker.handle(&MidiMessage::TimingClock, now).is_none());
+            now += TICK_120_BPM;
+        }
+        assert!(tracker.handle(&MidiMessage::TimingClock, now).is_some());
+        assert_eq!(tracker.state().song_position, 8);
+    }
+
+    #[test]
+    fn test_transport_and_song_position() {
+        let mut tracker = ClockTracker::new();
+
+        let state = tracker.handle(&MidiMessage::Start, 0).unwrap();
+        assert_eq!(state.status, TransportStatus::Playing);
+        assert_eq!(state.song_position, 0);
+
+        // One quarter note is four MIDI beats
+        feed_ticks(&mut tracker, 0, TICK_120_BPM, 24);
+        assert_eq!(tracker.state().song_position, 4);
+
+        let state = tracker.handle(&MidiMessage::Stop, 0).unwrap();
+        assert_eq!(state.status, TransportStatus::Stopped);
+
+        // Position doesn't advance while stopped
+        feed_ticks(&mut tracker, 600_000, TICK_120_BPM, 24);
+        assert_eq!(tracker.state().song_position, 4);
+
+        let state = tracker
+            .handle(&MidiMessage::SongPositionPointer { beats: 32 }, 0)
+            .unwrap();
+        assert_eq!(state.song_position, 32);
+
+        let state = tracker.handle(&MidiMessage::Continue, 0).unwrap();
+        assert_eq!(state.status, TransportStatus::Playing);
+        assert_eq!(state.song_position, 32);
+    }
+
+    #[test]
+    fn test_clock_timeout() {
+        let mut tracker = ClockTracker::new();
+        let end = feed_ticks(&mut tracker, 0, TICK_120_BPM, 24);
+        assert!(tracker.check_timeout(end).is_none());
+
+        let state = tracker.check_timeout(end + CLOCK_TIMEOUT_US).unwrap();
+        assert!(!state.clock);
+        assert_eq!(state.bpm, None);
+
+        // Only reported once
+        assert!(tracker.check_timeout(end + 2 * CLOCK_TIMEOUT_US).is_none());
+
+        // Clock coming back is reported immediately
+        assert!(tracker
+            .handle(&MidiMessage::TimingClock, end + 3 * CLOCK_TIMEOUT_US)
+            .is_some());
+    }
+}
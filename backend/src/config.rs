@@ -0,0 +1,547 @@
+//! Server configuration, read from a TOML file, then environment variables,
+//! then command-line flags, each overriding the one before.
+
+use crate::routing::Route;
+use crate::selector::PortSelector;
+use anyhow::{anyhow, bail, Context};
+use midi_protocol::MpeLayout;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml_edit::DocumentMut;
+
+/// Read from the working directory when no other file is named.
+pub const DEFAULT_CONFIG_FILE: &str = "midi-monitor.toml";
+
+/// Everything `start_server` can be told. Each setting can also come from
+/// the environment variable and flag named in its description.
+///
+/// ```toml
+/// address = "0.0.0.0"
+/// port = 3100
+/// allowed_origins = ["http://localhost:3001", "http://studio.local:3001"]
+/// inputs = ["regex:^Keystation", "MPD218"]
+/// virtual_input = "MIDI Monitor In"
+/// virtual_output = "MIDI Monitor Out"
+/// mpe_zones = "lower=15"
+///
+/// [[routes]]
+/// from = "input:Keystation 49"
+/// to = "output:FluidSynth"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address to listen on (`MIDI_MONITOR_ADDRESS`, `--address`).
+    pub address: IpAddr,
+    /// Port to listen on, 0 for any free one (`MIDI_MONITOR_PORT`, `--port`).
+    pub port: u16,
+    /// Origins browsers may call the API from, or `*` for any
+    /// (`MIDI_MONITOR_ALLOWED_ORIGINS`, separated by `,`, or repeated
+    /// `--allow-origin`).
+    pub allowed_origins: Vec<String>,
+    /// Events kept for each WebSocket client before a slow one starts
+    /// missing them (`MIDI_MONITOR_BROADCAST_CAPACITY`,
+    /// `--broadcast-capacity`).
+    pub broadcast_capacity: usize,
+    /// Whether to play simulated notes while no input is open
+    /// (`MIDI_MONITOR_SIMULATION`, `--simulation`, `true` or `false`).
+    pub simulation: bool,
+    /// Input ports to open (`MIDI_MONITOR_INPUT`, separated by `;`, or repeated
+    /// `--input`). Without any, every port other than a "Midi Through"
+    /// loopback.
+    pub inputs: Vec<PortSelector>,
+    /// Name of a virtual input port to create for other applications to
+    /// send to (`MIDI_MONITOR_VIRTUAL_INPUT`, `--virtual-input`).
+    pub virtual_input: Option<String>,
+    /// Name of a virtual output port to create that passes on all MIDI
+    /// input (`MIDI_MONITOR_VIRTUAL_OUTPUT`, `--virtual-output`).
+    pub virtual_output: Option<String>,
+    /// A fixed MPE zone layout such as `lower=15` or `lower=7,upper=7`, for
+    /// controllers that don't send an MPE Configuration Message
+    /// (`MIDI_MONITOR_MPE_ZONES`, `--mpe-zones`).
+    #[serde(deserialize_with = "deserialize_mpe_zones")]
+    pub mpe_zones: Option<MpeLayout>,
+    /// MIDI thru routes, also edited at runtime through `/api/routes`.
+    pub routes: Vec<Route>,
+    /// Where route edits are saved: the file the configuration came from.
+    /// Without one, edits last until the server stops.
+    #[serde(skip)]
+    pub file: Option<PathBuf>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 3000,
+            // Where `trunk serve` puts the frontend
+            allowed_origins: vec!["http://localhost:3001".to_string()],
+            broadcast_capacity: 100,
+            simulation: true,
+            inputs: Vec::new(),
+            virtual_input: None,
+            virtual_output: None,
+            mpe_zones: None,
+            routes: Vec::new(),
+            file: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Loads the configuration for the process: `args` are the command-line
+    /// arguments without the program name.
+    ///
+    /// The file is `--config <file>`, else `MIDI_MONITOR_CONFIG`, else
+    /// `midi-monitor.toml` if it exists.
+    pub fn load(args: impl IntoIterator<Item = String>) -> anyhow::Result<Self> {
+        Self::load_with(args, |name| std::env::var(name).ok())
+    }
+
+    fn load_with(
+        args: impl IntoIterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> anyhow::Result<Self> {
+        let args = Args::parse(args)?;
+        let path = args
+            .value("--config")
+            .map(PathBuf::from)
+            .or_else(|| env("MIDI_MONITOR_CONFIG").map(PathBuf::from));
+        // Only the default file may be missing
+        let named = path.is_some();
+        let file = path.unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_FILE));
+        let mut config = if named || file.exists() {
+            let mut config = Self::from_file(&file)?;
+            config.file = Some(file);
+            config
+        } else {
+            Self::default()
+        };
+
+        // The flag if given, else the environment variable, with where it
+        // came from for errors
+        let setting = |variable: &str, flag: &str| {
+            args.value(flag)
+                .map(|value| (flag.to_string(), value.to_string()))
+                .or_else(|| env(variable).map(|value| (variable.to_string(), value)))
+        };
+        let list = |variable: &str, separator: char, flag: &str| {
+            let values = args.values(flag);
+            if !values.is_empty() {
+                return Some((flag.to_string(), values));
+            }
+            let value = env(variable)?;
+            Some((
+                variable.to_string(),
+                value.split(separator).map(str::to_string).collect(),
+            ))
+        };
+
+        if let Some((from, value)) = setting("MIDI_MONITOR_ADDRESS", "--address") {
+            config.address = parse(&from, &value)?;
+        }
+        if let Some((from, value)) = setting("MIDI_MONITOR_PORT", "--port") {
+            config.port = parse(&from, &value)?;
+        }
+        if let Some((_, origins)) = list("MIDI_MONITOR_ALLOWED_ORIGINS", ',', "--allow-origin") {
+            config.allowed_origins = origins;
+        }
+        if let Some((from, value)) =
+            setting("MIDI_MONITOR_BROADCAST_CAPACITY", "--broadcast-capacity")
+        {
+            config.broadcast_capacity = parse(&from, &value)?;
+        }
+        if let Some((from, value)) = setting("MIDI_MONITOR_SIMULATION", "--simulation") {
+            config.simulation = parse(&from, &value)?;
+        }
+        if let Some((from, inputs)) = list("MIDI_MONITOR_INPUT", ';', "--input") {
+            config.inputs = inputs
+                .iter()
+                .map(|input| parse_selector(input))
+                .collect::<anyhow::Result<_>>()
+                .with_context(|| format!("Invalid {}", from))?;
+        }
+        if let Some((_, name)) = setting("MIDI_MONITOR_VIRTUAL_INPUT", "--virtual-input") {
+            config.virtual_input = Some(name);
+        }
+        if let Some((_, name)) = setting("MIDI_MONITOR_VIRTUAL_OUTPUT", "--virtual-output") {
+            config.virtual_output = Some(name);
+        }
+        if let Some((from, value)) = setting("MIDI_MONITOR_MPE_ZONES", "--mpe-zones") {
+            config.mpe_zones = Some(parse(&from, &value)?);
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        Self::from_toml(&text).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    pub fn from_toml(text: &str) -> anyhow::Result<Self> {
+        let config: Self = toml::from_str(text)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.broadcast_capacity == 0 {
+            bail!("broadcast_capacity must be at least 1");
+        }
+        for route in &self.routes {
+            route.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the routes into a config file, keeping its other settings,
+/// comments and formatting.
+pub fn save_routes(path: &Path, routes: &[Route]) -> anyhow::Result<()> {
+    let mut document: DocumentMut = match std::fs::read_to_string(path) {
+        Ok(text) => text
+            .parse()
+            .with_context(|| format!("Invalid config file {}", path.display()))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => DocumentMut::new(),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read config file {}", path.display()))
+        }
+    };
+
+    // Let toml lay the routes out as [[routes]] tables, then move them over
+    #[derive(Serialize)]
+    struct Routes<'a> {
+        routes: &'a [Route],
+    }
+    let mut saved: DocumentMut = toml::to_string(&Routes { routes })?.parse()?;
+    if let Some(mut routes) = saved.remove("routes") {
+        if let Some(tables) = routes.as_array_of_tables_mut() {
+            for table in tables.iter_mut() {
+                table.decor_mut().set_prefix("\n");
+            }
+        }
+        document.insert("routes", routes);
+    }
+
+    std::fs::write(path, document.to_string())
+        .with_context(|| format!("Failed to write config file {}", path.display()))
+}
+
+fn parse<T: FromStr>(from: &str, value: &str) -> anyhow::Result<T>
+where
+    T::Err: fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e| anyhow!("Invalid {} `{}`: {}", from, value, e))
+}
+
+fn deserialize_mpe_zones<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<MpeLayout>, D::Error> {
+    let zones = String::deserialize(deserializer)?;
+    zones.parse().map(Some).map_err(serde::de::Error::custom)
+}
+
+fn parse_selector(text: &str) -> anyhow::Result<PortSelector> {
+    text.parse().map_err(|e: String| anyhow!(e))
+}
+
+/// Flags that take a value; the repeatable ones collect every value given.
+const FLAGS: [&str; 10] = [
+    "--config",
+    "--address",
+    "--port",
+    "--allow-origin",
+    "--broadcast-capacity",
+    "--simulation",
+    "--input",
+    "--virtual-input",
+    "--virtual-output",
+    "--mpe-zones",
+];
+
+/// Command-line flags, as given.
+#[derive(Debug, Default)]
+struct Args {
+    flags: Vec<(&'static str, String)>,
+}
+
+impl Args {
+    /// Accepts `--flag value` and `--flag=value`.
+    fn parse(args: impl IntoIterator<Item = String>) -> anyhow::Result<Self> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let Some(&flag) = FLAGS.iter().find(|&&known| known == flag) else {
+                bail!("Unknown argument `{}`", flag);
+            };
+            let value = inline
+                .or_else(|| args.next())
+                .ok_or_else(|| anyhow!("{} needs a value", flag))?;
+            parsed.flags.push((flag, value));
+        }
+        Ok(parsed)
+    }
+
+    /// The last value given for a flag.
+    fn value(&self, flag: &str) -> Option<&str> {
+        self.flags
+            .iter()
+            .rev()
+            .find(|(name, _)| *name == flag)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn values(&self, flag: &str) -> Vec<String> {
+        self.flags
+            .iter()
+            .filter(|(name, _)| *name == flag)
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    /// Loads a config file holding `toml`, overridden by `env` and then
+    /// `flags`. `name` keeps tests running in parallel apart.
+    fn load(name: &str, toml: &str, env: &[(&str, &str)], flags: &[&str]) -> ServerConfig {
+        let path =
+            std::env::temp_dir().join(format!("midi-monitor-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, toml).unwrap();
+        let mut all = args(&["--config", path.to_str().unwrap()]);
+        all.extend(args(flags));
+        let config = ServerConfig::load_with(all, |name| {
+            env.iter()
+                .find(|(variable, _)| *variable == name)
+                .map(|(_, value)| value.to_string())
+        })
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        config
+    }
+
+    #[test]
+    fn test_inputs_precedence() {
+        let selectors = |config: ServerConfig| {
+            config
+                .inputs
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+        };
+        let file = "inputs = [\"exact:From File\"]\n";
+        let env = [("MIDI_MONITOR_INPUT", "regex:^Env;MPD218")];
+
+        let config = load("inputs", file, &[], &[]);
+        assert_eq!(selectors(config), vec!["exact:From File"]);
+        let config = load("inputs", file, &env, &[]);
+        assert_eq!(selectors(config), vec!["regex:^Env", "MPD218"]);
+        let config = load(
+            "inputs",
+            file,
+            &env,
+            &["--input=Keystation", "--input", "MPD"],
+        );
+        assert_eq!(selectors(config), vec!["Keystation", "MPD"]);
+    }
+
+    #[test]
+    fn test_virtual_ports_precedence() {
+        let file = "virtual_input = \"From File\"\n";
+        let env = [("MIDI_MONITOR_VIRTUAL_INPUT", "From Env")];
+
+        let config = load("virtual", file, &[], &[]);
+        assert_eq!(config.virtual_input.as_deref(), Some("From File"));
+        let config = load("virtual", file, &env, &[]);
+        assert_eq!(config.virtual_input.as_deref(), Some("From Env"));
+        assert_eq!(config.virtual_output, None);
+        let config = load(
+            "virtual",
+            file,
+            &env,
+            &["--virtual-input", "MIDI Monitor In"],
+        );
+        assert_eq!(config.virtual_input.as_deref(), Some("MIDI Monitor In"));
+
+        let env = [("MIDI_MONITOR_VIRTUAL_OUTPUT", "MIDI Monitor Out")];
+        let config = load("virtual", "", &env, &[]);
+        assert_eq!(config.virtual_output.as_deref(), Some("MIDI Monitor Out"));
+    }
+
+    #[test]
+    fn test_mpe_zones_precedence() {
+        let zones = |config: ServerConfig| config.mpe_zones.unwrap().to_string();
+        let file = "mpe_zones = \"lower=15\"\n";
+        let env = [("MIDI_MONITOR_MPE_ZONES", "lower=7,upper=7")];
+
+        let config = load("mpe", file, &[], &[]);
+        assert_eq!(zones(config), "MPE lower zone 15 members");
+        let config = load("mpe", file, &env, &[]);
+        assert_eq!(
+            zones(config),
+            "MPE lower zone 7 members, upper zone 7 members"
+        );
+        let config = load("mpe", file, &env, &["--mpe-zones=upper=15"]);
+        assert_eq!(zones(config), "MPE upper zone 15 members");
+    }
+
+    #[test]
+    fn test_address_precedence() {
+        let file = "address = \"0.0.0.0\"\n";
+        let env = [("MIDI_MONITOR_ADDRESS", "192.168.1.2")];
+
+        let config = load("address", "", &[], &[]);
+        assert_eq!(config.address.to_string(), "127.0.0.1");
+        let config = load("address", file, &[], &[]);
+        assert_eq!(config.address.to_string(), "0.0.0.0");
+        let config = load("address", file, &env, &[]);
+        assert_eq!(config.address.to_string(), "192.168.1.2");
+        let config = load("address", file, &env, &["--address", "::1"]);
+        assert_eq!(config.address.to_string(), "::1");
+    }
+
+    #[test]
+    fn test_port_precedence() {
+        let file = "port = 3100\n";
+        let env = [("MIDI_MONITOR_PORT", "3200")];
+
+        let config = load("port", "", &[], &[]);
+        assert_eq!(config.port, 3000);
+        let config = load("port", file, &[], &[]);
+        assert_eq!(config.port, 3100);
+        let config = load("port", file, &env, &[]);
+        assert_eq!(config.port, 3200);
+        let config = load("port", file, &env, &["--port=0"]);
+        assert_eq!(config.port, 0);
+    }
+
+    #[test]
+    fn test_allowed_origins_precedence() {
+        let file = "allowed_origins = [\"http://studio.local:3001\"]\n";
+        let env = [("MIDI_MONITOR_ALLOWED_ORIGINS", "http://a:1,http://b:2")];
+
+        let config = load("origins", "", &[], &[]);
+        assert_eq!(config.allowed_origins, vec!["http://localhost:3001"]);
+        let config = load("origins", file, &[], &[]);
+        assert_eq!(config.allowed_origins, vec!["http://studio.local:3001"]);
+        let config = load("origins", file, &env, &[]);
+        assert_eq!(config.allowed_origins, vec!["http://a:1", "http://b:2"]);
+        let config = load("origins", file, &env, &["--allow-origin", "*"]);
+        assert_eq!(config.allowed_origins, vec!["*"]);
+    }
+
+    #[test]
+    fn test_broadcast_capacity_precedence() {
+        let file = "broadcast_capacity = 500\n";
+        let env = [("MIDI_MONITOR_BROADCAST_CAPACITY", "800")];
+
+        let config = load("capacity", "", &[], &[]);
+        assert_eq!(config.broadcast_capacity, 100);
+        let config = load("capacity", file, &[], &[]);
+        assert_eq!(config.broadcast_capacity, 500);
+        let config = load("capacity", file, &env, &[]);
+        assert_eq!(config.broadcast_capacity, 800);
+        let config = load("capacity", file, &env, &["--broadcast-capacity", "1000"]);
+        assert_eq!(config.broadcast_capacity, 1000);
+    }
+
+    #[test]
+    fn test_simulation_precedence() {
+        let file = "simulation = false\n";
+        let env = [("MIDI_MONITOR_SIMULATION", "true")];
+
+        let config = load("simulation", "", &[], &[]);
+        assert!(config.simulation);
+        let config = load("simulation", file, &[], &[]);
+        assert!(!config.simulation);
+        let config = load("simulation", file, &env, &[]);
+        assert!(config.simulation);
+        let config = load("simulation", file, &env, &["--simulation", "false"]);
+        assert!(!config.simulation);
+    }
+
+    #[test]
+    fn test_save_routes_keeps_other_settings() {
+        let dir = std::env::temp_dir().join(format!("midi-monitor-routes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let settings = "# The studio keyboard\ninputs = [\"Keystation\"]  # by name\n\n\
+                        virtual_output = \"MIDI Monitor Out\"\n";
+        std::fs::write(&path, settings).unwrap();
+
+        let routes = vec![Route {
+            from: "input:Keystation 49".to_string(),
+            to: "output:FluidSynth".to_string(),
+            channels: vec![0],
+            types: Vec::new(),
+            notes: Some([36, 59]),
+        }];
+        save_routes(&path, &routes).unwrap();
+        let config = ServerConfig::from_file(&path).unwrap();
+        assert_eq!(config.routes, routes);
+        assert_eq!(config.inputs[0].to_string(), "Keystation");
+        assert_eq!(config.virtual_output.as_deref(), Some("MIDI Monitor Out"));
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with(settings), "{}", text);
+
+        // Saving again replaces the routes rather than adding to them
+        save_routes(&path, &[]).unwrap();
+        let config = ServerConfig::from_file(&path).unwrap();
+        assert!(config.routes.is_empty());
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with(settings), "{}", text);
+
+        // Without a file to save to, edits aren't written anywhere
+        let config = ServerConfig::load_with(args(&[]), |_| None).unwrap();
+        assert_eq!(config.file, None);
+        let config =
+            ServerConfig::load_with(args(&["--config", path.to_str().unwrap()]), |_| None).unwrap();
+        assert_eq!(config.file, Some(path));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(ServerConfig::from_toml("inputs = [\"regex:(\"]").is_err());
+        assert!(ServerConfig::from_toml("inptu = \"Keystation\"").is_err());
+        assert!(ServerConfig::from_toml(
+            "[[routes]]\nfrom = \"simulation\"\nto = \"output:Synth\"\nchannels = [16]"
+        )
+        .is_err());
+        assert!(ServerConfig::load_with(args(&["--input"]), |_| None).is_err());
+        assert!(ServerConfig::load_with(args(&["--verbose"]), |_| None).is_err());
+        assert!(
+            ServerConfig::load_with(args(&["--config", "/nonexistent.toml"]), |_| None).is_err()
+        );
+        assert!(ServerConfig::from_toml("broadcast_capacity = 0").is_err());
+        assert!(ServerConfig::from_toml("mpe_zones = \"middle=3\"").is_err());
+        assert!(ServerConfig::load_with(args(&["--mpe-zones", "lower=16"]), |_| None).is_err());
+        assert!(ServerConfig::load_with(args(&["--port", "70000"]), |_| None).is_err());
+        assert!(ServerConfig::load_with(args(&["--simulation", "off"]), |_| None).is_err());
+        let env = |name: &str| (name == "MIDI_MONITOR_ADDRESS").then(|| "localhost".to_string());
+        let error = ServerConfig::load_with(args(&[]), env).unwrap_err();
+        assert!(
+            error.to_string().contains("MIDI_MONITOR_ADDRESS"),
+            "{}",
+            error
+        );
+    }
+}
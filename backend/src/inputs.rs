@@ -0,0 +1,224 @@
+//! Opens MIDI input ports and keeps their connections, any number at once.
+
+use crate::outputs;
+use crate::ports::{self, NamedPort};
+use crate::selector::{is_selected, select_ports, PortSelector};
+use crate::watcher::PortChanges;
+use crate::{SharedState, SourceClock};
+use midi_protocol::{EventSource, MidiMessage, PortDirection, PortInfo, ServerEvent};
+use midir::{MidiInput, MidiInputConnection, MidiInputPort};
+use tracing::{info, warn};
+
+/// Source id of the events the simulation makes up.
+pub const SIMULATION_ID: &str = "simulation";
+
+pub fn simulation_source() -> EventSource {
+    EventSource {
+        id: SIMULATION_ID.to_string(),
+        name: "Simulation".to_string(),
+    }
+}
+
+/// Switches to the ports one selector picks, keeping it to match ports
+/// plugged in later. Returns the ports now open, none if nothing matched.
+pub fn switch_inputs(state: &SharedState, selector: PortSelector) -> anyhow::Result<Vec<PortInfo>> {
+    let ports = select_inputs(state, std::slice::from_ref(&selector))?;
+    if !ports.is_empty() {
+        state.lock().unwrap().selectors = vec![selector];
+    }
+    Ok(ports)
+}
+
+/// Opens every port the selectors pick, keeping ports already open and
+/// closing the rest, and tells clients. Returns the ports now open; if none
+/// match, returns nothing and leaves the current inputs alone.
+pub fn select_inputs(
+    state: &SharedState,
+    selectors: &[PortSelector],
+) -> anyhow::Result<Vec<PortInfo>> {
+    let midi_in = MidiInput::new("midir reading input")?;
+    let available = ports::named_ports(&midi_in, PortDirection::Input)?;
+    let mut chosen = select_ports(selectors, &available);
+    // Opening a virtual port of our own would feed everything back in
+    let virtual_ports = state.lock().unwrap().virtual_ports.clone();
+    chosen.retain(|&index| {
+        !virtual_ports
+            .iter()
+            .any(|name| available[index].name.contains(name.as_str()))
+    });
+    if chosen.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let input_connections = state.lock().unwrap().input_connections.clone();
+    let mut connections = input_connections.lock().unwrap();
+    let mut inputs = Vec::new();
+    for port in chosen.into_iter().map(|index| &available[index]) {
+        if !connections.contains_key(&port.id) {
+            match open_input(state, port) {
+                Ok(connection) => {
+                    connections.insert(port.id.clone(), connection);
+                }
+                Err(e) => {
+                    warn!("Failed to open {}: {}", port.name, e);
+                    continue;
+                }
+            }
+        }
+        inputs.push(PortInfo {
+            id: port.id.clone(),
+            name: port.name.clone(),
+            direction: PortDirection::Input,
+            connected: true,
+        });
+    }
+    if inputs.is_empty() {
+        return Ok(inputs);
+    }
+    // Closing waits for the connection's callback, which may be waiting for
+    // the state lock, so it mustn't be held here
+    connections.retain(|id, _| inputs.iter().any(|input| input.id == *id));
+    drop(connections);
+
+    state.lock().unwrap().set_inputs(inputs.clone());
+    Ok(inputs)
+}
+
+/// Tells clients about ports that came and went, closes inputs and outputs
+/// whose device is gone, and opens newly plugged ports the current selection picks.
+pub fn handle_port_changes(state: &SharedState, changes: &PortChanges) -> anyhow::Result<()> {
+    let (selectors, input_connections, output_connections) = {
+        let mut state_guard = state.lock().unwrap();
+        for port in &changes.disconnected {
+            info!("MIDI device disconnected: {}", port.name);
+            let wire = state_guard
+                .stamped(ServerEvent::DeviceDisconnected(port.clone()))
+                .with_description(format!("Disconnected: {}", port.name));
+            let _ = state_guard.event_sender.send(wire);
+        }
+        for port in &changes.connected {
+            info!("MIDI device connected: {}", port.name);
+            let wire = state_guard
+                .stamped(ServerEvent::DeviceConnected(port.clone()))
+                .with_description(format!("Connected: {}", port.name));
+            let _ = state_guard.event_sender.send(wire);
+        }
+        if !changes.connected.is_empty() {
+            state_guard.router.retry_unavailable();
+        }
+        (
+            state_guard.selectors.clone(),
+            state_guard.input_connections.clone(),
+            state_guard.output_connections.clone(),
+        )
+    };
+
+    let gone = |id: &str| changes.disconnected.iter().any(|port| port.id == id);
+    input_connections.lock().unwrap().retain(|id, _| !gone(id));
+    output_connections.lock().unwrap().retain(|id, _| !gone(id));
+    let mut state_guard = state.lock().unwrap();
+    if state_guard.inputs.iter().any(|input| gone(&input.id)) {
+        let mut inputs = state_guard.inputs.clone();
+        inputs.retain(|input| !gone(&input.id));
+        state_guard.set_inputs(inputs);
+    }
+    drop(state_guard);
+    if !changes.connected.is_empty() {
+        outputs::connect_routes(state);
+    }
+
+    let wanted = changes.connected.iter().any(|port| {
+        port.direction == PortDirection::Input && is_selected(&selectors, &port.id, &port.name)
+    });
+    if wanted {
+        select_inputs(state, &selectors)?;
+    }
+    Ok(())
+}
+
+/// Connects to a port, publishing everything it receives tagged with it.
+fn open_input(
+    state: &SharedState,
+    port: &NamedPort<MidiInputPort>,
+) -> anyhow::Result<MidiInputConnection<()>> {
+    info!("Connecting to MIDI device: {}", port.name);
+
+    // Each connection takes its own client; ports stay valid across clients
+    let midi_in = MidiInput::new("midir reading input")?;
+    let source = EventSource {
+        id: port.id.clone(),
+        name: port.name.clone(),
+    };
+    midi_in
+        .connect(
+            &port.port,
+            "midir-read-input",
+            input_callback(state.clone(), source),
+            (),
+        )
+        .map_err(|e| anyhow::anyhow!("Failed to connect to MIDI device: {}", e))
+}
+
+/// A midir input callback publishing everything received, tagged with the
+/// source, on the source's own device clock.
+pub fn input_callback(
+    state: SharedState,
+    source: EventSource,
+) -> impl FnMut(u64, &[u8], &mut ()) + Send + 'static {
+    let mut source_clock = SourceClock::new();
+    move |stamp, message, _| {
+        let mut state_guard = state.lock().unwrap();
+        let now_us = state_guard.now_us();
+        let timestamp_us = source_clock.map_stamp(stamp, now_us);
+        match MidiMessage::parse(message) {
+            Ok(midi_message) => state_guard.publish_at(midi_message, timestamp_us, &source),
+            Err(malformed) => state_guard.publish_malformed_at(malformed, timestamp_us, &source),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AppState;
+    use std::sync::{Arc, Mutex};
+
+    fn port(name: &str) -> PortInfo {
+        PortInfo {
+            id: format!("input:{}", name),
+            name: name.to_string(),
+            direction: PortDirection::Input,
+            connected: false,
+        }
+    }
+
+    #[test]
+    fn test_unplugged_input_is_dropped() {
+        let mut app_state = AppState::new(100);
+        app_state.inputs = vec![port("Keystation 49"), port("MPD218")];
+        let mut receiver = app_state.event_sender.subscribe();
+        let state = Arc::new(Mutex::new(app_state));
+
+        // Midi Through isn't picked by default, so nothing gets opened
+        let changes = PortChanges {
+            connected: vec![port("Midi Through Port-0")],
+            disconnected: vec![port("Keystation 49")],
+        };
+        handle_port_changes(&state, &changes).unwrap();
+
+        let events: Vec<ServerEvent> = std::iter::from_fn(|| receiver.try_recv().ok())
+            .map(|wire| wire.event)
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                ServerEvent::DeviceDisconnected(port("Keystation 49")),
+                ServerEvent::DeviceConnected(port("Midi Through Port-0")),
+                ServerEvent::Inputs {
+                    ports: vec![port("MPD218")]
+                },
+            ]
+        );
+        assert_eq!(state.lock().unwrap().inputs, vec![port("MPD218")]);
+    }
+}
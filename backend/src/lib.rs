@@ -1,122 +1,270 @@
+use anyhow::Context;
 use axum::{
     extract::{
-        ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        Path, State,
     },
     http::StatusCode,
-    response::IntoResponse,
-    routing::get,
-    Router,
+    response::{IntoResponse, Response},
+    routing::{delete, get, put},
+    Json, Router,
 };
 use futures_util::{sink::SinkExt, stream::StreamExt};
-use midir::{MidiInput, MidiInputConnection};
-use serde::{Deserialize, Serialize};
+pub use midi_protocol::{
+    ClientMessage, ClientRequest, CommandReply, Describer, EventFilter, EventSource,
+    MalformedMessage, MidiMessage, MpeLayout, PortDirection, PortInfo, ServerEvent, WireMessage,
+    PROTOCOL_VERSION,
+};
+use midir::{MidiInputConnection, MidiOutputConnection};
+use serde::Deserialize;
 use std::{
+    collections::HashMap,
+    future::Future,
+    net::SocketAddr,
+    path::PathBuf,
     sync::{Arc, Mutex},
-    time::Duration,
+    time::{Duration, Instant},
 };
-use tokio::sync::broadcast;
-use tower_http::cors::CorsLayer;
-use tracing::{error, info};
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MidiMessage {
-    pub message_type: String,
-    pub note: Option<u8>,
-    pub velocity: Option<u8>,
-    pub control: Option<u8>,
-    pub value: Option<u8>,
-}
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::JoinHandle;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::{debug, error, info, warn};
+
+mod clock;
+mod config;
+mod inputs;
+mod mpe;
+mod mtc;
+mod outputs;
+mod parameters;
+mod ports;
+mod routing;
+mod selector;
+mod session;
+mod stream;
+mod timing;
+mod virtual_ports;
+mod watcher;
+
+pub use clock::ClockTracker;
+pub use config::ServerConfig;
+pub use mpe::MpeTracker;
+pub use mtc::MtcAssembler;
+pub use parameters::ParameterTracker;
+pub use ports::list_ports;
+pub use routing::Route;
+pub use selector::PortSelector;
+pub use stream::{StreamDecoder, StreamEncoder};
+pub use timing::SourceClock;
 
-impl MidiMessage {
-    fn from_raw_message(message: &[u8]) -> Option<Self> {
-        if message.is_empty() {
-            return None;
-        }
+type SharedState = Arc<Mutex<AppState>>;
 
-        let status = message[0];
-        let message_type = status & 0xF0;
-
-        match message_type {
-            0x90 => {
-                // Note On
-                if message.len() >= 3 {
-                    let velocity = message[2];
-                    // Velocity 0 is actually Note Off
-                    if velocity == 0 {
-                        Some(MidiMessage {
-                            message_type: "NoteOff".to_string(),
-                            note: Some(message[1]),
-                            velocity: Some(velocity),
-                            control: None,
-                            value: None,
-                        })
-                    } else {
-                        Some(MidiMessage {
-                            message_type: "NoteOn".to_string(),
-                            note: Some(message[1]),
-                            velocity: Some(velocity),
-                            control: None,
-                            value: None,
-                        })
-                    }
-                } else {
-                    None
-                }
-            }
-            0x80 => {
-                // Note Off
-                if message.len() >= 3 {
-                    Some(MidiMessage {
-                        message_type: "NoteOff".to_string(),
-                        note: Some(message[1]),
-                        velocity: Some(message[2]),
-                        control: None,
-                        value: None,
-                    })
-                } else {
-                    None
-                }
-            }
-            0xB0 => {
-                // Control Change
-                if message.len() >= 3 {
-                    Some(MidiMessage {
-                        message_type: "ControlChange".to_string(),
-                        note: None,
-                        velocity: None,
-                        control: Some(message[1]),
-                        value: Some(message[2]),
-                    })
-                } else {
-                    None
-                }
-            }
-            _ => {
-                // Other message types
-                Some(MidiMessage {
-                    message_type: format!("Unknown({})", message_type),
-                    note: None,
-                    velocity: None,
-                    control: None,
-                    value: None,
-                })
-            }
-        }
-    }
+/// Runs midir calls, which list ports, open connections and join ALSA
+/// threads, off the async worker threads.
+async fn blocking<T: Send + 'static>(
+    work: impl FnOnce() -> anyhow::Result<T> + Send + 'static,
+) -> anyhow::Result<T> {
+    tokio::task::spawn_blocking(work).await?
 }
 
-type SharedState = Arc<Mutex<AppState>>;
+/// Decoding state that belongs to one input, so two devices' bank selects
+/// and RPN/NRPN edits don't get mixed up.
+#[derive(Clone, Default)]
+struct SourceState {
+    describer: Describer,
+    parameters: ParameterTracker,
+}
 
 #[derive(Clone)]
 struct AppState {
-    midi_sender: broadcast::Sender<MidiMessage>,
+    event_sender: broadcast::Sender<WireMessage>,
+    /// Per-input state, by source id.
+    sources: HashMap<String, SourceState>,
+    // A rig has one clock master and one MPE layout, whichever input they
+    // arrive on
+    clock: ClockTracker,
+    mpe: MpeTracker,
+    timecode: MtcAssembler,
+    /// The input selection hot-plugged ports are matched against.
+    selectors: Vec<PortSelector>,
+    /// The MIDI input ports currently open.
+    inputs: Vec<PortInfo>,
+    /// Open connections by port id. Kept apart from the rest of the state:
+    /// closing a connection waits for its callback, which needs the state
+    /// lock.
+    input_connections: Arc<Mutex<HashMap<String, MidiInputConnection<()>>>>,
+    /// Output connections by port id, opened when routes are set or when
+    /// first sent to.
+    output_connections: Arc<Mutex<HashMap<String, MidiOutputConnection>>>,
+    /// Names of the virtual ports published for other applications. The
+    /// monitor never opens them itself, and doesn't simulate while they're
+    /// up so nothing made up reaches another application.
+    virtual_ports: Vec<String>,
+    /// Virtual output passing on the MIDI input.
+    thru: Option<virtual_ports::Thru>,
+    /// Whether to simulate input while there is none.
+    simulation: bool,
+    router: routing::Router,
+    /// Where route edits are saved.
+    config_file: Option<PathBuf>,
+    /// Set to `true` to stop the server; every task and WebSocket client
+    /// holds a receiver until it has wound up.
+    shutdown: watch::Sender<bool>,
+    started: Instant,
 }
 
 impl AppState {
-    fn new() -> Self {
-        let (midi_sender, _) = broadcast::channel(100);
-        Self { midi_sender }
+    fn new(broadcast_capacity: usize) -> Self {
+        let (event_sender, _) = broadcast::channel(broadcast_capacity);
+        Self {
+            event_sender,
+            sources: HashMap::new(),
+            clock: ClockTracker::new(),
+            mpe: MpeTracker::new(),
+            timecode: MtcAssembler::new(),
+            selectors: Vec::new(),
+            inputs: Vec::new(),
+            input_connections: Arc::new(Mutex::new(HashMap::new())),
+            output_connections: Arc::new(Mutex::new(HashMap::new())),
+            virtual_ports: Vec::new(),
+            thru: None,
+            simulation: true,
+            router: routing::Router::default(),
+            config_file: None,
+            shutdown: watch::channel(false).0,
+            started: Instant::now(),
+        }
+    }
+
+    /// Microseconds since the server started.
+    fn now_us(&self) -> u64 {
+        self.started.elapsed().as_micros() as u64
+    }
+
+    /// Derived state a newly connected client should start from.
+    fn snapshot(&self) -> Vec<ServerEvent> {
+        let mut events = vec![
+            ServerEvent::Transport(self.clock.state().clone()),
+            ServerEvent::Timecode(self.timecode.state().clone()),
+            ServerEvent::MpeLayout(self.mpe.layout().clone()),
+        ];
+        if !self.inputs.is_empty() {
+            events.push(ServerEvent::Inputs {
+                ports: self.inputs.clone(),
+            });
+        }
+        events
+    }
+
+    /// Wraps an event for the wire, stamped with the current time.
+    fn stamped(&self, event: ServerEvent) -> WireMessage {
+        WireMessage::from(event).with_timestamps(self.now_us(), timing::unix_time_us())
+    }
+
+    fn broadcast(&self, event: ServerEvent) {
+        let _ = self.event_sender.send(self.stamped(event));
+    }
+
+    /// Records which inputs are open and tells clients.
+    fn set_inputs(&mut self, inputs: Vec<PortInfo>) {
+        let names: Vec<&str> = inputs.iter().map(|input| input.name.as_str()).collect();
+        let description = if names.is_empty() {
+            "Inputs: none".to_string()
+        } else {
+            format!("Inputs: {}", names.join(", "))
+        };
+        let wire = self
+            .stamped(ServerEvent::Inputs {
+                ports: inputs.clone(),
+            })
+            .with_description(description);
+        self.inputs = inputs;
+        let _ = self.event_sender.send(wire);
+    }
+
+    /// Publishes a message from the simulation.
+    fn publish(&mut self, message: MidiMessage) {
+        let now_us = self.now_us();
+        self.publish_at(message, now_us, &inputs::simulation_source());
+    }
+
+    /// Broadcasts a MIDI message with its description, followed by any
+    /// transport change, parameter edit, MPE update or timecode it completes.
+    /// All of them carry the message's source and source timestamp, which
+    /// also drives the clock and timecode trackers.
+    fn publish_at(&mut self, message: MidiMessage, timestamp_us: u64, source: &EventSource) {
+        let captured_at_us = timing::unix_time_us();
+        let stamped = |event| {
+            WireMessage::from(event)
+                .with_timestamps(timestamp_us, captured_at_us)
+                .with_source(source.clone())
+        };
+        let source_state = self.sources.entry(source.id.clone()).or_default();
+        let description = source_state.describer.describe(&message);
+        debug!("{}: {}", source.name, description);
+        let transport = self.clock.handle(&message, timestamp_us);
+        let parameter = source_state.parameters.handle(&message);
+        let layout = parameter
+            .as_ref()
+            .and_then(|change| self.mpe.handle_parameter(change));
+        let expressions = self.mpe.handle(&message);
+        let timecode = self.timecode.handle(&message, timestamp_us);
+
+        if let Some(thru) = &self.thru {
+            if source.id != inputs::SIMULATION_ID {
+                thru.send(&message);
+            }
+        }
+        for output in self.router.destinations(&source.id, &message) {
+            // Opened when the routes were set; connecting here would hold
+            // the lock inside the input callback
+            if let Err(e) = outputs::send_to(&self.output_connections, &output, &message) {
+                warn!("Route to {} is down until the ports change: {}", output, e);
+                self.router.mark_unavailable(&output);
+            }
+        }
+        let wire = stamped(ServerEvent::Midi(message)).with_description(description);
+        if let Err(e) = self.event_sender.send(wire) {
+            error!("Failed to send MIDI message: {}", e);
+        }
+        let mut derived = Vec::new();
+        if let Some(transport) = transport {
+            derived.push(stamped(ServerEvent::Transport(transport)));
+        }
+        if let Some(parameter) = parameter {
+            let description = format!("Ch{} {}", parameter.channel + 1, parameter);
+            debug!("{}", description);
+            derived.push(stamped(ServerEvent::Parameter(parameter)).with_description(description));
+        }
+        if let Some(layout) = layout {
+            derived.push(stamped(ServerEvent::MpeLayout(layout)));
+        }
+        for expression in expressions {
+            derived.push(stamped(ServerEvent::NoteExpression(expression)));
+        }
+        if let Some(timecode) = timecode {
+            derived.push(stamped(ServerEvent::Timecode(timecode)));
+        }
+        for wire in derived {
+            let _ = self.event_sender.send(wire);
+        }
+    }
+
+    /// Broadcasts input that couldn't be decoded, so flaky devices show up
+    /// in the log instead of being silently dropped.
+    fn publish_malformed_at(
+        &mut self,
+        malformed: MalformedMessage,
+        timestamp_us: u64,
+        source: &EventSource,
+    ) {
+        let description = malformed.to_string();
+        warn!("{}: {}", source.name, description);
+        let wire = WireMessage::from(ServerEvent::Malformed(malformed))
+            .with_timestamps(timestamp_us, timing::unix_time_us())
+            .with_source(source.clone())
+            .with_description(description);
+        let _ = self.event_sender.send(wire);
     }
 }
 
@@ -124,33 +272,86 @@ async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(state): State<SharedState>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+    // Subscribed before the upgrade, so shutdown waits for this client too
+    let shutdown = state.lock().unwrap().shutdown.subscribe();
+    ws.on_upgrade(|socket| handle_socket(socket, state, shutdown))
 }
 
-async fn handle_socket(socket: WebSocket, state: SharedState) {
+async fn handle_socket(socket: WebSocket, state: SharedState, mut shutdown: watch::Receiver<bool>) {
     let (mut sender, mut receiver) = socket.split();
-    let mut midi_receiver = {
+    let (mut event_receiver, snapshot) = {
         let state_guard = state.lock().unwrap();
-        state_guard.midi_sender.subscribe()
+        let snapshot: Vec<WireMessage> = state_guard
+            .snapshot()
+            .into_iter()
+            .map(|event| state_guard.stamped(event))
+            .collect();
+        (state_guard.event_sender.subscribe(), snapshot)
     };
 
-    // Task to forward MIDI messages to WebSocket
-    let send_task = tokio::spawn(async move {
-        while let Ok(midi_message) = midi_receiver.recv().await {
-            if let Ok(json) = serde_json::to_string(&midi_message) {
-                if sender.send(Message::Text(json)).await.is_err() {
-                    break;
+    // Replies meant for this client alone
+    let (reply_sender, mut replies) = mpsc::unbounded_channel::<WireMessage>();
+    let session = Arc::new(Mutex::new(session::Session::default()));
+    let send_session = session.clone();
+
+    // Task to forward events to WebSocket, starting with the current state
+    let mut send_task = tokio::spawn(async move {
+        let mut next = Ok(snapshot);
+        loop {
+            match next {
+                Ok(messages) => {
+                    for message in messages {
+                        {
+                            let mut session = send_session.lock().unwrap();
+                            if !session.wants(&message) {
+                                continue;
+                            }
+                            session.record(&message);
+                        }
+                        if let Ok(json) = serde_json::to_string(&message) {
+                            if sender.send(Message::Text(json)).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
                 }
+                // Dense clock or aftertouch streams can outrun a slow client;
+                // drop what it missed rather than disconnecting it
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("WebSocket client lagged, skipped {} events", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
             }
+            next = tokio::select! {
+                event = event_receiver.recv() => event.map(|message| vec![message]),
+                Some(reply) = replies.recv() => Ok(vec![reply]),
+                _ = shutdown.wait_for(|&stop| stop) => break,
+            };
         }
+        let close = CloseFrame {
+            code: close_code::AWAY,
+            reason: "Server shutting down".into(),
+        };
+        let _ = sender.send(Message::Close(Some(close))).await;
     });
 
     // Task to handle incoming WebSocket messages
-    let recv_task = tokio::spawn(async move {
+    let mut recv_task = tokio::spawn(async move {
         while let Some(msg) = receiver.next().await {
             match msg {
-                Ok(Message::Text(_)) => {
-                    // Echo or handle client messages if needed
+                Ok(Message::Text(text)) => {
+                    // Sending, panicking and switching inputs call into midir
+                    let (state, session) = (state.clone(), session.clone());
+                    let reply = tokio::task::spawn_blocking(move || {
+                        session::handle_request(&state, &session, &text)
+                    })
+                    .await;
+                    match reply {
+                        Ok(reply) => {
+                            let _ = reply_sender.send(reply);
+                        }
+                        Err(e) => error!("WebSocket command failed: {}", e),
+                    }
                 }
                 Ok(Message::Close(_)) => break,
                 Err(_) => break,
@@ -159,10 +360,10 @@ async fn handle_socket(socket: WebSocket, state: SharedState) {
         }
     });
 
-    // Wait for either task to complete
+    // Whichever side finishes first ends the connection
     tokio::select! {
-        _ = send_task => {},
-        _ = recv_task => {},
+        _ = &mut send_task => recv_task.abort(),
+        _ = &mut recv_task => send_task.abort(),
     }
 }
 
@@ -170,34 +371,129 @@ async fn health_check() -> impl IntoResponse {
     (StatusCode::OK, "MIDI Backend is running!")
 }
 
-fn setup_midi_input(state: SharedState) -> anyhow::Result<Option<MidiInputConnection<()>>> {
-    let midi_in = MidiInput::new("midir reading input")?;
-    let in_ports = midi_in.ports();
+/// Every MIDI input and output port the host sees, and which are open.
+async fn ports_handler(State(state): State<SharedState>) -> impl IntoResponse {
+    let connected: Vec<String> = {
+        let state_guard = state.lock().unwrap();
+        let outputs = state_guard.output_connections.lock().unwrap();
+        state_guard
+            .inputs
+            .iter()
+            .map(|port| port.id.clone())
+            .chain(outputs.keys().cloned())
+            .collect()
+    };
+    match blocking(move || list_ports(&connected)).await {
+        Ok(ports) => Json(ports).into_response(),
+        Err(e) => {
+            error!("Failed to list MIDI ports: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SelectInputRequest {
+    selector: PortSelector,
+}
 
-    if in_ports.is_empty() {
-        info!("No MIDI input devices found, will use simulation mode");
-        return Ok(None);
+/// Switches to the input ports a selector matches without restarting.
+async fn select_input_handler(
+    State(state): State<SharedState>,
+    Json(request): Json<SelectInputRequest>,
+) -> impl IntoResponse {
+    let selector = request.selector.to_string();
+    match blocking(move || inputs::switch_inputs(&state, request.selector)).await {
+        Ok(ports) if ports.is_empty() => (
+            StatusCode::NOT_FOUND,
+            format!("No MIDI input matches `{}`", selector),
+        )
+            .into_response(),
+        Ok(ports) => Json(ports).into_response(),
+        Err(e) => {
+            error!("Failed to switch MIDI input: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
     }
+}
 
-    let in_port = &in_ports[0];
-    info!("Connecting to MIDI device: {}", midi_in.port_name(in_port)?);
-
-    let state_clone = state.clone();
-    let _conn_in = midi_in.connect(
-        in_port,
-        "midir-read-input",
-        move |_stamp, message, _| {
-            if let Some(midi_message) = MidiMessage::from_raw_message(message) {
-                let state_guard = state_clone.lock().unwrap();
-                if let Err(e) = state_guard.midi_sender.send(midi_message) {
-                    error!("Failed to send MIDI message: {}", e);
-                }
-            }
-        },
-        (),
-    )?;
+async fn routes_handler(State(state): State<SharedState>) -> impl IntoResponse {
+    Json(state.lock().unwrap().router.routes().to_vec())
+}
 
-    Ok(Some(_conn_in))
+/// Replaces the whole routing matrix.
+async fn set_routes_handler(
+    State(state): State<SharedState>,
+    Json(routes): Json<Vec<Route>>,
+) -> Response {
+    update_routes(&state, |current| {
+        *current = routes;
+        Ok(())
+    })
+    .await
+}
+
+async fn add_route_handler(State(state): State<SharedState>, Json(route): Json<Route>) -> Response {
+    update_routes(&state, |routes| {
+        routes.push(route);
+        Ok(())
+    })
+    .await
+}
+
+async fn delete_route_handler(
+    State(state): State<SharedState>,
+    Path(index): Path<usize>,
+) -> Response {
+    update_routes(&state, |routes| {
+        if index >= routes.len() {
+            return Err((StatusCode::NOT_FOUND, format!("No route {}", index)));
+        }
+        routes.remove(index);
+        Ok(())
+    })
+    .await
+}
+
+/// Applies an edit to the routes, validates them, opens their outputs and
+/// saves them to the config file. Responds with the routes now in use.
+async fn update_routes(
+    state: &SharedState,
+    edit: impl FnOnce(&mut Vec<Route>) -> Result<(), (StatusCode, String)>,
+) -> Response {
+    let (routes, config_file) = {
+        let mut state_guard = state.lock().unwrap();
+        let mut routes = state_guard.router.routes().to_vec();
+        if let Err(error) = edit(&mut routes) {
+            return error.into_response();
+        }
+        if let Err(e) = routes.iter().try_for_each(Route::validate) {
+            return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+        }
+        state_guard.router.set_routes(routes.clone());
+        (routes, state_guard.config_file.clone())
+    };
+    info!("Routes updated: {} in use", routes.len());
+    let connect_state = state.clone();
+    let connected = blocking(move || {
+        outputs::connect_routes(&connect_state);
+        Ok(())
+    })
+    .await;
+    if let Err(e) = connected {
+        error!("Failed to open route outputs: {}", e);
+    }
+    if let Some(path) = config_file {
+        if let Err(e) = config::save_routes(&path, &routes) {
+            error!("Failed to save routes: {:#}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Routes updated but not saved: {:#}", e),
+            )
+                .into_response();
+        }
+    }
+    Json(routes).into_response()
 }
 
 async fn simulate_midi_events(state: SharedState) {
@@ -207,133 +503,497 @@ async fn simulate_midi_events(state: SharedState) {
     loop {
         tokio::time::sleep(Duration::from_millis(500)).await;
 
+        // Real input takes over from the simulation while there is any
+        {
+            let state_guard = state.lock().unwrap();
+            if !state_guard.simulation
+                || !state_guard.inputs.is_empty()
+                || !state_guard.virtual_ports.is_empty()
+            {
+                continue;
+            }
+        }
+
         let note = c_major_scale[current_note % c_major_scale.len()];
 
         // Send Note On
-        let note_on = MidiMessage {
-            message_type: "NoteOn".to_string(),
-            note: Some(note),
-            velocity: Some(64),
-            control: None,
-            value: None,
+        let note_on = MidiMessage::NoteOn {
+            channel: 0,
+            note,
+            velocity: 64,
         };
 
-        {
-            let state_guard = state.lock().unwrap();
-            if let Err(e) = state_guard.midi_sender.send(note_on) {
-                error!("Failed to send simulated Note On: {}", e);
-            }
-        }
+        state.lock().unwrap().publish(note_on);
 
         tokio::time::sleep(Duration::from_millis(400)).await;
 
         // Send Note Off
-        let note_off = MidiMessage {
-            message_type: "NoteOff".to_string(),
-            note: Some(note),
-            velocity: Some(0),
-            control: None,
-            value: None,
+        let note_off = MidiMessage::NoteOff {
+            channel: 0,
+            note,
+            velocity: 0,
         };
 
-        {
-            let state_guard = state.lock().unwrap();
-            if let Err(e) = state_guard.midi_sender.send(note_off) {
-                error!("Failed to send simulated Note Off: {}", e);
-            }
-        }
+        state.lock().unwrap().publish(note_off);
 
         current_note += 1;
     }
 }
 
-// Export the start_server function for use by Tauri
-pub async fn start_server() -> anyhow::Result<()> {
-    let state = Arc::new(Mutex::new(AppState::new()));
+/// Reports MIDI clock and timecode as lost when they stop arriving.
+async fn watch_timeouts(state: SharedState) {
+    let mut interval = tokio::time::interval(Duration::from_millis(50));
+    loop {
+        interval.tick().await;
+        let mut state_guard = state.lock().unwrap();
+        let now_us = state_guard.now_us();
+        if let Some(transport) = state_guard.clock.check_timeout(now_us) {
+            info!("MIDI clock lost");
+            state_guard.broadcast(ServerEvent::Transport(transport));
+        }
+        if let Some(timecode) = state_guard.timecode.check_timeout(now_us) {
+            info!("MIDI Time Code stopped");
+            state_guard.broadcast(ServerEvent::Timecode(timecode));
+        }
+    }
+}
 
-    // Try to set up real MIDI input
-    let _midi_connection = setup_midi_input(state.clone())?;
+/// Runs a background task until the server shuts down.
+fn spawn_until_shutdown(state: &SharedState, task: impl Future<Output = ()> + Send + 'static) {
+    let mut shutdown = state.lock().unwrap().shutdown.subscribe();
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = task => {}
+            _ = shutdown.wait_for(|&stop| stop) => {}
+        }
+    });
+}
 
-    // If no MIDI device, start simulation
-    if _midi_connection.is_none() {
-        info!("Starting MIDI simulation");
-        let sim_state = state.clone();
-        tokio::spawn(async move {
-            simulate_midi_events(sim_state).await;
-        });
+/// Closes every MIDI connection, leaving the ports to other applications.
+fn close_midi(state: &SharedState) {
+    let (input_connections, output_connections) = {
+        let mut state_guard = state.lock().unwrap();
+        state_guard.thru = None;
+        state_guard.inputs.clear();
+        (
+            state_guard.input_connections.clone(),
+            state_guard.output_connections.clone(),
+        )
+    };
+    input_connections.lock().unwrap().clear();
+    output_connections.lock().unwrap().clear();
+}
+
+/// Lets the configured origins call the API from a browser.
+fn cors_layer(allowed_origins: &[String]) -> anyhow::Result<CorsLayer> {
+    let allow_origin = if allowed_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins = allowed_origins
+            .iter()
+            .map(|origin| {
+                origin
+                    .parse::<axum::http::HeaderValue>()
+                    .with_context(|| format!("Invalid allowed origin `{}`", origin))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        AllowOrigin::list(origins)
+    };
+    Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([
+            axum::http::Method::GET,
+            axum::http::Method::PUT,
+            axum::http::Method::POST,
+            axum::http::Method::DELETE,
+        ])
+        .allow_headers([axum::http::header::CONTENT_TYPE]))
+}
+
+/// A running server, from [`start_server`].
+pub struct ServerHandle {
+    local_addr: SocketAddr,
+    shutdown: watch::Sender<bool>,
+    task: JoinHandle<anyhow::Result<()>>,
+}
+
+impl ServerHandle {
+    /// Where the server is listening, with the actual port if it was given 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stops the server: stops accepting connections, sends every
+    /// WebSocket client a close frame, stops the simulation and background
+    /// tasks and closes the MIDI ports. Returns once all of it is done.
+    pub async fn shutdown(self) -> anyhow::Result<()> {
+        info!("Shutting down MIDI Backend server");
+        self.shutdown.send_replace(true);
+        self.task.await?
+    }
+
+    /// Serves until `signal` completes, then shuts down and returns what
+    /// it completed with. Returns the error instead if the server stops by
+    /// itself first.
+    pub async fn run_until<T>(mut self, signal: impl Future<Output = T>) -> anyhow::Result<T> {
+        tokio::select! {
+            result = &mut self.task => {
+                // Background tasks have nothing left to serve
+                self.shutdown.send_replace(true);
+                result??;
+                anyhow::bail!("MIDI Backend server stopped unexpectedly")
+            }
+            output = signal => {
+                self.shutdown().await?;
+                Ok(output)
+            }
+        }
+    }
+}
+
+/// Starts the server in the background, returning once it is listening.
+/// Used by both the binary and the Tauri app.
+pub async fn start_server(config: ServerConfig) -> anyhow::Result<ServerHandle> {
+    let cors = cors_layer(&config.allowed_origins)?;
+    // Before opening any MIDI port, so a port in use fails straight away
+    let listener = tokio::net::TcpListener::bind((config.address, config.port))
+        .await
+        .with_context(|| format!("Failed to listen on {}:{}", config.address, config.port))?;
+    let local_addr = listener.local_addr()?;
+    let mut app_state = AppState::new(config.broadcast_capacity);
+
+    // A fixed zone layout for MPE controllers that don't send RPN 6
+    if let Some(layout) = &config.mpe_zones {
+        info!("Using configured {}", layout);
+        app_state.mpe = MpeTracker::with_layout(layout.clone());
     }
 
+    app_state.selectors = config.inputs.clone();
+    app_state.router = routing::Router::new(config.routes.clone());
+    app_state.config_file = config.file.clone();
+    app_state.simulation = config.simulation;
+    if let Some(name) = &config.virtual_output {
+        info!("Creating virtual MIDI output: {}", name);
+        app_state.thru = Some(virtual_ports::create_output(name)?);
+        app_state.virtual_ports.push(name.clone());
+    }
+    app_state
+        .virtual_ports
+        .extend(config.virtual_input.iter().cloned());
+    let state = Arc::new(Mutex::new(app_state));
+
+    // Before the device watcher starts, which would report it as plugged in.
+    // Lives as long as the server does
+    let virtual_input = match &config.virtual_input {
+        Some(name) => {
+            info!("Creating virtual MIDI input: {}", name);
+            Some(virtual_ports::create_input(&state, name)?)
+        }
+        None => None,
+    };
+
+    // Before any input, so routed MIDI finds its outputs open
+    outputs::connect_routes(&state);
+
+    // Watch from before opening inputs so no device plugged in meanwhile
+    // is missed
+    let device_watcher = watcher::DeviceWatcher::new(watcher::MidirPorts)?;
+
+    // Try to set up real MIDI input
+    let inputs = inputs::select_inputs(&state, &config.inputs)?;
+
+    // If no MIDI device, simulate until one is plugged in
+    let virtual_ports = config.virtual_input.is_some() || config.virtual_output.is_some();
+    if inputs.is_empty() && !virtual_ports && config.simulation {
+        if config.inputs.is_empty() {
+            info!("No MIDI input devices found, will use simulation mode");
+        } else {
+            warn!("No MIDI input matches the configured selectors, will use simulation mode");
+        }
+    }
+    // Kept running even when off, since clients can turn it on
+    spawn_until_shutdown(&state, simulate_midi_events(state.clone()));
+
+    spawn_until_shutdown(&state, watch_timeouts(state.clone()));
+    spawn_until_shutdown(
+        &state,
+        watcher::watch_devices(state.clone(), device_watcher),
+    );
+
     let app = Router::new()
         .route("/", get(health_check))
         .route("/ws", get(websocket_handler))
-        .layer(
-            CorsLayer::new()
-                .allow_origin("http://localhost:3001".parse::<axum::http::HeaderValue>().unwrap())
-                .allow_methods([axum::http::Method::GET])
-                .allow_headers([axum::http::header::CONTENT_TYPE]),
+        .route("/api/ports", get(ports_handler))
+        .route("/api/input", put(select_input_handler))
+        .route(
+            "/api/routes",
+            get(routes_handler)
+                .put(set_routes_handler)
+                .post(add_route_handler),
         )
-        .with_state(state);
-
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await?;
-    info!("MIDI Backend server running on http://localhost:3000");
-
-    axum::serve(listener, app).await?;
+        .route("/api/routes/:index", delete(delete_route_handler))
+        .layer(cors)
+        .with_state(state.clone());
+
+    let shutdown = state.lock().unwrap().shutdown.clone();
+    let mut stop = shutdown.subscribe();
+    let running = shutdown.clone();
+    let task = tokio::spawn(async move {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async move {
+                let _ = stop.wait_for(|&stop| stop).await;
+            })
+            .await?;
+        // Every WebSocket client and background task has wound up
+        running.closed().await;
+        // Its callback takes the state lock, so it goes first
+        drop(virtual_input);
+        close_midi(&state);
+        info!("MIDI Backend server stopped");
+        Ok(())
+    });
+    info!("MIDI Backend server running on http://{}", local_addr);
 
-    Ok(())
+    Ok(ServerHandle {
+        local_addr,
+        shutdown,
+        task,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_midi_message_parsing() {
-        // Test Note On
-        let note_on = vec![0x90, 60, 64]; // Note On, C4, velocity 64
-        let msg = MidiMessage::from_raw_message(&note_on).unwrap();
-        assert_eq!(msg.message_type, "NoteOn");
-        assert_eq!(msg.note, Some(60));
-        assert_eq!(msg.velocity, Some(64));
-
-        // Test Note Off
-        let note_off = vec![0x80, 60, 0]; // Note Off, C4
-        let msg = MidiMessage::from_raw_message(&note_off).unwrap();
-        assert_eq!(msg.message_type, "NoteOff");
-        assert_eq!(msg.note, Some(60));
-
-        // Test Control Change
-        let cc = vec![0xB0, 7, 100]; // CC, Volume, value 100
-        let msg = MidiMessage::from_raw_message(&cc).unwrap();
-        assert_eq!(msg.message_type, "ControlChange");
-        assert_eq!(msg.control, Some(7));
-        assert_eq!(msg.value, Some(100));
-    }
-
     #[tokio::test]
     async fn test_simulation() {
-        let state = Arc::new(Mutex::new(AppState::new()));
+        let state = Arc::new(Mutex::new(AppState::new(100)));
         let mut receiver = {
             let state_guard = state.lock().unwrap();
-            state_guard.midi_sender.subscribe()
+            state_guard.event_sender.subscribe()
         };
 
         // Start simulation task
         let sim_state = state.clone();
         tokio::spawn(async move {
-            let note_on = MidiMessage {
-                message_type: "NoteOn".to_string(),
-                note: Some(60),
-                velocity: Some(64),
-                control: None,
-                value: None,
+            let note_on = MidiMessage::NoteOn {
+                channel: 0,
+                note: 60,
+                velocity: 64,
             };
-            let state_guard = sim_state.lock().unwrap();
-            state_guard.midi_sender.send(note_on).unwrap();
+            sim_state.lock().unwrap().publish(note_on);
         });
 
         // Receive the message
         let msg = receiver.recv().await.unwrap();
-        assert_eq!(msg.message_type, "NoteOn");
-        assert_eq!(msg.note, Some(60));
+        assert!(matches!(
+            msg.event,
+            ServerEvent::Midi(MidiMessage::NoteOn { note: 60, .. })
+        ));
+        assert_eq!(msg.description.as_deref(), Some("Ch1 Note On C4 vel 64"));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_background_tasks() {
+        let state = Arc::new(Mutex::new(AppState::new(100)));
+        let (mut receiver, shutdown) = {
+            let state_guard = state.lock().unwrap();
+            (
+                state_guard.event_sender.subscribe(),
+                state_guard.shutdown.clone(),
+            )
+        };
+        spawn_until_shutdown(&state, simulate_midi_events(state.clone()));
+        let msg = receiver.recv().await.unwrap();
+        assert!(matches!(msg.event, ServerEvent::Midi(_)));
+
+        shutdown.send_replace(true);
+        tokio::time::timeout(Duration::from_secs(1), shutdown.closed())
+            .await
+            .expect("simulation still running");
+        // A task started during shutdown doesn't run at all
+        spawn_until_shutdown(&state, std::future::pending());
+        tokio::time::timeout(Duration::from_secs(1), shutdown.closed())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_until_returns_server_error() {
+        let handle =
+            |task: JoinHandle<anyhow::Result<()>>, shutdown: &watch::Sender<bool>| ServerHandle {
+                local_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+                shutdown: shutdown.clone(),
+                task,
+            };
+
+        let (shutdown, _) = watch::channel(false);
+        let task = tokio::spawn(async { anyhow::bail!("accept failed") });
+        let error = handle(task, &shutdown)
+            .run_until(std::future::pending::<()>())
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "accept failed");
+        assert!(*shutdown.borrow());
+
+        let (shutdown, mut stop) = watch::channel(false);
+        let task = tokio::spawn(async move {
+            stop.wait_for(|&stop| stop).await?;
+            Ok(())
+        });
+        let output = handle(task, &shutdown)
+            .run_until(async { "signal" })
+            .await
+            .unwrap();
+        assert_eq!(output, "signal");
+    }
+
+    #[test]
+    fn test_publish_broadcasts_transport_changes() {
+        let mut state = AppState::new(100);
+        let mut receiver = state.event_sender.subscribe();
+
+        state.publish(MidiMessage::Start);
+
+        assert_eq!(
+            receiver.try_recv().unwrap().event,
+            ServerEvent::Midi(MidiMessage::Start)
+        );
+        match receiver.try_recv().unwrap().event {
+            ServerEvent::Transport(transport) => {
+                assert_eq!(transport.status, midi_protocol::TransportStatus::Playing)
+            }
+            other => panic!("expected transport event, got {:?}", other),
+        }
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_derived_events_share_source_timestamp() {
+        let mut state = AppState::new(100);
+        let mut receiver = state.event_sender.subscribe();
+
+        state.publish_at(MidiMessage::Start, 1_234, &inputs::simulation_source());
+
+        let midi = receiver.try_recv().unwrap();
+        let transport = receiver.try_recv().unwrap();
+        assert_eq!(midi.timestamp_us, Some(1_234));
+        assert_eq!(transport.timestamp_us, Some(1_234));
+        assert_eq!(midi.captured_at_us, transport.captured_at_us);
+        assert!(midi.captured_at_us.unwrap() > 0);
+    }
+
+    #[test]
+    fn test_sources_are_described_apart() {
+        let mut state = AppState::new(100);
+        let mut receiver = state.event_sender.subscribe();
+        let source = |id: &str| EventSource {
+            id: format!("input:{}", id),
+            name: id.to_string(),
+        };
+        let drums = source("Drum Pad");
+        let keys = source("Keystation");
+        let note = MidiMessage::NoteOn {
+            channel: 0,
+            note: 38,
+            velocity: 90,
+        };
+
+        // Only the pad switches channel 1 to a rhythm channel
+        for message in [
+            MidiMessage::ControlChange {
+                channel: 0,
+                controller: 0,
+                value: 0x78,
+            },
+            MidiMessage::ProgramChange {
+                channel: 0,
+                program: 0,
+            },
+        ] {
+            state.publish_at(message, 0, &drums);
+        }
+        state.publish_at(note.clone(), 10, &drums);
+        state.publish_at(note, 20, &keys);
+
+        let wires: Vec<WireMessage> = std::iter::from_fn(|| receiver.try_recv().ok()).collect();
+        let (pad, keystation) = (&wires[wires.len() - 2], &wires[wires.len() - 1]);
+        assert_eq!(pad.source.as_ref(), Some(&drums));
+        assert_eq!(
+            pad.description.as_deref(),
+            Some("Ch1 Acoustic Snare vel 90")
+        );
+        assert_eq!(keystation.source.as_ref(), Some(&keys));
+        assert_eq!(
+            keystation.description.as_deref(),
+            Some("Ch1 Note On D2 vel 90")
+        );
+    }
+
+    #[test]
+    fn test_snapshot_includes_selected_input() {
+        let mut state = AppState::new(100);
+        assert_eq!(state.snapshot().len(), 3);
+
+        let port = PortInfo {
+            id: "input:Keystation 49".to_string(),
+            name: "Keystation 49".to_string(),
+            direction: PortDirection::Input,
+            connected: true,
+        };
+        state.inputs = vec![port.clone()];
+        assert_eq!(
+            state.snapshot().last(),
+            Some(&ServerEvent::Inputs { ports: vec![port] })
+        );
+    }
+
+    #[test]
+    fn test_publish_malformed() {
+        let mut state = AppState::new(100);
+        let mut receiver = state.event_sender.subscribe();
+
+        let malformed = MidiMessage::parse(&[0x90, 60]).unwrap_err();
+        state.publish_malformed_at(malformed.clone(), 42, &inputs::simulation_source());
+
+        let wire = receiver.try_recv().unwrap();
+        assert_eq!(wire.event, ServerEvent::Malformed(malformed));
+        assert_eq!(
+            wire.description.as_deref(),
+            Some("Malformed (truncated): 90 3C")
+        );
+        assert_eq!(wire.timestamp_us, Some(42));
+    }
+
+    #[test]
+    fn test_publish_mpe_configuration() {
+        let mut state = AppState::new(100);
+        let mut receiver = state.event_sender.subscribe();
+
+        // RPN 6 = 15 on the Lower Zone manager channel
+        for (controller, value) in [(101, 0), (100, 6), (6, 15)] {
+            state.publish(MidiMessage::ControlChange {
+                channel: 0,
+                controller,
+                value,
+            });
+        }
+        state.publish(MidiMessage::NoteOn {
+            channel: 1,
+            note: 60,
+            velocity: 100,
+        });
+
+        let events: Vec<ServerEvent> = std::iter::from_fn(|| receiver.try_recv().ok())
+            .map(|message| message.event)
+            .collect();
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ServerEvent::MpeLayout(layout) if layout.lower.is_some()
+        )));
+        assert!(matches!(
+            events.last(),
+            Some(ServerEvent::NoteExpression(expression)) if expression.note == 60 && expression.active
+        ));
     }
 }
@@ -1,9 +1,14 @@
 // Binary entry point - just calls the library function
-use midi_backend::start_server;
-use tracing_subscriber;
+use anyhow::Context;
+use midi_backend::{start_server, ServerConfig};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
-    start_server().await
+    let config = ServerConfig::load(std::env::args().skip(1))?;
+    let server = start_server(config).await?;
+    server
+        .run_until(tokio::signal::ctrl_c())
+        .await?
+        .context("Failed to listen for Ctrl+C")
 }
@@ -0,0 +1,341 @@
+//! Groups MPE member-channel messages into per-note expression events.
+
+use midi_protocol::mpe::{MpeChannelRole, MPE_SLIDE_CONTROLLER};
+use midi_protocol::parameter::{RPN_MPE_CONFIGURATION, RPN_PITCH_BEND_SENSITIVITY};
+use midi_protocol::{
+    MidiMessage, MpeLayout, MpeZoneSide, NoteExpression, ParameterChange, ParameterType,
+};
+
+#[derive(Debug, Clone, Default)]
+struct ChannelState {
+    /// Sounding notes with their Note On velocity.
+    notes: Vec<(u8, u8)>,
+    bend: i16,
+    slide: u8,
+    pressure: u8,
+}
+
+/// Follows the MPE zone layout and the expression of every note played in it.
+///
+/// Zones come from MPE Configuration Messages (RPN 6 on a manager channel)
+/// or a layout given up front. Member channels carry one note's pitch bend,
+/// CC74 slide and channel pressure; pitch bend on the manager channel shifts
+/// every note in its zone. Expression sent before the Note On, as MPE
+/// controllers do, is picked up by the note.
+#[derive(Debug, Clone, Default)]
+pub struct MpeTracker {
+    layout: MpeLayout,
+    channels: [ChannelState; 16],
+}
+
+impl MpeTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_layout(layout: MpeLayout) -> Self {
+        Self {
+            layout,
+            ..Self::default()
+        }
+    }
+
+    pub fn layout(&self) -> &MpeLayout {
+        &self.layout
+    }
+
+    /// Applies MPE Configuration and Pitch Bend Sensitivity edits, returning
+    /// the new layout if it changed.
+    pub fn handle_parameter(&mut self, change: &ParameterChange) -> Option<MpeLayout> {
+        if change.parameter_type != ParameterType::Rpn {
+            return None;
+        }
+        let previous = self.layout.clone();
+        match change.number {
+            RPN_MPE_CONFIGURATION => {
+                let side = match change.channel {
+                    0 => MpeZoneSide::Lower,
+                    15 => MpeZoneSide::Upper,
+                    _ => return None,
+                };
+                self.layout.configure(side, change.value_msb());
+                if self.layout != previous {
+                    self.channels = Default::default();
+                }
+            }
+            RPN_PITCH_BEND_SENSITIVITY => {
+                // Sensitivity sent on any member channel applies to the whole zone
+                let range = change.value_msb();
+                match self.layout.role(change.channel)? {
+                    MpeChannelRole::Manager(side) => {
+                        self.layout.zone_mut(side)?.manager_bend_range = range
+                    }
+                    MpeChannelRole::Member(side) => {
+                        self.layout.zone_mut(side)?.member_bend_range = range
+                    }
+                }
+            }
+            _ => return None,
+        }
+        (self.layout != previous).then(|| self.layout.clone())
+    }
+
+    /// Feeds a message, returning the expression of every note it changed.
+    pub fn handle(&mut self, message: &MidiMessage) -> Vec<NoteExpression> {
+        let Some(channel) = message.channel() else {
+            return Vec::new();
+        };
+        let side = match self.layout.role(channel) {
+            Some(MpeChannelRole::Member(side)) => side,
+            Some(MpeChannelRole::Manager(side)) => {
+                let MidiMessage::PitchBend { value, .. } = *message else {
+                    return Vec::new();
+                };
+                self.channels[channel as usize].bend = value;
+                return self.zone_expressions(side);
+            }
+            None => return Vec::new(),
+        };
+
+        let state = &mut self.channels[channel as usize];
+        match *message {
+            MidiMessage::NoteOn { note, velocity, .. } => {
+                state.notes.retain(|&(n, _)| n != note);
+                state.notes.push((note, velocity));
+                self.expression(side, channel, note, velocity, true)
+                    .into_iter()
+                    .collect()
+            }
+            MidiMessage::NoteOff { note, .. } => {
+                let Some(index) = state.notes.iter().position(|&(n, _)| n == note) else {
+                    return Vec::new();
+                };
+                let (note, velocity) = state.notes.remove(index);
+                self.expression(side, channel, note, velocity, false)
+                    .into_iter()
+                    .collect()
+            }
+            MidiMessage::PitchBend { value, .. } => {
+                state.bend = value;
+                self.channel_expressions(side, channel)
+            }
+            MidiMessage::ControlChange {
+                controller: MPE_SLIDE_CONTROLLER,
+                value,
+                ..
+            } => {
+                state.slide = value;
+                self.channel_expressions(side, channel)
+            }
+            MidiMessage::ChannelPressure { pressure, .. } => {
+                state.pressure = pressure;
+                self.channel_expressions(side, channel)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn expression(
+        &self,
+        side: MpeZoneSide,
+        channel: u8,
+        note: u8,
+        velocity: u8,
+        active: bool,
+    ) -> Option<NoteExpression> {
+        let zone = self.layout.zone(side)?;
+        let state = &self.channels[channel as usize];
+        let manager = &self.channels[side.manager_channel() as usize];
+        let bend = state.bend as f64 / 8192.0 * zone.member_bend_range as f64
+            + manager.bend as f64 / 8192.0 * zone.manager_bend_range as f64;
+        Some(NoteExpression {
+            zone: side,
+            channel,
+            note,
+            velocity,
+            bend,
+            slide: state.slide,
+            pressure: state.pressure,
+            active,
+        })
+    }
+
+    fn channel_expressions(&self, side: MpeZoneSide, channel: u8) -> Vec<NoteExpression> {
+        self.channels[channel as usize]
+            .notes
+            .iter()
+            .filter_map(|&(note, velocity)| self.expression(side, channel, note, velocity, true))
+            .collect()
+    }
+
+    fn zone_expressions(&self, side: MpeZoneSide) -> Vec<NoteExpression> {
+        (0..16)
+            .filter(|&channel| self.layout.role(channel) == Some(MpeChannelRole::Member(side)))
+            .flat_map(|channel| self.channel_expressions(side, channel))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rpn(channel: u8, number: u16, msb: u8) -> ParameterChange {
+        ParameterChange {
+            channel,
+            parameter_type: ParameterType::Rpn,
+            number,
+            value: (msb as u16) << 7,
+        }
+    }
+
+    #[test]
+    fn test_configuration_message() {
+        let mut tracker = MpeTracker::new();
+        assert!(tracker
+            .handle(&MidiMessage::NoteOn {
+                channel: 1,
+                note: 60,
+                velocity: 100
+            })
+            .is_empty());
+
+        let layout = tracker
+            .handle_parameter(&rpn(0, RPN_MPE_CONFIGURATION, 15))
+            .unwrap();
+        assert_eq!(layout.lower.as_ref().unwrap().member_channels, 15);
+
+        // MPE Configuration on a member channel means nothing
+        assert!(tracker
+            .handle_parameter(&rpn(3, RPN_MPE_CONFIGURATION, 4))
+            .is_none());
+
+        let layout = tracker
+            .handle_parameter(&rpn(2, RPN_PITCH_BEND_SENSITIVITY, 24))
+            .unwrap();
+        assert_eq!(layout.lower.as_ref().unwrap().member_bend_range, 24);
+    }
+
+    #[test]
+    fn test_unchanged_layout_is_not_reported() {
+        let mut tracker = MpeTracker::new();
+        assert!(tracker
+            .handle_parameter(&rpn(0, RPN_MPE_CONFIGURATION, 15))
+            .is_some());
+        let note_on = MidiMessage::NoteOn {
+            channel: 1,
+            note: 60,
+            velocity: 100,
+        };
+        assert_eq!(tracker.handle(&note_on).len(), 1);
+
+        // The Data Entry LSB repeats the MSB's layout
+        assert!(tracker
+            .handle_parameter(&rpn(0, RPN_MPE_CONFIGURATION, 15))
+            .is_none());
+        assert!(tracker
+            .handle_parameter(&rpn(0, RPN_PITCH_BEND_SENSITIVITY, 12))
+            .is_some());
+        assert!(tracker
+            .handle_parameter(&rpn(0, RPN_PITCH_BEND_SENSITIVITY, 12))
+            .is_none());
+
+        // Sounding notes are kept
+        let note_off = MidiMessage::NoteOff {
+            channel: 1,
+            note: 60,
+            velocity: 0,
+        };
+        assert_eq!(tracker.handle(&note_off).len(), 1);
+    }
+
+    #[test]
+    fn test_per_note_expression() {
+        let mut tracker = MpeTracker::with_layout("lower=15".parse().unwrap());
+
+        // Initial expression is sent before the note
+        assert!(tracker
+            .handle(&MidiMessage::PitchBend {
+                channel: 1,
+                value: 4096
+            })
+            .is_empty());
+        tracker.handle(&MidiMessage::ControlChange {
+            channel: 1,
+            controller: MPE_SLIDE_CONTROLLER,
+            value: 64,
+        });
+        let events = tracker.handle(&MidiMessage::NoteOn {
+            channel: 1,
+            note: 60,
+            velocity: 90,
+        });
+        assert_eq!(
+            events,
+            vec![NoteExpression {
+                zone: MpeZoneSide::Lower,
+                channel: 1,
+                note: 60,
+                velocity: 90,
+                bend: 24.0,
+                slide: 64,
+                pressure: 0,
+                active: true,
+            }]
+        );
+
+        let events = tracker.handle(&MidiMessage::ChannelPressure {
+            channel: 1,
+            pressure: 100,
+        });
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].pressure, 100);
+
+        // Another member channel is a different note
+        tracker.handle(&MidiMessage::NoteOn {
+            channel: 2,
+            note: 64,
+            velocity: 80,
+        });
+        let events = tracker.handle(&MidiMessage::PitchBend {
+            channel: 2,
+            value: -8192,
+        });
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].note, events[0].bend), (64, -48.0));
+
+        let events = tracker.handle(&MidiMessage::NoteOff {
+            channel: 1,
+            note: 60,
+            velocity: 0,
+        });
+        assert_eq!(events.len(), 1);
+        assert!(!events[0].active);
+        assert!(tracker
+            .handle(&MidiMessage::ChannelPressure {
+                channel: 1,
+                pressure: 0
+            })
+            .is_empty());
+    }
+
+    #[test]
+    fn test_manager_bend_moves_whole_zone() {
+        let mut tracker = MpeTracker::with_layout("lower=3,upper=3".parse().unwrap());
+        for channel in [1, 2, 14] {
+            tracker.handle(&MidiMessage::NoteOn {
+                channel,
+                note: 60 + channel,
+                velocity: 100,
+            });
+        }
+
+        let events = tracker.handle(&MidiMessage::PitchBend {
+            channel: 0,
+            value: 8191,
+        });
+        let notes: Vec<u8> = events.iter().map(|event| event.note).collect();
+        assert_eq!(notes, vec![61, 62]);
+        assert!((events[0].bend - 2.0).abs() < 0.001);
+    }
+}
@@ -0,0 +1,297 @@
+//! MIDI Time Code assembly from quarter frames and Full Frame SysEx.
+
+use midi_protocol::{FrameRate, MidiMessage, SysEx, Timecode, TimecodeDirection, TimecodeState};
+
+/// Quarter frames needed to carry a complete timecode.
+const PIECES: u8 = 8;
+/// Frames that pass while the eight quarter frames of one timecode are sent.
+const FRAMES_PER_CYCLE: i64 = 2;
+
+/// With no quarter frame for this long, MTC is considered stopped.
+pub const MTC_TIMEOUT_US: u64 = 200_000;
+
+/// Reconstructs SMPTE time, frame rate and direction from MIDI Time Code.
+///
+/// Quarter frames 0-7 each carry one nibble of the time. A complete time
+/// is assembled after eight consecutive pieces: on piece 7 when running
+/// forward, or piece 0 when running in reverse. Since sending the pieces
+/// takes two frames, the assembled time is advanced by two frames in the
+/// direction of travel. A piece that doesn't follow its predecessor counts
+/// as a dropout and restarts assembly.
+#[derive(Debug, Clone, Default)]
+pub struct MtcAssembler {
+    state: TimecodeState,
+    nibbles: [u8; PIECES as usize],
+    last_piece: Option<u8>,
+    /// Consecutive in-sequence pieces received in the current direction.
+    run: u8,
+    last_quarter_frame_us: Option<u64>,
+}
+
+impl MtcAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn state(&self) -> &TimecodeState {
+        &self.state
+    }
+
+    /// Feeds a message received at `now_us`, returning the new state when a
+    /// timecode was completed or a dropout was detected.
+    pub fn handle(&mut self, message: &MidiMessage, now_us: u64) -> Option<TimecodeState> {
+        match message {
+            MidiMessage::QuarterFrame { piece, value } => {
+                self.quarter_frame(*piece, *value, now_us)
+            }
+            MidiMessage::SysEx(sysex) => self.full_frame(sysex),
+            _ => None,
+        }
+    }
+
+    /// Marks MTC as stopped once no quarter frame has arrived for
+    /// [`MTC_TIMEOUT_US`]. Returns the new state if that just happened.
+    pub fn check_timeout(&mut self, now_us: u64) -> Option<TimecodeState> {
+        match self.last_quarter_frame_us {
+            Some(last) if self.state.running && now_us.saturating_sub(last) >= MTC_TIMEOUT_US => {
+                self.state.running = false;
+                self.last_piece = None;
+                self.run = 0;
+                Some(self.state.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn quarter_frame(&mut self, piece: u8, value: u8, now_us: u64) -> Option<TimecodeState> {
+        let piece = piece % PIECES;
+        self.nibbles[piece as usize] = value & 0x0F;
+        self.last_quarter_frame_us = Some(now_us);
+
+        let mut dropout = false;
+        let direction = match self.last_piece {
+            Some(last) if piece == (last + 1) % PIECES => Some(TimecodeDirection::Forward),
+            Some(last) if piece == (last + PIECES - 1) % PIECES => Some(TimecodeDirection::Reverse),
+            Some(_) => {
+                dropout = true;
+                None
+            }
+            None => None,
+        };
+        self.last_piece = Some(piece);
+
+        self.run = match direction {
+            Some(direction) if direction == self.state.direction => self.run.saturating_add(1),
+            Some(direction) => {
+                self.state.direction = direction;
+                2
+            }
+            None => 1,
+        };
+
+        if dropout {
+            self.state.dropouts += 1;
+            return Some(self.state.clone());
+        }
+
+        let last_piece = match self.state.direction {
+            TimecodeDirection::Forward => PIECES - 1,
+            TimecodeDirection::Reverse => 0,
+        };
+        if self.run < PIECES || piece != last_piece {
+            return None;
+        }
+
+        let adjustment = match self.state.direction {
+            TimecodeDirection::Forward => FRAMES_PER_CYCLE,
+            TimecodeDirection::Reverse => -FRAMES_PER_CYCLE,
+        };
+        self.state.timecode = self.assemble().offset(adjustment);
+        self.state.running = true;
+        Some(self.state.clone())
+    }
+
+    fn assemble(&self) -> Timecode {
+        let n = &self.nibbles;
+        Timecode {
+            frames: n[0] | (n[1] & 0x01) << 4,
+            seconds: n[2] | (n[3] & 0x03) << 4,
+            minutes: n[4] | (n[5] & 0x03) << 4,
+            hours: n[6] | (n[7] & 0x01) << 4,
+            rate: FrameRate::from_bits(n[7] >> 1),
+        }
+    }
+
+    /// Full Frame message: F0 7F <device> 01 01 hr mn sc fr F7, sent when
+    /// the transport locates rather than runs.
+    fn full_frame(&mut self, sysex: &SysEx) -> Option<TimecodeState> {
+        let universal = sysex.universal.as_ref()?;
+        if !universal.realtime || universal.sub_id1 != 0x01 || universal.sub_id2 != Some(0x01) {
+            return None;
+        }
+        let &[hours, minutes, seconds, frames] = sysex.payload().get(3..7)? else {
+            return None;
+        };
+
+        self.state.timecode = Timecode {
+            hours: hours & 0x1F,
+            minutes,
+            seconds,
+            frames,
+            rate: FrameRate::from_bits(hours >> 5),
+        };
+        self.state.running = false;
+        self.last_piece = None;
+        self.run = 0;
+        Some(self.state.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The eight quarter frames encoding `time`, in forward order.
+    fn quarter_frames(time: Timecode) -> Vec<MidiMessage> {
+        let rate_bits = match time.rate {
+            FrameRate::Fps24 => 0,
+            FrameRate::Fps25 => 1,
+            FrameRate::Fps2997DropFrame => 2,
+            FrameRate::Fps30 => 3,
+        };
+        let values = [
+            time.frames & 0x0F,
+            time.frames >> 4,
+            time.seconds & 0x0F,
+            time.seconds >> 4,
+            time.minutes & 0x0F,
+            time.minutes >> 4,
+            time.hours & 0x0F,
+            (time.hours >> 4) | (rate_bits << 1),
+        ];
+        values
+            .iter()
+            .enumerate()
+            .map(|(piece, &value)| MidiMessage::QuarterFrame {
+                piece: piece as u8,
+                value,
+            })
+            .collect()
+    }
+
+    fn tc(hours: u8, minutes: u8, seconds: u8, frames: u8, rate: FrameRate) -> Timecode {
+        Timecode {
+            hours,
+            minutes,
+            seconds,
+            frames,
+            rate,
+        }
+    }
+
+    fn feed(assembler: &mut MtcAssembler, messages: &[MidiMessage]) -> Vec<TimecodeState> {
+        messages
+            .iter()
+            .filter_map(|m| assembler.handle(m, 0))
+            .collect()
+    }
+
+    #[test]
+    fn test_forward_assembly() {
+        let mut assembler = MtcAssembler::new();
+        let time = tc(1, 2, 3, 4, FrameRate::Fps25);
+        let states = feed(&mut assembler, &quarter_frames(time));
+        assert_eq!(states.len(), 1);
+
+        let state = &states[0];
+        assert_eq!(state.timecode, tc(1, 2, 3, 6, FrameRate::Fps25));
+        assert_eq!(state.direction, TimecodeDirection::Forward);
+        assert!(state.running);
+        assert_eq!(state.dropouts, 0);
+
+        // The next cycle continues the run
+        let states = feed(&mut assembler, &quarter_frames(time.offset(2)));
+        assert_eq!(states[0].timecode, tc(1, 2, 3, 8, FrameRate::Fps25));
+    }
+
+    #[test]
+    fn test_frame_rates() {
+        for rate in [
+            FrameRate::Fps24,
+            FrameRate::Fps25,
+            FrameRate::Fps2997DropFrame,
+            FrameRate::Fps30,
+        ] {
+            let mut assembler = MtcAssembler::new();
+            let time = tc(23, 59, 30, 10, rate);
+            let states = feed(&mut assembler, &quarter_frames(time));
+            assert_eq!(states[0].timecode.rate, rate);
+            assert_eq!(states[0].timecode, time.offset(2));
+        }
+    }
+
+    #[test]
+    fn test_reverse_assembly() {
+        let mut assembler = MtcAssembler::new();
+        let time = tc(0, 10, 0, 12, FrameRate::Fps30);
+        let mut frames = quarter_frames(time);
+        frames.reverse();
+        let states = feed(&mut assembler, &frames);
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].direction, TimecodeDirection::Reverse);
+        assert_eq!(states[0].timecode, tc(0, 10, 0, 10, FrameRate::Fps30));
+    }
+
+    #[test]
+    fn test_dropout_detection() {
+        let mut assembler = MtcAssembler::new();
+        let mut frames = quarter_frames(tc(0, 0, 1, 0, FrameRate::Fps24));
+        frames.remove(3);
+        let states = feed(&mut assembler, &frames);
+
+        // The gap is reported and the broken cycle produces no time
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].dropouts, 1);
+        assert!(!states[0].running);
+
+        // A clean cycle recovers
+        let states = feed(
+            &mut assembler,
+            &quarter_frames(tc(0, 0, 1, 4, FrameRate::Fps24)),
+        );
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].timecode, tc(0, 0, 1, 6, FrameRate::Fps24));
+        assert!(states[0].running);
+        assert_eq!(states[0].dropouts, 1);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut assembler = MtcAssembler::new();
+        feed(
+            &mut assembler,
+            &quarter_frames(tc(0, 0, 0, 0, FrameRate::Fps25)),
+        );
+        assert!(assembler.check_timeout(MTC_TIMEOUT_US - 1).is_none());
+        let state = assembler.check_timeout(MTC_TIMEOUT_US).unwrap();
+        assert!(!state.running);
+        assert!(assembler.check_timeout(2 * MTC_TIMEOUT_US).is_none());
+    }
+
+    #[test]
+    fn test_full_frame() {
+        let mut assembler = MtcAssembler::new();
+        // 01:02:03:04 at 29.97df (rate bits 10 -> 0x40 | hours)
+        let bytes = [0xF0, 0x7F, 0x7F, 0x01, 0x01, 0x41, 0x02, 0x03, 0x04, 0xF7];
+        let message = MidiMessage::from_raw_message(&bytes).unwrap();
+        let state = assembler.handle(&message, 0).unwrap();
+        assert_eq!(state.timecode, tc(1, 2, 3, 4, FrameRate::Fps2997DropFrame));
+        assert!(!state.running);
+
+        // Other Universal Real-Time messages are ignored
+        let master_volume =
+            MidiMessage::from_raw_message(&[0xF0, 0x7F, 0x7F, 0x04, 0x01, 0x00, 0x40, 0xF7])
+                .unwrap();
+        assert!(assembler.handle(&master_volume, 0).is_none());
+    }
+}
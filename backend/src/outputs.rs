@@ -0,0 +1,140 @@
+//! Sends MIDI out of output ports. Routes' outputs are opened as routes
+//! are set, others on first use.
+
+use crate::ports;
+use crate::SharedState;
+use anyhow::anyhow;
+use midi_protocol::{MidiMessage, PortDirection};
+use midir::{MidiOutput, MidiOutputConnection};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tracing::{debug, info, warn};
+
+/// Channel Mode controllers that silence a channel.
+const ALL_SOUND_OFF: u8 = 120;
+const ALL_NOTES_OFF: u8 = 123;
+
+/// Sends a message out of the output port with this id, connecting to it
+/// first if need be.
+pub fn send(state: &SharedState, output: &str, message: &MidiMessage) -> anyhow::Result<()> {
+    let output_connections = state.lock().unwrap().output_connections.clone();
+    connect(&output_connections, output)?;
+    send_to(&output_connections, output, message)
+}
+
+/// Opens the outputs the routes go to, so routing MIDI only has to look
+/// them up. One that can't be opened is skipped by the routes until the
+/// ports change.
+pub fn connect_routes(state: &SharedState) {
+    let (mut outputs, output_connections) = {
+        let state_guard = state.lock().unwrap();
+        let outputs: Vec<String> = state_guard
+            .router
+            .routes()
+            .iter()
+            .map(|route| route.to.clone())
+            .collect();
+        (outputs, state_guard.output_connections.clone())
+    };
+    outputs.sort();
+    outputs.dedup();
+
+    // Connecting can take a while, so no lock is held meanwhile
+    let connected: Vec<(String, bool)> = outputs
+        .into_iter()
+        .map(|output| match connect(&output_connections, &output) {
+            Ok(()) => (output, true),
+            Err(e) => {
+                warn!("Route to {} is down until the ports change: {}", output, e);
+                (output, false)
+            }
+        })
+        .collect();
+    // Messages routed meanwhile may have found an output not open yet
+    let mut state_guard = state.lock().unwrap();
+    for (output, connected) in &connected {
+        if *connected {
+            state_guard.router.mark_available(output);
+        } else {
+            state_guard.router.mark_unavailable(output);
+        }
+    }
+}
+
+/// Sends All Sound Off and All Notes Off on every channel, out of one
+/// output or else every open one. Returns the outputs it went out of.
+pub fn panic(state: &SharedState, output: Option<&str>) -> anyhow::Result<Vec<String>> {
+    let outputs = match output {
+        Some(output) => vec![output.to_string()],
+        None => {
+            let output_connections = state.lock().unwrap().output_connections.clone();
+            let mut outputs: Vec<String> =
+                output_connections.lock().unwrap().keys().cloned().collect();
+            outputs.sort();
+            outputs
+        }
+    };
+    for output in &outputs {
+        for channel in 0..16 {
+            for controller in [ALL_SOUND_OFF, ALL_NOTES_OFF] {
+                let message = MidiMessage::ControlChange {
+                    channel,
+                    controller,
+                    value: 0,
+                };
+                send(state, output, &message)?;
+            }
+        }
+    }
+    Ok(outputs)
+}
+
+/// Sends a message out of an output that is already open, for callers
+/// holding the state lock. A connection that fails to send is dropped, so
+/// the output has to be connected again.
+pub fn send_to(
+    connections: &Mutex<HashMap<String, MidiOutputConnection>>,
+    output: &str,
+    message: &MidiMessage,
+) -> anyhow::Result<()> {
+    let mut connections = connections.lock().unwrap();
+    let connection = connections
+        .get_mut(output)
+        .ok_or_else(|| anyhow!("MIDI output `{}` isn't open", output))?;
+    debug!("Sending to {}: {:?}", output, message);
+    if let Err(e) = connection.send(&message.to_bytes()) {
+        connections.remove(output);
+        return Err(anyhow!("Failed to send to {}: {}", output, e));
+    }
+    Ok(())
+}
+
+/// Opens an output unless it already is, holding the connections' lock only
+/// to look it up and to add it.
+fn connect(
+    connections: &Mutex<HashMap<String, MidiOutputConnection>>,
+    output: &str,
+) -> anyhow::Result<()> {
+    if connections.lock().unwrap().contains_key(output) {
+        return Ok(());
+    }
+    let connection = open_output(output)?;
+    connections
+        .lock()
+        .unwrap()
+        .entry(output.to_string())
+        .or_insert(connection);
+    Ok(())
+}
+
+fn open_output(output: &str) -> anyhow::Result<MidiOutputConnection> {
+    let midi_out = MidiOutput::new("midi-monitor output")?;
+    let port = ports::named_ports(&midi_out, PortDirection::Output)?
+        .into_iter()
+        .find(|port| port.id == output)
+        .ok_or_else(|| anyhow!("No MIDI output `{}`", output))?;
+    info!("Connecting to MIDI output: {}", port.name);
+    midi_out
+        .connect(&port.port, "midi-monitor-out")
+        .map_err(|e| anyhow!("Failed to connect to MIDI output: {}", e))
+}
@@ -0,0 +1,256 @@
+//! Assembles RPN/NRPN edits and 14-bit controller values from the raw
+//! Control Change stream.
+
+use midi_protocol::parameter::RPN_NULL;
+use midi_protocol::{MidiMessage, ParameterChange, ParameterType};
+
+const DATA_ENTRY_MSB: u8 = 6;
+const DATA_ENTRY_LSB: u8 = 38;
+const DATA_INCREMENT: u8 = 96;
+const DATA_DECREMENT: u8 = 97;
+const NRPN_LSB: u8 = 98;
+const NRPN_MSB: u8 = 99;
+const RPN_LSB: u8 = 100;
+const RPN_MSB: u8 = 101;
+
+const MAX_14_BIT: u16 = 0x3FFF;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ChannelState {
+    /// Selected RPN as [MSB, LSB].
+    rpn: [Option<u8>; 2],
+    /// Selected NRPN as [MSB, LSB].
+    nrpn: [Option<u8>; 2],
+    /// Whichever of RPN or NRPN was selected most recently.
+    active: Option<ParameterType>,
+    /// Current value of the selected parameter.
+    value: u16,
+    /// Last MSB seen on each of CC 0-31, if any.
+    controller_msb: [Option<u8>; 32],
+}
+
+impl ChannelState {
+    fn select(&mut self, parameter_type: ParameterType, is_lsb: bool, value: u8) {
+        let number = match parameter_type {
+            ParameterType::Nrpn => &mut self.nrpn,
+            _ => &mut self.rpn,
+        };
+        number[is_lsb as usize] = Some(value);
+        self.active = Some(parameter_type);
+        self.value = 0;
+    }
+
+    /// The parameter Data Entry currently applies to. 127/127 is the null
+    /// parameter, which deselects.
+    fn selected(&self) -> Option<(ParameterType, u16)> {
+        let parameter_type = self.active?;
+        let [Some(msb), Some(lsb)] = (match parameter_type {
+            ParameterType::Nrpn => self.nrpn,
+            _ => self.rpn,
+        }) else {
+            return None;
+        };
+        let number = (msb as u16) << 7 | lsb as u16;
+        (number != RPN_NULL).then_some((parameter_type, number))
+    }
+}
+
+/// Watches Control Changes per channel and reports completed parameter edits.
+///
+/// - RPN (CC 101/100) and NRPN (CC 99/98) select a parameter; Data Entry
+///   (CC 6/38) and Data Increment/Decrement (CC 96/97) then change it. A
+///   Data Entry MSB resets the LSB to 0, and increments step the full
+///   14-bit value by one.
+/// - CC 0-31 pair with CC 32-63 into 14-bit values, reported when the LSB
+///   arrives. Controllers that only ever send the MSB, or only ever send
+///   CC 32-63, produce no 14-bit events; their plain Control Changes
+///   already say everything.
+#[derive(Debug, Clone, Default)]
+pub struct ParameterTracker {
+    channels: [ChannelState; 16],
+}
+
+impl ParameterTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds a message, returning a parameter event if it completed one.
+    pub fn handle(&mut self, message: &MidiMessage) -> Option<ParameterChange> {
+        let MidiMessage::ControlChange {
+            channel,
+            controller,
+            value,
+        } = *message
+        else {
+            return None;
+        };
+        let state = &mut self.channels[(channel & 0x0F) as usize];
+
+        match controller {
+            RPN_MSB | RPN_LSB => {
+                state.select(ParameterType::Rpn, controller == RPN_LSB, value);
+                None
+            }
+            NRPN_MSB | NRPN_LSB => {
+                state.select(ParameterType::Nrpn, controller == NRPN_LSB, value);
+                None
+            }
+            DATA_ENTRY_MSB | DATA_ENTRY_LSB | DATA_INCREMENT | DATA_DECREMENT
+                if state.selected().is_some() =>
+            {
+                state.value = match controller {
+                    DATA_ENTRY_MSB => (value as u16) << 7,
+                    DATA_ENTRY_LSB => (state.value & !0x7F) | value as u16,
+                    DATA_INCREMENT => (state.value + 1).min(MAX_14_BIT),
+                    _ => state.value.saturating_sub(1),
+                };
+                let (parameter_type, number) = state.selected()?;
+                Some(ParameterChange {
+                    channel,
+                    parameter_type,
+                    number,
+                    value: state.value,
+                })
+            }
+            0..=31 => {
+                state.controller_msb[controller as usize] = Some(value);
+                None
+            }
+            32..=63 => {
+                let msb_controller = controller - 32;
+                // Without an MSB this is an ordinary controller, not a 14-bit one
+                let msb = state.controller_msb[msb_controller as usize]?;
+                Some(ParameterChange {
+                    channel,
+                    parameter_type: ParameterType::Controller,
+                    number: msb_controller as u16,
+                    value: (msb as u16) << 7 | value as u16,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cc(channel: u8, controller: u8, value: u8) -> MidiMessage {
+        MidiMessage::ControlChange {
+            channel,
+            controller,
+            value,
+        }
+    }
+
+    fn feed(tracker: &mut ParameterTracker, messages: &[MidiMessage]) -> Vec<ParameterChange> {
+        messages.iter().filter_map(|m| tracker.handle(m)).collect()
+    }
+
+    #[test]
+    fn test_pitch_bend_sensitivity() {
+        let mut tracker = ParameterTracker::new();
+        let events = feed(
+            &mut tracker,
+            &[cc(0, 101, 0), cc(0, 100, 0), cc(0, 6, 12), cc(0, 38, 0)],
+        );
+        assert_eq!(events.len(), 2);
+        let last = events.last().unwrap();
+        assert_eq!(last.parameter_type, ParameterType::Rpn);
+        assert_eq!(last.number, 0);
+        assert_eq!(last.value_msb(), 12);
+        assert_eq!(
+            last.to_string(),
+            "RPN 0 Pitch Bend Sensitivity = 12 semitones"
+        );
+    }
+
+    #[test]
+    fn test_nrpn_with_increment_and_decrement() {
+        let mut tracker = ParameterTracker::new();
+        let events = feed(
+            &mut tracker,
+            &[
+                cc(3, 99, 1),
+                cc(3, 98, 8),
+                cc(3, 6, 64),
+                cc(3, 96, 0),
+                cc(3, 96, 0),
+                cc(3, 97, 0),
+            ],
+        );
+        let values: Vec<u16> = events.iter().map(|e| e.value).collect();
+        assert_eq!(values, vec![8192, 8193, 8194, 8193]);
+        assert!(events
+            .iter()
+            .all(|e| e.parameter_type == ParameterType::Nrpn
+                && e.number == (1 << 7 | 8)
+                && e.channel == 3));
+    }
+
+    #[test]
+    fn test_rpn_null_deselects() {
+        let mut tracker = ParameterTracker::new();
+        let events = feed(
+            &mut tracker,
+            &[
+                cc(0, 101, 0),
+                cc(0, 100, 0),
+                cc(0, 101, 127),
+                cc(0, 100, 127),
+                cc(0, 6, 12),
+            ],
+        );
+        assert!(events.is_empty());
+
+        // Data Entry before any selection isn't a parameter edit
+        assert!(tracker.handle(&cc(1, 96, 0)).is_none());
+    }
+
+    #[test]
+    fn test_channels_are_independent() {
+        let mut tracker = ParameterTracker::new();
+        feed(&mut tracker, &[cc(0, 101, 0), cc(0, 100, 0)]);
+        assert!(tracker.handle(&cc(1, 6, 2)).is_none());
+        assert!(tracker.handle(&cc(0, 6, 2)).is_some());
+    }
+
+    #[test]
+    fn test_14_bit_controllers() {
+        let mut tracker = ParameterTracker::new();
+
+        // MSB alone is not reported
+        assert!(tracker.handle(&cc(0, 1, 64)).is_none());
+
+        let event = tracker.handle(&cc(0, 33, 5)).unwrap();
+        assert_eq!(event.parameter_type, ParameterType::Controller);
+        assert_eq!(event.number, 1);
+        assert_eq!(event.value, 64 << 7 | 5);
+
+        // MSB is kept, so a further LSB pairs with it
+        assert_eq!(tracker.handle(&cc(0, 33, 6)).unwrap().value, 64 << 7 | 6);
+
+        // Other controllers pass through untouched
+        assert!(tracker.handle(&cc(0, 74, 100)).is_none());
+        assert!(tracker
+            .handle(&MidiMessage::NoteOn {
+                channel: 0,
+                note: 60,
+                velocity: 1
+            })
+            .is_none());
+    }
+
+    #[test]
+    fn test_lsb_without_msb_is_not_14_bit() {
+        let mut tracker = ParameterTracker::new();
+        let events = feed(&mut tracker, &[cc(0, 33, 5), cc(0, 40, 90), cc(0, 33, 6)]);
+        assert!(events.is_empty());
+
+        // An MSB on another channel doesn't count
+        tracker.handle(&cc(1, 1, 64));
+        assert!(tracker.handle(&cc(0, 33, 7)).is_none());
+    }
+}
@@ -0,0 +1,104 @@
+//! Enumerates the host's MIDI ports and gives them stable identifiers.
+
+use midi_protocol::{PortDirection, PortInfo};
+use midir::{MidiIO, MidiInput, MidiOutput};
+
+/// A midir port with the id and name it's reported under.
+pub struct NamedPort<P> {
+    pub id: String,
+    pub name: String,
+    pub port: P,
+}
+
+/// Ids for ports in enumeration order: `input:<name>` or `output:<name>`,
+/// with `#2`, `#3`, ... appended for further ports sharing a name. Unlike
+/// midir's indices they don't shift when another device is plugged in.
+pub fn port_ids(direction: PortDirection, names: &[String]) -> Vec<String> {
+    names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let id = format!("{}:{}", direction.as_str(), name);
+            match names[..index].iter().filter(|other| *other == name).count() {
+                0 => id,
+                earlier => format!("{}#{}", id, earlier + 1),
+            }
+        })
+        .collect()
+}
+
+/// Every port of a midir input or output, in enumeration order.
+pub fn named_ports<T: MidiIO>(
+    io: &T,
+    direction: PortDirection,
+) -> anyhow::Result<Vec<NamedPort<T::Port>>> {
+    let ports = io.ports();
+    let names = ports
+        .iter()
+        .map(|port| io.port_name(port))
+        .collect::<Result<Vec<_>, _>>()?;
+    let ids = port_ids(direction, &names);
+    Ok(ids
+        .into_iter()
+        .zip(names)
+        .zip(ports)
+        .map(|((id, name), port)| NamedPort { id, name, port })
+        .collect())
+}
+
+/// Every input and output port on the host, marking the ones whose ids
+/// are in `connected`.
+pub fn list_ports(connected: &[String]) -> anyhow::Result<Vec<PortInfo>> {
+    let inputs = named_ports(
+        &MidiInput::new("midi-monitor port list")?,
+        PortDirection::Input,
+    )?;
+    let outputs = named_ports(
+        &MidiOutput::new("midi-monitor port list")?,
+        PortDirection::Output,
+    )?;
+    Ok(inputs
+        .into_iter()
+        .map(|port| port_info(PortDirection::Input, port, connected))
+        .chain(
+            outputs
+                .into_iter()
+                .map(|port| port_info(PortDirection::Output, port, connected)),
+        )
+        .collect())
+}
+
+fn port_info<P>(direction: PortDirection, port: NamedPort<P>, connected: &[String]) -> PortInfo {
+    PortInfo {
+        connected: connected.contains(&port.id),
+        id: port.id,
+        name: port.name,
+        direction,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_names_get_distinct_ids() {
+        let names: Vec<String> = ["Midi Through Port-0", "USB MIDI", "USB MIDI", "USB MIDI"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            port_ids(PortDirection::Input, &names),
+            vec![
+                "input:Midi Through Port-0",
+                "input:USB MIDI",
+                "input:USB MIDI#2",
+                "input:USB MIDI#3",
+            ]
+        );
+        assert_eq!(
+            port_ids(PortDirection::Output, &names[..1]),
+            vec!["output:Midi Through Port-0"]
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! MIDI thru routing: routes from any source to any output port, each
+//! passing only what its filter lets through.
+
+use anyhow::bail;
+use midi_protocol::MidiMessage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Passes MIDI from a source on to an output port.
+///
+/// ```toml
+/// [[routes]]
+/// from = "input:Keystation 49"
+/// to = "output:FluidSynth"
+/// channels = [0]
+/// types = ["NoteOn", "NoteOff"]
+/// notes = [36, 59]
+/// ```
+///
+/// An empty filter passes everything. `channels` only applies to channel
+/// messages and `notes` only to messages about a note, so a route limited to
+/// channel 1 still passes clock.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Route {
+    /// Source id: an input port id from `/api/ports`, `virtual:<name>` or
+    /// `simulation`.
+    pub from: String,
+    /// Output port id from `/api/ports`.
+    pub to: String,
+    /// Zero-based channels to pass.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub channels: Vec<u8>,
+    /// Message `type` names to pass, e.g. `NoteOn`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub types: Vec<String>,
+    /// Lowest and highest note to pass.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<[u8; 2]>,
+}
+
+impl Route {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(channel) = self.channels.iter().find(|&&channel| channel > 15) {
+            bail!("Route channel {} isn't 0-15", channel);
+        }
+        if let Some(name) = self
+            .types
+            .iter()
+            .find(|name| !MidiMessage::TYPES.contains(&name.as_str()))
+        {
+            bail!("Route message type `{}` doesn't exist", name);
+        }
+        if let Some([low, high]) = self.notes {
+            if low > high || high > 127 {
+                bail!("Route note range {}-{} isn't within 0-127", low, high);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a message from a source goes down this route.
+    pub fn passes(&self, source: &str, message: &MidiMessage) -> bool {
+        self.from == source
+            && (self.types.is_empty() || self.types.iter().any(|name| name == message.type_name()))
+            && message.channel().map_or(true, |channel| {
+                self.channels.is_empty() || self.channels.contains(&channel)
+            })
+            && message
+                .note()
+                .zip(self.notes)
+                .map_or(true, |(note, [low, high])| (low..=high).contains(&note))
+    }
+}
+
+/// The routes, and which of their outputs can't be reached right now.
+#[derive(Debug, Clone, Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    /// Outputs that couldn't be opened, skipped until the port list changes
+    /// rather than retried on every message.
+    unavailable: HashSet<String>,
+}
+
+impl Router {
+    pub fn new(routes: Vec<Route>) -> Self {
+        Self {
+            routes,
+            unavailable: HashSet::new(),
+        }
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    pub fn set_routes(&mut self, routes: Vec<Route>) {
+        self.routes = routes;
+        self.unavailable.clear();
+    }
+
+    /// Outputs a message from a source should be sent to, each once.
+    pub fn destinations(&self, source: &str, message: &MidiMessage) -> Vec<String> {
+        let mut outputs: Vec<String> = Vec::new();
+        for route in &self.routes {
+            if route.passes(source, message)
+                && !self.unavailable.contains(&route.to)
+                && !outputs.contains(&route.to)
+            {
+                outputs.push(route.to.clone());
+            }
+        }
+        outputs
+    }
+
+    pub fn mark_unavailable(&mut self, output: &str) {
+        self.unavailable.insert(output.to_string());
+    }
+
+    pub fn mark_available(&mut self, output: &str) {
+        self.unavailable.remove(output);
+    }
+
+    /// Gives every output another try, e.g. after a device is plugged in.
+    pub fn retry_unavailable(&mut self) {
+        self.unavailable.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(to: &str) -> Route {
+        Route {
+            from: "input:Keystation 49".to_string(),
+            to: to.to_string(),
+            channels: Vec::new(),
+            types: Vec::new(),
+            notes: None,
+        }
+    }
+
+    fn note_on(channel: u8, note: u8) -> MidiMessage {
+        MidiMessage::NoteOn {
+            channel,
+            note,
+            velocity: 100,
+        }
+    }
+
+    #[test]
+    fn test_filters() {
+        let bass = Route {
+            channels: vec![0],
+            types: vec!["NoteOn".to_string(), "NoteOff".to_string()],
+            notes: Some([36, 59]),
+            ..route("output:Bass")
+        };
+        let source = "input:Keystation 49";
+        assert!(bass.passes(source, &note_on(0, 40)));
+        assert!(!bass.passes("input:MPD218", &note_on(0, 40)));
+        assert!(!bass.passes(source, &note_on(1, 40)));
+        assert!(!bass.passes(source, &note_on(0, 60)));
+        assert!(!bass.passes(
+            source,
+            &MidiMessage::ControlChange {
+                channel: 0,
+                controller: 64,
+                value: 127
+            }
+        ));
+
+        // Channel and note limits don't stop messages without either
+        let keys = Route {
+            channels: vec![0],
+            notes: Some([36, 59]),
+            ..route("output:Keys")
+        };
+        assert!(keys.passes(source, &MidiMessage::TimingClock));
+        assert!(keys.passes(
+            source,
+            &MidiMessage::PitchBend {
+                channel: 0,
+                value: 100
+            }
+        ));
+    }
+
+    #[test]
+    fn test_destinations() {
+        let mut router = Router::new(vec![
+            route("output:Synth"),
+            route("output:Synth"),
+            Route {
+                channels: vec![9],
+                ..route("output:Drums")
+            },
+        ]);
+        let source = "input:Keystation 49";
+        assert_eq!(
+            router.destinations(source, &note_on(9, 36)),
+            vec!["output:Synth", "output:Drums"]
+        );
+        assert_eq!(
+            router.destinations(source, &note_on(0, 60)),
+            vec!["output:Synth"]
+        );
+
+        router.mark_unavailable("output:Synth");
+        assert_eq!(
+            router.destinations(source, &note_on(9, 36)),
+            vec!["output:Drums"]
+        );
+        router.mark_available("output:Synth");
+        assert_eq!(router.destinations(source, &note_on(0, 60)).len(), 1);
+        router.mark_unavailable("output:Synth");
+        router.retry_unavailable();
+        assert_eq!(router.destinations(source, &note_on(0, 60)).len(), 1);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(route("output:Synth").validate().is_ok());
+        let invalid = [
+            Route {
+                channels: vec![16],
+                ..route("output:Synth")
+            },
+            Route {
+                types: vec!["NoteOnn".to_string()],
+                ..route("output:Synth")
+            },
+            Route {
+                notes: Some([60, 48]),
+                ..route("output:Synth")
+            },
+            Route {
+                notes: Some([0, 128]),
+                ..route("output:Synth")
+            },
+        ];
+        for route in invalid {
+            assert!(route.validate().is_err(), "{:?}", route);
+        }
+    }
+}
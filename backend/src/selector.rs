@@ -0,0 +1,146 @@
+//! Picks input ports by name instead of by enumeration order.
+
+use crate::ports::NamedPort;
+use regex::Regex;
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// Prefix of the loopback ports ALSA creates on every Linux machine, which
+/// are never the device anyone wants to monitor.
+const MIDI_THROUGH: &str = "Midi Through";
+
+/// Which ports to open, written as:
+///
+/// - `Keystation`: name contains the text, ignoring case
+/// - `exact:Keystation 49 MIDI 1`: the whole name
+/// - `regex:^USB.*MIDI 1$`: name matches the regular expression
+/// - `id:input:USB MIDI#2`: a port id as listed by `/api/ports`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub enum PortSelector {
+    Contains(String),
+    Exact(String),
+    Regex(Regex),
+    Id(String),
+}
+
+impl PortSelector {
+    pub fn matches(&self, id: &str, name: &str) -> bool {
+        match self {
+            PortSelector::Contains(text) => name.to_lowercase().contains(&text.to_lowercase()),
+            PortSelector::Exact(text) => name == text,
+            PortSelector::Regex(regex) => regex.is_match(name),
+            PortSelector::Id(text) => id == text,
+        }
+    }
+}
+
+impl FromStr for PortSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(name) = s.strip_prefix("exact:") {
+            Ok(PortSelector::Exact(name.to_string()))
+        } else if let Some(pattern) = s.strip_prefix("regex:") {
+            Regex::new(pattern)
+                .map(PortSelector::Regex)
+                .map_err(|e| format!("invalid port regex `{}`: {}", pattern, e))
+        } else if let Some(id) = s.strip_prefix("id:") {
+            Ok(PortSelector::Id(id.to_string()))
+        } else if s.is_empty() {
+            Err("empty port selector".to_string())
+        } else {
+            Ok(PortSelector::Contains(s.to_string()))
+        }
+    }
+}
+
+impl TryFrom<String> for PortSelector {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for PortSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PortSelector::Contains(text) => write!(f, "{}", text),
+            PortSelector::Exact(text) => write!(f, "exact:{}", text),
+            PortSelector::Regex(regex) => write!(f, "regex:{}", regex),
+            PortSelector::Id(text) => write!(f, "id:{}", text),
+        }
+    }
+}
+
+/// Whether any of the selectors match a port. Without selectors, every port
+/// that isn't an ALSA "Midi Through" loopback.
+pub fn is_selected(selectors: &[PortSelector], id: &str, name: &str) -> bool {
+    if selectors.is_empty() {
+        !name.starts_with(MIDI_THROUGH)
+    } else {
+        selectors.iter().any(|selector| selector.matches(id, name))
+    }
+}
+
+/// Indices of the ports the selectors pick, in enumeration order.
+pub fn select_ports<P>(selectors: &[PortSelector], ports: &[NamedPort<P>]) -> Vec<usize> {
+    ports
+        .iter()
+        .enumerate()
+        .filter(|(_, port)| is_selected(selectors, &port.id, &port.name))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ports(names: &[&str]) -> Vec<NamedPort<()>> {
+        let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        crate::ports::port_ids(midi_protocol::PortDirection::Input, &names)
+            .into_iter()
+            .zip(names)
+            .map(|(id, name)| NamedPort { id, name, port: () })
+            .collect()
+    }
+
+    #[test]
+    fn test_selectors() {
+        let ports = ports(&[
+            "Midi Through Port-0",
+            "Keystation 49 MIDI 1",
+            "USB MIDI",
+            "USB MIDI",
+        ]);
+        let select = |selectors: &[&str]| {
+            let selectors: Vec<PortSelector> = selectors
+                .iter()
+                .map(|selector| selector.parse().unwrap())
+                .collect();
+            select_ports(&selectors, &ports)
+        };
+
+        assert_eq!(select(&[]), vec![1, 2, 3]);
+        assert_eq!(select(&["keystation"]), vec![1]);
+        assert_eq!(select(&["exact:Keystation 49"]), Vec::<usize>::new());
+        assert_eq!(select(&["exact:USB MIDI"]), vec![2, 3]);
+        assert_eq!(select(&["regex:^USB"]), vec![2, 3]);
+        assert_eq!(select(&["id:input:USB MIDI#2"]), vec![3]);
+        assert_eq!(select(&["Launchpad"]), Vec::<usize>::new());
+        assert_eq!(select(&["id:input:USB MIDI", "Keystation"]), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("regex:(".parse::<PortSelector>().is_err());
+        assert!("".parse::<PortSelector>().is_err());
+        assert_eq!(
+            "regex:^USB".parse::<PortSelector>().unwrap().to_string(),
+            "regex:^USB"
+        );
+    }
+}
@@ -0,0 +1,532 @@
+//! A WebSocket client's session: the commands it sends, what it subscribed
+//! to and what it is recording.
+
+use crate::selector::PortSelector;
+use crate::{inputs, outputs, SharedState};
+use anyhow::{anyhow, bail};
+use midi_protocol::sysex::{SYSEX_END, SYSEX_START};
+use midi_protocol::{
+    ClientMessage, ClientRequest, CommandReply, EventFilter, MidiMessage, ServerEvent, WireMessage,
+};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// Most MIDI events one recording keeps; later ones are dropped. They all
+/// go back in a single reply, which this keeps to around a megabyte.
+pub const MAX_RECORDED_EVENTS: usize = 5_000;
+
+#[derive(Debug, Default)]
+pub struct Session {
+    /// Subscriptions by the number handed out for them, oldest first.
+    subscriptions: Vec<(u32, EventFilter)>,
+    last_subscription: u32,
+    /// MIDI events sent while recording.
+    recording: Option<Vec<WireMessage>>,
+}
+
+impl Session {
+    /// Whether the client gets an event: everything until it subscribes,
+    /// then what any subscription passes. Replies and errors always go.
+    pub fn wants(&self, message: &WireMessage) -> bool {
+        matches!(
+            message.event,
+            ServerEvent::Reply(_) | ServerEvent::Error { .. }
+        ) || self.subscriptions.is_empty()
+            || self
+                .subscriptions
+                .iter()
+                .any(|(_, filter)| passes(filter, message))
+    }
+
+    /// Keeps a MIDI event sent to the client while it's recording.
+    pub fn record(&mut self, message: &WireMessage) {
+        if let (Some(events), ServerEvent::Midi(_)) = (&mut self.recording, &message.event) {
+            if events.len() < MAX_RECORDED_EVENTS {
+                events.push(message.clone());
+            }
+        }
+    }
+
+    fn subscribe(&mut self, filter: EventFilter) -> anyhow::Result<u32> {
+        validate(&filter)?;
+        self.last_subscription += 1;
+        self.subscriptions.push((self.last_subscription, filter));
+        Ok(self.last_subscription)
+    }
+
+    fn unsubscribe(&mut self, subscription: u32) -> anyhow::Result<()> {
+        let count = self.subscriptions.len();
+        self.subscriptions
+            .retain(|(number, _)| *number != subscription);
+        if self.subscriptions.len() == count {
+            bail!("No subscription {}", subscription);
+        }
+        Ok(())
+    }
+
+    fn start_recording(&mut self) -> anyhow::Result<()> {
+        if self.recording.is_some() {
+            bail!("Already recording");
+        }
+        self.recording = Some(Vec::new());
+        Ok(())
+    }
+
+    fn stop_recording(&mut self) -> anyhow::Result<Vec<WireMessage>> {
+        self.recording
+            .take()
+            .ok_or_else(|| anyhow!("Not recording"))
+    }
+}
+
+fn validate(filter: &EventFilter) -> anyhow::Result<()> {
+    if let Some(kind) = filter
+        .kinds
+        .iter()
+        .find(|kind| !ServerEvent::KINDS.contains(&kind.as_str()))
+    {
+        bail!("Event kind `{}` doesn't exist", kind);
+    }
+    if let Some(name) = filter
+        .types
+        .iter()
+        .find(|name| !MidiMessage::TYPES.contains(&name.as_str()))
+    {
+        bail!("Message type `{}` doesn't exist", name);
+    }
+    if let Some(channel) = filter.channels.iter().find(|&&channel| channel > 15) {
+        bail!("Channel {} isn't 0-15", channel);
+    }
+    if let Some([low, high]) = filter.notes {
+        if low > high || high > 127 {
+            bail!("Note range {}-{} isn't within 0-127", low, high);
+        }
+    }
+    if let Some(controller) = filter
+        .controllers
+        .iter()
+        .find(|&&controller| controller > 127)
+    {
+        bail!("Controller {} isn't 0-127", controller);
+    }
+    Ok(())
+}
+
+/// Refuses messages that wouldn't reach hardware as the client described
+/// them: undefined statuses, channels past 15, data bytes past 127, pitch
+/// bend outside -8192..=8191 and SysEx not framed by F0...F7.
+fn check_sendable(message: &MidiMessage) -> anyhow::Result<()> {
+    let in_range = match message {
+        MidiMessage::NoteOff {
+            channel,
+            note,
+            velocity,
+        }
+        | MidiMessage::NoteOn {
+            channel,
+            note,
+            velocity,
+        } => *channel < 16 && *note <= 127 && *velocity <= 127,
+        MidiMessage::PolyPressure {
+            channel,
+            note,
+            pressure,
+        } => *channel < 16 && *note <= 127 && *pressure <= 127,
+        MidiMessage::ControlChange {
+            channel,
+            controller,
+            value,
+        } => *channel < 16 && *controller <= 127 && *value <= 127,
+        MidiMessage::ProgramChange { channel, program } => *channel < 16 && *program <= 127,
+        MidiMessage::ChannelPressure { channel, pressure } => *channel < 16 && *pressure <= 127,
+        MidiMessage::PitchBend { channel, value } => {
+            *channel < 16 && (-8192..=8191).contains(value)
+        }
+        MidiMessage::SysEx(sysex) => match sysex.data.as_slice() {
+            [SYSEX_START, data @ .., SYSEX_END] => data.iter().all(|byte| *byte <= 127),
+            _ => false,
+        },
+        MidiMessage::QuarterFrame { piece, value } => *piece < 8 && *value < 16,
+        MidiMessage::SongPositionPointer { beats } => *beats <= 0x3FFF,
+        MidiMessage::SongSelect { song } => *song <= 127,
+        MidiMessage::Unknown { status, .. } => {
+            bail!("Can't send unknown status 0x{:02X}", status)
+        }
+        _ => true,
+    };
+    if !in_range {
+        bail!(
+            "Can't send {}: it isn't a valid MIDI message",
+            message.type_name()
+        );
+    }
+    Ok(())
+}
+
+/// Whether a subscription passes an event; see [`EventFilter`] for which
+/// criteria apply to which events.
+fn passes(filter: &EventFilter, message: &WireMessage) -> bool {
+    let event = &message.event;
+    let midi = match event {
+        ServerEvent::Midi(midi) => Some(midi),
+        _ => None,
+    };
+    let channel = match event {
+        ServerEvent::Midi(midi) => midi.channel(),
+        ServerEvent::Parameter(parameter) => Some(parameter.channel),
+        ServerEvent::NoteExpression(expression) => Some(expression.channel),
+        _ => None,
+    };
+    let note = match event {
+        ServerEvent::Midi(midi) => midi.note(),
+        ServerEvent::NoteExpression(expression) => Some(expression.note),
+        _ => None,
+    };
+    let controller = match midi {
+        Some(MidiMessage::ControlChange { controller, .. }) => Some(*controller),
+        _ => None,
+    };
+    (filter.kinds.is_empty() || filter.kinds.iter().any(|kind| kind == event.kind()))
+        && midi.map_or(true, |midi| {
+            filter.types.is_empty() || filter.types.iter().any(|name| name == midi.type_name())
+        })
+        && channel.map_or(true, |channel| {
+            filter.channels.is_empty() || filter.channels.contains(&channel)
+        })
+        && note
+            .zip(filter.notes)
+            .map_or(true, |(note, [low, high])| (low..=high).contains(&note))
+        && controller.map_or(true, |controller| {
+            filter.controllers.is_empty() || filter.controllers.contains(&controller)
+        })
+        && message.source.as_ref().map_or(true, |source| {
+            filter.sources.is_empty() || filter.sources.contains(&source.id)
+        })
+}
+
+/// Carries out a command from a client's text frame, returning the reply or
+/// error to send it.
+pub fn handle_request(state: &SharedState, session: &Mutex<Session>, text: &str) -> WireMessage {
+    // Read the id on its own first, so even a malformed command's error can
+    // say which request it answers
+    let (id, result) = match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(json) => {
+            let id = json.get("id").and_then(|id| id.as_u64());
+            let result = serde_json::from_value::<ClientRequest>(json)
+                .map_err(|e| anyhow!("Invalid client message: {}", e))
+                .and_then(|request| execute(state, session, request.command));
+            (id, result)
+        }
+        Err(e) => (None, Err(anyhow!("Invalid client message: {}", e))),
+    };
+    let event = match result {
+        Ok(reply) => ServerEvent::Reply(reply),
+        Err(e) => {
+            warn!("{}", e);
+            ServerEvent::Error {
+                message: e.to_string(),
+            }
+        }
+    };
+    state.lock().unwrap().stamped(event).with_reply_to(id)
+}
+
+fn execute(
+    state: &SharedState,
+    session: &Mutex<Session>,
+    command: ClientMessage,
+) -> anyhow::Result<CommandReply> {
+    match command {
+        ClientMessage::Send { output, message } => {
+            check_sendable(&message)?;
+            outputs::send(state, &output, &message)?;
+            Ok(CommandReply::Sent)
+        }
+        ClientMessage::Subscribe { filter } => Ok(CommandReply::Subscribed {
+            subscription: session.lock().unwrap().subscribe(filter)?,
+        }),
+        ClientMessage::Unsubscribe { subscription } => {
+            session.lock().unwrap().unsubscribe(subscription)?;
+            Ok(CommandReply::Unsubscribed)
+        }
+        ClientMessage::SelectInput { selector } => {
+            let selector: PortSelector = selector.parse().map_err(|e: String| anyhow!(e))?;
+            let description = selector.to_string();
+            let ports = inputs::switch_inputs(state, selector)?;
+            if ports.is_empty() {
+                bail!("No MIDI input matches `{}`", description);
+            }
+            Ok(CommandReply::InputSelected { ports })
+        }
+        ClientMessage::Panic { output } => Ok(CommandReply::Panicked {
+            outputs: outputs::panic(state, output.as_deref())?,
+        }),
+        ClientMessage::StartRecording => {
+            session.lock().unwrap().start_recording()?;
+            Ok(CommandReply::RecordingStarted)
+        }
+        ClientMessage::StopRecording => Ok(CommandReply::RecordingStopped {
+            events: session.lock().unwrap().stop_recording()?,
+        }),
+        ClientMessage::Simulation { enabled } => {
+            info!("Simulation {}", if enabled { "on" } else { "off" });
+            state.lock().unwrap().simulation = enabled;
+            Ok(CommandReply::Simulation { enabled })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AppState;
+    use midi_protocol::EventSource;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_subscriptions() {
+        let state = Arc::new(Mutex::new(AppState::new(100)));
+        let session = Mutex::new(Session::default());
+        let midi = WireMessage::from(ServerEvent::from(MidiMessage::Start));
+        let inputs = WireMessage::from(ServerEvent::Inputs { ports: Vec::new() });
+        assert!(session.lock().unwrap().wants(&inputs));
+
+        let reply = handle_request(
+            &state,
+            &session,
+            r#"{"id":1,"kind":"subscribe","filter":{"kinds":["midi"]}}"#,
+        );
+        assert_eq!(reply.reply_to, Some(1));
+        assert_eq!(
+            reply.event,
+            ServerEvent::Reply(CommandReply::Subscribed { subscription: 1 })
+        );
+        assert!(session.lock().unwrap().wants(&midi));
+        assert!(!session.lock().unwrap().wants(&inputs));
+        assert!(session.lock().unwrap().wants(&reply));
+
+        let reply = handle_request(
+            &state,
+            &session,
+            r#"{"id":2,"kind":"subscribe","filter":{"kinds":["midy"]}}"#,
+        );
+        assert!(matches!(reply.event, ServerEvent::Error { .. }));
+
+        let reply = handle_request(
+            &state,
+            &session,
+            r#"{"kind":"unsubscribe","subscription":1}"#,
+        );
+        assert_eq!(reply.reply_to, None);
+        assert_eq!(reply.event, ServerEvent::Reply(CommandReply::Unsubscribed));
+        assert!(session.lock().unwrap().wants(&inputs));
+        let reply = handle_request(
+            &state,
+            &session,
+            r#"{"kind":"unsubscribe","subscription":1}"#,
+        );
+        assert!(matches!(reply.event, ServerEvent::Error { .. }));
+    }
+
+    #[test]
+    fn test_filters() {
+        let control_change = |channel, controller| {
+            WireMessage::from(ServerEvent::from(MidiMessage::ControlChange {
+                channel,
+                controller,
+                value: 64,
+            }))
+        };
+        let note_on = |channel, note| {
+            WireMessage::from(ServerEvent::from(MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity: 100,
+            }))
+        };
+        let clock = WireMessage::from(ServerEvent::from(MidiMessage::TimingClock));
+
+        // One channel's mod wheel and cutoff
+        let dashboard = EventFilter {
+            kinds: vec!["midi".to_string()],
+            channels: vec![0],
+            types: vec!["ControlChange".to_string()],
+            controllers: vec![1, 74],
+            ..EventFilter::default()
+        };
+        assert!(passes(&dashboard, &control_change(0, 74)));
+        assert!(!passes(&dashboard, &control_change(0, 7)));
+        assert!(!passes(&dashboard, &control_change(1, 74)));
+        assert!(!passes(&dashboard, &note_on(0, 60)));
+        assert!(!passes(&dashboard, &clock));
+        assert!(!passes(
+            &dashboard,
+            &WireMessage::from(ServerEvent::Inputs { ports: Vec::new() })
+        ));
+
+        // Criteria don't stop events they can't describe
+        let drums = EventFilter {
+            channels: vec![9],
+            notes: Some([36, 51]),
+            sources: vec!["input:MPD218".to_string()],
+            ..EventFilter::default()
+        };
+        let pad = note_on(9, 36).with_source(EventSource {
+            id: "input:MPD218".to_string(),
+            name: "MPD218".to_string(),
+        });
+        assert!(passes(&drums, &pad));
+        assert!(passes(&drums, &clock));
+        assert!(passes(&drums, &control_change(9, 7)));
+        assert!(!passes(&drums, &note_on(9, 60)));
+        assert!(!passes(&drums, &note_on(0, 36)));
+        let keys = EventSource {
+            id: "input:Keystation 49".to_string(),
+            name: "Keystation 49".to_string(),
+        };
+        assert!(!passes(&drums, &note_on(9, 36).with_source(keys)));
+
+        let invalid = [
+            r#"{"kind":"subscribe","filter":{"types":["NoteOnn"]}}"#,
+            r#"{"kind":"subscribe","filter":{"channels":[16]}}"#,
+            r#"{"kind":"subscribe","filter":{"notes":[60,48]}}"#,
+            r#"{"kind":"subscribe","filter":{"controllers":[128]}}"#,
+        ];
+        let state = Arc::new(Mutex::new(AppState::new(100)));
+        let session = Mutex::new(Session::default());
+        for text in invalid {
+            let reply = handle_request(&state, &session, text);
+            assert!(matches!(reply.event, ServerEvent::Error { .. }), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_recording() {
+        let state = Arc::new(Mutex::new(AppState::new(100)));
+        let session = Mutex::new(Session::default());
+        let note_on = WireMessage::from(ServerEvent::from(MidiMessage::NoteOn {
+            channel: 0,
+            note: 60,
+            velocity: 100,
+        }));
+        session.lock().unwrap().record(&note_on);
+
+        let reply = handle_request(&state, &session, r#"{"id":1,"kind":"start_recording"}"#);
+        assert_eq!(
+            reply.event,
+            ServerEvent::Reply(CommandReply::RecordingStarted)
+        );
+        let reply = handle_request(&state, &session, r#"{"id":2,"kind":"start_recording"}"#);
+        assert!(matches!(reply.event, ServerEvent::Error { .. }));
+        session.lock().unwrap().record(&note_on);
+        session
+            .lock()
+            .unwrap()
+            .record(&WireMessage::from(ServerEvent::Inputs {
+                ports: Vec::new(),
+            }));
+
+        let reply = handle_request(&state, &session, r#"{"id":3,"kind":"stop_recording"}"#);
+        assert_eq!(reply.reply_to, Some(3));
+        assert_eq!(
+            reply.event,
+            ServerEvent::Reply(CommandReply::RecordingStopped {
+                events: vec![note_on.clone()]
+            })
+        );
+        let reply = handle_request(&state, &session, r#"{"id":4,"kind":"stop_recording"}"#);
+        assert!(matches!(reply.event, ServerEvent::Error { .. }));
+
+        // Events past the cap are dropped
+        handle_request(&state, &session, r#"{"kind":"start_recording"}"#);
+        for _ in 0..MAX_RECORDED_EVENTS + 10 {
+            session.lock().unwrap().record(&note_on);
+        }
+        let reply = handle_request(&state, &session, r#"{"kind":"stop_recording"}"#);
+        match reply.event {
+            ServerEvent::Reply(CommandReply::RecordingStopped { events }) => {
+                assert_eq!(events.len(), MAX_RECORDED_EVENTS)
+            }
+            other => panic!("expected recording, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_errors_are_correlated() {
+        let state = Arc::new(Mutex::new(AppState::new(100)));
+        let session = Mutex::new(Session::default());
+        let reply = handle_request(
+            &state,
+            &session,
+            r#"{"id":5,"kind":"send","output":"output:Synth"}"#,
+        );
+        assert_eq!(reply.reply_to, Some(5));
+        match reply.event {
+            ServerEvent::Error { message } => {
+                assert!(message.starts_with("Invalid client message"), "{}", message)
+            }
+            other => panic!("expected error, got {:?}", other),
+        }
+        let reply = handle_request(&state, &session, "not json");
+        assert_eq!(reply.reply_to, None);
+        assert!(matches!(reply.event, ServerEvent::Error { .. }));
+
+        let reply = handle_request(
+            &state,
+            &session,
+            r#"{"id":6,"kind":"simulation","enabled":false}"#,
+        );
+        assert_eq!(
+            reply.event,
+            ServerEvent::Reply(CommandReply::Simulation { enabled: false })
+        );
+        assert!(!state.lock().unwrap().simulation);
+    }
+
+    #[test]
+    fn test_send_rejects_invalid_messages() {
+        let state = Arc::new(Mutex::new(AppState::new(100)));
+        let session = Mutex::new(Session::default());
+        let invalid = [
+            r#"{"kind":"send","output":"output:Synth","message":{"type":"SysEx","manufacturer_id":[],"manufacturer":null,"universal":null,"payload_len":0,"data":[]}}"#,
+            r#"{"kind":"send","output":"output:Synth","message":{"type":"SysEx","manufacturer_id":[67],"manufacturer":null,"universal":null,"payload_len":1,"data":[240,67,144,247]}}"#,
+            r#"{"kind":"send","output":"output:Synth","message":{"type":"Unknown","status":244,"data":[1,2]}}"#,
+            r#"{"kind":"send","output":"output:Synth","message":{"type":"NoteOn","channel":0,"note":200,"velocity":100}}"#,
+        ];
+        for text in invalid {
+            match handle_request(&state, &session, text).event {
+                ServerEvent::Error { message } => {
+                    assert!(message.starts_with("Can't send"), "{}: {}", text, message)
+                }
+                other => panic!("expected error for {}, got {:?}", text, other),
+            }
+        }
+
+        assert!(check_sendable(&MidiMessage::NoteOn {
+            channel: 0,
+            note: 60,
+            velocity: 100
+        })
+        .is_ok());
+        let sysex = MidiMessage::parse(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap();
+        assert!(check_sendable(&sysex).is_ok());
+    }
+
+    #[test]
+    fn test_send_accepts_note_off_velocity_and_bare_sysex() {
+        let valid = [
+            r#"{"type":"NoteOn","channel":0,"note":60,"velocity":0}"#,
+            r#"{"type":"SysEx","data":[240,67,16,76,0,0,126,0,247]}"#,
+            r#"{"type":"PitchBend","channel":15,"value":-8192}"#,
+        ];
+        for text in valid {
+            let message: MidiMessage = serde_json::from_str(text).unwrap();
+            assert!(check_sendable(&message).is_ok(), "{}", text);
+        }
+        assert!(check_sendable(&MidiMessage::PitchBend {
+            channel: 0,
+            value: 8192
+        })
+        .is_err());
+    }
+}
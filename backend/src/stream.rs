@@ -0,0 +1,587 @@
+//! Stateful decoding and encoding of raw MIDI byte streams.
+//!
+//! midir hands over one complete message per callback, but DIN/serial
+//! links, network payloads and files deliver an arbitrary chunked byte
+//! stream. [`StreamDecoder`] reassembles that stream into messages, and
+//! [`StreamEncoder`] writes messages back out with running status.
+
+use midi_protocol::sysex::{SYSEX_END, SYSEX_START};
+use midi_protocol::{MalformedMessage, MalformedReason, MidiMessage};
+
+/// Longest SysEx message kept; bytes beyond this are dropped so a missing
+/// End of Exclusive can't grow the buffer without bound.
+pub const MAX_SYSEX_LEN: usize = 64 * 1024;
+
+/// Decodes a MIDI 1.0 byte stream delivered in arbitrary chunks.
+///
+/// Handles running status, System Real-Time bytes interleaved anywhere
+/// (including inside other messages and SysEx), and SysEx split across
+/// chunks. Bytes that can't be decoded come out as [`MalformedMessage`]s,
+/// for callers to publish like any other malformed input.
+#[derive(Debug, Default)]
+pub struct StreamDecoder {
+    running_status: Option<u8>,
+    /// Status and data bytes of the message being assembled.
+    pending: Vec<u8>,
+    /// Data bytes the pending message needs after its status byte.
+    expected: usize,
+    /// SysEx bytes received so far, starting with 0xF0.
+    sysex: Option<Vec<u8>>,
+}
+
+impl StreamDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds the next chunk of the stream, returning every message it
+    /// completed and every run of bytes it had to drop, in stream order.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Result<MidiMessage, MalformedMessage>> {
+        let mut messages = Vec::new();
+        for &byte in chunk {
+            self.push_byte(byte, &mut messages);
+        }
+        messages
+    }
+
+    /// Drops any partial message and running status, e.g. after the
+    /// underlying link was reset.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn push_byte(&mut self, byte: u8, out: &mut Vec<Result<MidiMessage, MalformedMessage>>) {
+        if byte >= 0xF8 {
+            // System Real-Time can appear anywhere and affects nothing else
+            out.push(MidiMessage::parse(&[byte]));
+            return;
+        }
+
+        if byte & 0x80 != 0 {
+            // Any other status byte ends a SysEx, terminated or not
+            if byte == SYSEX_END {
+                match self.sysex.take() {
+                    Some(mut sysex) => {
+                        sysex.push(SYSEX_END);
+                        out.push(MidiMessage::parse(&sysex));
+                    }
+                    None => out.push(Err(malformed(
+                        MalformedReason::UnexpectedStatus,
+                        vec![byte],
+                    ))),
+                }
+            } else {
+                self.finish_sysex(out);
+            }
+            // A message cut short by a new status byte
+            if !self.pending.is_empty() {
+                let truncated = std::mem::take(&mut self.pending);
+                out.push(Err(malformed(MalformedReason::Truncated, truncated)));
+            }
+
+            match byte {
+                SYSEX_START => {
+                    self.running_status = None;
+                    self.sysex = Some(vec![SYSEX_START]);
+                }
+                SYSEX_END => self.running_status = None,
+                _ => {
+                    // Channel messages set running status, System Common clears it
+                    self.running_status = (byte < 0xF0).then_some(byte);
+                    self.pending.push(byte);
+                    self.expected = MidiMessage::data_len(byte);
+                    self.complete_pending(out);
+                }
+            }
+            return;
+        }
+
+        if let Some(sysex) = &mut self.sysex {
+            if sysex.len() < MAX_SYSEX_LEN {
+                sysex.push(byte);
+            }
+            return;
+        }
+
+        if self.pending.is_empty() {
+            match self.running_status {
+                Some(status) => {
+                    self.pending.push(status);
+                    self.expected = MidiMessage::data_len(status);
+                }
+                // Stray data byte with nothing to attach it to
+                None => {
+                    out.push(Err(malformed(
+                        MalformedReason::UnexpectedStatus,
+                        vec![byte],
+                    )));
+                    return;
+                }
+            }
+        }
+        self.pending.push(byte);
+        self.complete_pending(out);
+    }
+
+    fn complete_pending(&mut self, out: &mut Vec<Result<MidiMessage, MalformedMessage>>) {
+        if self.pending.len() == 1 + self.expected {
+            out.push(MidiMessage::parse(&self.pending));
+            self.pending.clear();
+        }
+    }
+
+    /// Emits a SysEx cut short by another status byte.
+    fn finish_sysex(&mut self, out: &mut Vec<Result<MidiMessage, MalformedMessage>>) {
+        if let Some(sysex) = self.sysex.take() {
+            out.push(MidiMessage::parse(&sysex));
+        }
+    }
+}
+
+fn malformed(reason: MalformedReason, data: Vec<u8>) -> MalformedMessage {
+    MalformedMessage { reason, data }
+}
+
+/// Encodes messages into a MIDI 1.0 byte stream, leaving out the status
+/// byte of a channel message that repeats the previous one (running
+/// status).
+///
+/// Real-time messages don't interrupt running status; SysEx and System
+/// Common messages cancel it. A Note Off with velocity 0 is sent as a Note
+/// On with velocity 0 when that keeps running status going.
+#[derive(Debug, Default)]
+pub struct StreamEncoder {
+    running_status: Option<u8>,
+}
+
+impl StreamEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends the bytes of the next message to `out`.
+    pub fn push(&mut self, message: &MidiMessage, out: &mut Vec<u8>) {
+        let mut bytes = message.to_bytes();
+        let Some(&status) = bytes.first() else {
+            return;
+        };
+        if message.is_realtime() {
+            out.extend(bytes);
+            return;
+        }
+        if status >= 0xF0 {
+            self.running_status = None;
+            out.extend(bytes);
+            return;
+        }
+
+        if let MidiMessage::NoteOff { velocity: 0, .. } = message {
+            let note_on = 0x90 | (status & 0x0F);
+            if self.running_status == Some(note_on) {
+                bytes[0] = note_on;
+            }
+        }
+        if self.running_status == Some(bytes[0]) {
+            out.extend(&bytes[1..]);
+        } else {
+            self.running_status = Some(bytes[0]);
+            out.extend(bytes);
+        }
+    }
+
+    /// Encodes a sequence of messages as one stream.
+    pub fn encode<'a>(&mut self, messages: impl IntoIterator<Item = &'a MidiMessage>) -> Vec<u8> {
+        let mut out = Vec::new();
+        for message in messages {
+            self.push(message, &mut out);
+        }
+        out
+    }
+
+    /// Forgets running status, so the next channel message is sent in
+    /// full, e.g. when the receiver may have just been connected.
+    pub fn reset(&mut self) {
+        self.running_status = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Decodes a chunk that should hold nothing malformed.
+    fn decode(decoder: &mut StreamDecoder, chunk: &[u8]) -> Vec<MidiMessage> {
+        decoder
+            .push(chunk)
+            .into_iter()
+            .map(|decoded| decoded.unwrap())
+            .collect()
+    }
+
+    fn note_on(channel: u8, note: u8, velocity: u8) -> MidiMessage {
+        MidiMessage::NoteOn {
+            channel,
+            note,
+            velocity,
+        }
+    }
+
+    #[test]
+    fn test_complete_messages() {
+        let mut decoder = StreamDecoder::new();
+        let messages = decode(&mut decoder, &[0x90, 60, 100, 0xB1, 7, 90, 0xC2, 5]);
+        assert_eq!(
+            messages,
+            vec![
+                note_on(0, 60, 100),
+                MidiMessage::ControlChange {
+                    channel: 1,
+                    controller: 7,
+                    value: 90
+                },
+                MidiMessage::ProgramChange {
+                    channel: 2,
+                    program: 5
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_running_status() {
+        let mut decoder = StreamDecoder::new();
+        let messages = decode(&mut decoder, &[0x90, 60, 100, 62, 100, 60, 0]);
+        assert_eq!(
+            messages,
+            vec![
+                note_on(0, 60, 100),
+                note_on(0, 62, 100),
+                MidiMessage::NoteOff {
+                    channel: 0,
+                    note: 60,
+                    velocity: 0
+                },
+            ]
+        );
+
+        // Running status carries across chunks, one byte at a time
+        let mut decoder = StreamDecoder::new();
+        let mut messages = Vec::new();
+        for byte in [0xD0, 10, 20, 30] {
+            messages.extend(decode(&mut decoder, &[byte]));
+        }
+        assert_eq!(messages.len(), 3);
+        assert_eq!(
+            messages[2],
+            MidiMessage::ChannelPressure {
+                channel: 0,
+                pressure: 30
+            }
+        );
+    }
+
+    #[test]
+    fn test_system_common_cancels_running_status() {
+        let mut decoder = StreamDecoder::new();
+        let decoded = decoder.push(&[0x90, 60, 100, 0xF6, 62]);
+        assert_eq!(
+            decoded,
+            vec![
+                Ok(note_on(0, 60, 100)),
+                Ok(MidiMessage::TuneRequest),
+                Err(malformed(MalformedReason::UnexpectedStatus, vec![62])),
+            ]
+        );
+
+        let decoded = decoder.push(&[0x80, 60, 0, 0xF2, 0x10, 0x00, 61]);
+        assert_eq!(
+            decoded,
+            vec![
+                Ok(MidiMessage::NoteOff {
+                    channel: 0,
+                    note: 60,
+                    velocity: 0
+                }),
+                Ok(MidiMessage::SongPositionPointer { beats: 0x10 }),
+                Err(malformed(MalformedReason::UnexpectedStatus, vec![61])),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_realtime_inside_message() {
+        let mut decoder = StreamDecoder::new();
+        let messages = decode(&mut decoder, &[0x90, 0xF8, 60, 0xFE, 100, 62, 0xFA, 100]);
+        assert_eq!(
+            messages,
+            vec![
+                MidiMessage::TimingClock,
+                MidiMessage::ActiveSensing,
+                note_on(0, 60, 100),
+                MidiMessage::Start,
+                note_on(0, 62, 100),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sysex_split_across_chunks() {
+        let mut decoder = StreamDecoder::new();
+        assert!(decode(&mut decoder, &[0xF0, 0x7E]).is_empty());
+        assert_eq!(
+            decode(&mut decoder, &[0x7F, 0xF8, 0x06]),
+            vec![MidiMessage::TimingClock]
+        );
+        let messages = decode(&mut decoder, &[0x01, 0xF7, 0x90, 60, 100]);
+        assert_eq!(messages.len(), 2);
+        match &messages[0] {
+            MidiMessage::SysEx(sysex) => {
+                assert_eq!(sysex.data, vec![0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7])
+            }
+            other => panic!("expected SysEx, got {:?}", other),
+        }
+        assert_eq!(messages[1], note_on(0, 60, 100));
+    }
+
+    #[test]
+    fn test_unterminated_sysex() {
+        let mut decoder = StreamDecoder::new();
+        let messages = decode(&mut decoder, &[0xF0, 0x43, 0x10, 0x90, 60, 100]);
+        assert_eq!(messages.len(), 2);
+        match &messages[0] {
+            MidiMessage::SysEx(sysex) => assert_eq!(sysex.data, vec![0xF0, 0x43, 0x10]),
+            other => panic!("expected SysEx, got {:?}", other),
+        }
+        assert_eq!(messages[1], note_on(0, 60, 100));
+
+        // Running status doesn't survive a SysEx
+        let decoded = decoder.push(&[0xF0, 0x43, 0xF7, 60]);
+        assert_eq!(decoded.len(), 2);
+        assert!(decoded[0].is_ok());
+        assert_eq!(
+            decoded[1],
+            Err(malformed(MalformedReason::UnexpectedStatus, vec![60]))
+        );
+    }
+
+    #[test]
+    fn test_oversized_sysex_is_capped() {
+        let mut decoder = StreamDecoder::new();
+        decode(&mut decoder, &[0xF0, 0x43]);
+        decode(&mut decoder, &vec![0x01; MAX_SYSEX_LEN * 2]);
+        let messages = decode(&mut decoder, &[0xF7]);
+        match &messages[0] {
+            MidiMessage::SysEx(sysex) => assert_eq!(sysex.data.len(), MAX_SYSEX_LEN + 1),
+            other => panic!("expected SysEx, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_stray_data_bytes_are_malformed() {
+        let mut decoder = StreamDecoder::new();
+        assert_eq!(
+            decoder.push(&[0x40, 0x90, 60, 100]),
+            vec![
+                Err(malformed(MalformedReason::UnexpectedStatus, vec![0x40])),
+                Ok(note_on(0, 60, 100)),
+            ]
+        );
+
+        // A lone End of Exclusive cancels running status
+        let decoded = decoder.push(&[0xF7, 1]);
+        assert_eq!(
+            decoded,
+            vec![
+                Err(malformed(MalformedReason::UnexpectedStatus, vec![0xF7])),
+                Err(malformed(MalformedReason::UnexpectedStatus, vec![1])),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_message_cut_short_is_truncated() {
+        let mut decoder = StreamDecoder::new();
+        assert_eq!(
+            decoder.push(&[0x90, 60, 0x80, 60, 0]),
+            vec![
+                Err(malformed(MalformedReason::Truncated, vec![0x90, 60])),
+                Ok(MidiMessage::NoteOff {
+                    channel: 0,
+                    note: 60,
+                    velocity: 0
+                }),
+            ]
+        );
+
+        // Running status resumes a message, so one cut short keeps its status
+        assert_eq!(
+            decoder.push(&[62, 0xC0, 1]),
+            vec![
+                Err(malformed(MalformedReason::Truncated, vec![0x80, 62])),
+                Ok(MidiMessage::ProgramChange {
+                    channel: 0,
+                    program: 1
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encoder_running_status() {
+        let messages = [
+            note_on(0, 60, 100),
+            MidiMessage::TimingClock,
+            note_on(0, 64, 100),
+            MidiMessage::NoteOff {
+                channel: 0,
+                note: 60,
+                velocity: 0,
+            },
+            MidiMessage::NoteOff {
+                channel: 0,
+                note: 64,
+                velocity: 40,
+            },
+            MidiMessage::SongSelect { song: 2 },
+            MidiMessage::NoteOff {
+                channel: 0,
+                note: 67,
+                velocity: 40,
+            },
+        ];
+        let bytes = StreamEncoder::new().encode(&messages);
+        assert_eq!(
+            bytes,
+            vec![0x90, 60, 100, 0xF8, 64, 100, 60, 0, 0x80, 64, 40, 0xF3, 2, 0x80, 67, 40]
+        );
+        assert_eq!(decode(&mut StreamDecoder::new(), &bytes), messages.to_vec());
+    }
+
+    /// Raw bytes for a random channel voice message.
+    fn channel_message() -> impl Strategy<Value = Vec<u8>> {
+        (0x80u8..0xF0, any::<[u8; 2]>()).prop_map(|(status, data)| {
+            let mut bytes = vec![status];
+            bytes.extend(
+                data.iter()
+                    .take(MidiMessage::data_len(status))
+                    .map(|b| b & 0x7F),
+            );
+            bytes
+        })
+    }
+
+    /// A random message of any kind the encoder can write.
+    fn any_message() -> impl Strategy<Value = MidiMessage> {
+        let sysex = prop::collection::vec(0u8..0x80, 1..32).prop_map(|mut data| {
+            data.insert(0, 0xF0);
+            data.push(0xF7);
+            data
+        });
+        let system = (
+            prop::sample::select(vec![0xF1, 0xF2, 0xF3, 0xF6]),
+            any::<[u8; 2]>(),
+        )
+            .prop_map(|(status, data)| {
+                let mut bytes = vec![status];
+                bytes.extend(
+                    data.iter()
+                        .take(MidiMessage::data_len(status))
+                        .map(|b| b & 0x7F),
+                );
+                bytes
+            });
+        prop_oneof![
+            channel_message(),
+            system,
+            realtime_byte().prop_map(|byte| vec![byte]),
+            sysex,
+        ]
+        .prop_filter_map("not a valid message", |bytes| {
+            MidiMessage::from_raw_message(&bytes)
+        })
+    }
+
+    fn realtime_byte() -> impl Strategy<Value = u8> {
+        prop::sample::select(vec![0xF8, 0xFA, 0xFB, 0xFC, 0xFE])
+    }
+
+    fn split_into_chunks(bytes: &[u8], cuts: &[usize]) -> Vec<Vec<u8>> {
+        let mut cuts: Vec<usize> = cuts.iter().map(|c| c % (bytes.len() + 1)).collect();
+        cuts.sort_unstable();
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        for cut in cuts {
+            chunks.push(bytes[start..cut].to_vec());
+            start = cut;
+        }
+        chunks.push(bytes[start..].to_vec());
+        chunks
+    }
+
+    proptest! {
+        #[test]
+        fn prop_chunking_does_not_change_output(
+            bytes in prop::collection::vec(any::<u8>(), 0..256),
+            cuts in prop::collection::vec(any::<usize>(), 0..16),
+        ) {
+            let whole = StreamDecoder::new().push(&bytes);
+
+            let mut decoder = StreamDecoder::new();
+            let mut chunked = Vec::new();
+            for chunk in split_into_chunks(&bytes, &cuts) {
+                chunked.extend(decoder.push(&chunk));
+            }
+            prop_assert_eq!(whole, chunked);
+        }
+
+        #[test]
+        fn prop_running_status_and_realtime_round_trip(
+            messages in prop::collection::vec(channel_message(), 1..32),
+            realtime in prop::collection::vec((any::<usize>(), realtime_byte()), 0..16),
+            cuts in prop::collection::vec(any::<usize>(), 0..8),
+        ) {
+            let expected: Vec<MidiMessage> = messages
+                .iter()
+                .filter_map(|bytes| MidiMessage::from_raw_message(bytes))
+                .collect();
+
+            // Compress with running status
+            let mut stream = Vec::new();
+            let mut last_status = None;
+            for bytes in &messages {
+                if last_status == Some(bytes[0]) {
+                    stream.extend(&bytes[1..]);
+                } else {
+                    stream.extend(bytes);
+                }
+                last_status = Some(bytes[0]);
+            }
+
+            // Sprinkle real-time bytes anywhere
+            for (position, byte) in &realtime {
+                let position = position % (stream.len() + 1);
+                stream.insert(position, *byte);
+            }
+
+            let mut decoder = StreamDecoder::new();
+            let mut decoded = Vec::new();
+            for chunk in split_into_chunks(&stream, &cuts) {
+                decoded.extend(decode(&mut decoder, &chunk));
+            }
+
+            let (decoded_realtime, decoded_other): (Vec<_>, Vec<_>) =
+                decoded.into_iter().partition(|m| m.is_realtime());
+            prop_assert_eq!(decoded_other, expected);
+            prop_assert_eq!(decoded_realtime.len(), realtime.len());
+        }
+
+        #[test]
+        fn prop_encoder_round_trips_through_decoder(
+            messages in prop::collection::vec(any_message(), 0..64),
+        ) {
+            let bytes = StreamEncoder::new().encode(&messages);
+            prop_assert_eq!(decode(&mut StreamDecoder::new(), &bytes), messages);
+        }
+    }
+}
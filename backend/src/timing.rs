@@ -0,0 +1,77 @@
+//! Event timestamps: device stamps mapped onto the server clock, and the
+//! wall-clock capture time.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Device stamps further than this from the server clock mean the driver's
+/// time base has restarted.
+const RESYNC_THRESHOLD_US: u64 = 1_000_000;
+
+/// Maps a device's timestamps (midir's microseconds, counted from whenever
+/// the driver likes) onto the server's monotonic clock.
+///
+/// The first stamp fixes the offset between the two clocks; after that the
+/// device's own spacing between events is kept, so deltas have the driver's
+/// precision rather than the jitter of when the callback happened to run.
+#[derive(Debug, Clone, Default)]
+pub struct SourceClock {
+    offset: Option<i64>,
+}
+
+impl SourceClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Converts a device stamp to server microseconds, given the server
+    /// time at which it was received.
+    pub fn map_stamp(&mut self, stamp_us: u64, now_us: u64) -> u64 {
+        let offset = *self.offset.get_or_insert(now_us as i64 - stamp_us as i64);
+        let mapped = (stamp_us as i64 + offset).max(0) as u64;
+        // An event can't arrive before it happened: a stamp mapping into the
+        // future means the first one was delivered late, so tighten the offset
+        if mapped > now_us || mapped.abs_diff(now_us) > RESYNC_THRESHOLD_US {
+            self.offset = Some(now_us as i64 - stamp_us as i64);
+            return now_us;
+        }
+        mapped
+    }
+}
+
+/// Microseconds since the Unix epoch.
+pub fn unix_time_us() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_micros() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_device_spacing() {
+        let mut clock = SourceClock::new();
+        assert_eq!(clock.map_stamp(5_000, 100_000), 100_000);
+        // The callback ran late, but the device says 250us passed
+        assert_eq!(clock.map_stamp(5_250, 100_900), 100_250);
+        assert_eq!(clock.map_stamp(6_000, 101_100), 101_000);
+    }
+
+    #[test]
+    fn test_tightens_offset_after_late_first_stamp() {
+        let mut clock = SourceClock::new();
+        clock.map_stamp(5_000, 100_500);
+        assert_eq!(clock.map_stamp(6_000, 101_000), 101_000);
+        assert_eq!(clock.map_stamp(6_300, 101_800), 101_300);
+    }
+
+    #[test]
+    fn test_resyncs_when_device_clock_restarts() {
+        let mut clock = SourceClock::new();
+        clock.map_stamp(50_000_000, 100_000);
+        assert_eq!(clock.map_stamp(10, 2_000_000), 2_000_000);
+        assert_eq!(clock.map_stamp(110, 2_000_200), 2_000_100);
+    }
+}
@@ -0,0 +1,68 @@
+//! Virtual MIDI ports other applications can connect to, so the monitor can
+//! sit between them. Only ALSA and CoreMIDI can create them.
+
+use crate::SharedState;
+use midi_protocol::MidiMessage;
+use midir::{MidiInputConnection, MidiOutputConnection};
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// A virtual output passing on the monitored stream.
+#[derive(Clone)]
+pub struct Thru {
+    connection: Arc<Mutex<MidiOutputConnection>>,
+}
+
+impl Thru {
+    pub fn send(&self, message: &MidiMessage) {
+        // Nobody needs to be connected, and nothing waits on it if so
+        if let Err(e) = self.connection.lock().unwrap().send(&message.to_bytes()) {
+            debug!("Failed to pass on MIDI: {}", e);
+        }
+    }
+}
+
+/// Creates a virtual input port and publishes whatever applications send it.
+#[cfg(unix)]
+pub fn create_input(state: &SharedState, name: &str) -> anyhow::Result<MidiInputConnection<()>> {
+    use crate::inputs;
+    use midi_protocol::EventSource;
+    use midir::os::unix::VirtualInput;
+
+    let source = EventSource {
+        id: format!("virtual:{}", name),
+        name: name.to_string(),
+    };
+    midir::MidiInput::new(name)?
+        .create_virtual(name, inputs::input_callback(state.clone(), source), ())
+        .map_err(|e| anyhow::anyhow!("Failed to create virtual input {}: {}", name, e))
+}
+
+/// Creates a virtual output port for [`Thru`].
+#[cfg(unix)]
+pub fn create_output(name: &str) -> anyhow::Result<Thru> {
+    use midir::os::unix::VirtualOutput;
+
+    let connection = midir::MidiOutput::new(name)?
+        .create_virtual(name)
+        .map_err(|e| anyhow::anyhow!("Failed to create virtual output {}: {}", name, e))?;
+    Ok(Thru {
+        connection: Arc::new(Mutex::new(connection)),
+    })
+}
+
+#[cfg(not(unix))]
+pub fn create_input(_state: &SharedState, name: &str) -> anyhow::Result<MidiInputConnection<()>> {
+    anyhow::bail!(
+        "Can't create virtual input {}: not supported on this platform",
+        name
+    )
+}
+
+#[cfg(not(unix))]
+pub fn create_output(name: &str) -> anyhow::Result<Thru> {
+    anyhow::bail!(
+        "Can't create virtual output {}: not supported on this platform",
+        name
+    )
+}
@@ -0,0 +1,186 @@
+//! Notices MIDI devices being plugged in and unplugged by diffing the port
+//! list every so often.
+
+use crate::{inputs, ports, SharedState};
+use midi_protocol::PortInfo;
+use std::time::Duration;
+use tracing::{error, warn};
+
+/// How often the port list is checked.
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Where the watcher gets the host's ports from, so it can be tested
+/// without hardware.
+pub trait PortProvider {
+    fn ports(&self) -> anyhow::Result<Vec<PortInfo>>;
+}
+
+/// The host's ports as midir sees them.
+pub struct MidirPorts;
+
+impl PortProvider for MidirPorts {
+    fn ports(&self) -> anyhow::Result<Vec<PortInfo>> {
+        ports::list_ports(&[])
+    }
+}
+
+/// Ports that appeared or went away since the last check.
+#[derive(Debug, Default, PartialEq)]
+pub struct PortChanges {
+    pub connected: Vec<PortInfo>,
+    pub disconnected: Vec<PortInfo>,
+}
+
+impl PortChanges {
+    pub fn is_empty(&self) -> bool {
+        self.connected.is_empty() && self.disconnected.is_empty()
+    }
+}
+
+/// Remembers the last port list to diff the next one against.
+pub struct DeviceWatcher<P> {
+    provider: P,
+    known: Vec<PortInfo>,
+}
+
+impl<P: PortProvider> DeviceWatcher<P> {
+    /// Starts from the ports there are now, which count as neither
+    /// connected nor disconnected.
+    pub fn new(provider: P) -> anyhow::Result<Self> {
+        let known = provider.ports()?;
+        Ok(Self { provider, known })
+    }
+
+    /// Lists the ports again and reports what changed, by port id. If
+    /// listing fails, the ports known so far are kept.
+    pub fn poll(&mut self) -> anyhow::Result<PortChanges> {
+        let current = self.provider.ports()?;
+        let is_in = |ports: &[PortInfo], port: &PortInfo| ports.iter().any(|p| p.id == port.id);
+        let changes = PortChanges {
+            connected: current
+                .iter()
+                .filter(|port| !is_in(&self.known, port))
+                .cloned()
+                .collect(),
+            disconnected: self
+                .known
+                .iter()
+                .filter(|port| !is_in(&current, port))
+                .cloned()
+                .collect(),
+        };
+        self.known = current;
+        Ok(changes)
+    }
+}
+
+/// Checks for port changes forever, connecting and disconnecting inputs as
+/// they come and go.
+pub async fn watch_devices<P: PortProvider + Send + 'static>(
+    state: SharedState,
+    mut watcher: DeviceWatcher<P>,
+) {
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    loop {
+        interval.tick().await;
+        // Listing ports and opening or closing inputs call into midir
+        let state = state.clone();
+        let checked = tokio::task::spawn_blocking(move || {
+            check(&state, &mut watcher);
+            watcher
+        })
+        .await;
+        match checked {
+            Ok(checked) => watcher = checked,
+            Err(e) => {
+                error!("MIDI device watcher stopped: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+fn check<P: PortProvider>(state: &SharedState, watcher: &mut DeviceWatcher<P>) {
+    match watcher.poll() {
+        Ok(changes) if changes.is_empty() => {}
+        Ok(changes) => {
+            if let Err(e) = inputs::handle_port_changes(state, &changes) {
+                warn!("Failed to update MIDI inputs: {}", e);
+            }
+        }
+        Err(e) => warn!("Failed to list MIDI ports: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midi_protocol::PortDirection;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+
+    /// Hands out port lists from a script, failing once it runs out.
+    struct MockPorts(RefCell<VecDeque<Vec<&'static str>>>);
+
+    impl MockPorts {
+        fn new(lists: &[&[&'static str]]) -> Self {
+            Self(RefCell::new(
+                lists.iter().map(|list| list.to_vec()).collect(),
+            ))
+        }
+    }
+
+    impl PortProvider for MockPorts {
+        fn ports(&self) -> anyhow::Result<Vec<PortInfo>> {
+            let names = self
+                .0
+                .borrow_mut()
+                .pop_front()
+                .ok_or_else(|| anyhow::anyhow!("no more port lists"))?;
+            Ok(names.into_iter().map(port).collect())
+        }
+    }
+
+    fn port(name: &str) -> PortInfo {
+        PortInfo {
+            id: format!("input:{}", name),
+            name: name.to_string(),
+            direction: PortDirection::Input,
+            connected: false,
+        }
+    }
+
+    #[test]
+    fn test_reports_plugged_and_unplugged_ports() {
+        let mut watcher = DeviceWatcher::new(MockPorts::new(&[
+            &["Midi Through"],
+            &["Midi Through"],
+            &["Midi Through", "Keystation 49"],
+            &["Keystation 49", "MPD218"],
+        ]))
+        .unwrap();
+
+        assert!(watcher.poll().unwrap().is_empty());
+        assert_eq!(
+            watcher.poll().unwrap(),
+            PortChanges {
+                connected: vec![port("Keystation 49")],
+                disconnected: vec![],
+            }
+        );
+        assert_eq!(
+            watcher.poll().unwrap(),
+            PortChanges {
+                connected: vec![port("MPD218")],
+                disconnected: vec![port("Midi Through")],
+            }
+        );
+    }
+
+    #[test]
+    fn test_keeps_known_ports_when_listing_fails() {
+        let mut watcher = DeviceWatcher::new(MockPorts::new(&[&["Keystation 49"]])).unwrap();
+        assert!(watcher.poll().is_err());
+        assert_eq!(watcher.known, vec![port("Keystation 49")]);
+    }
+}
@@ -1,50 +1,56 @@
 use leptos::*;
 use leptos_meta::*;
-use serde::{Deserialize, Serialize};
+use midi_protocol::{
+    note_name, ClientMessage, Describer, EventSource, MidiMessage, MpeLayout, NoteExpression,
+    PortDirection, PortInfo, ServerEvent, TimecodeDirection, TimecodeState, TransportState,
+    TransportStatus, WireMessage, PROTOCOL_VERSION,
+};
 use std::collections::HashMap;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
 use web_sys::{WebSocket, MessageEvent, ErrorEvent, CloseEvent};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct MidiMessage {
-    pub message_type: String,
-    pub note: Option<u8>,
-    pub velocity: Option<u8>,
-    pub control: Option<u8>,
-    pub value: Option<u8>,
-}
-
 #[derive(Debug, Clone, PartialEq)]
 struct MidiEvent {
-    message: MidiMessage,
+    event: ServerEvent,
+    description: Option<String>,
     timestamp: String,
+    timestamp_us: Option<u64>,
+    source: Option<EventSource>,
 }
 
 impl MidiEvent {
-    fn new(message: MidiMessage) -> Self {
-        let now = js_sys::Date::new_0();
+    fn new(wire: WireMessage) -> Self {
+        // Prefer the backend's capture time over when the browser got to it
+        let now = match wire.captured_at_us {
+            Some(captured_at_us) => js_sys::Date::new(&JsValue::from_f64(captured_at_us as f64 / 1000.0)),
+            None => js_sys::Date::new_0(),
+        };
         let timestamp = format!("{:02}:{:02}:{:02}.{:03}",
             now.get_hours(),
             now.get_minutes(), 
             now.get_seconds(),
             now.get_milliseconds()
         );
-        Self { message, timestamp }
+        Self {
+            event: wire.event,
+            description: wire.description,
+            timestamp,
+            timestamp_us: wire.timestamp_us,
+            source: wire.source,
+        }
     }
 }
 
 #[component]
-fn Piano(active_notes: ReadSignal<HashMap<u8, bool>>) -> impl IntoView {
+fn Piano(
+    active_notes: ReadSignal<HashMap<u8, bool>>,
+    expressions: ReadSignal<HashMap<u8, NoteExpression>>,
+) -> impl IntoView {
     // C4–B4 (12 keys) - includes all white and black keys
     let white_keys = [60, 62, 64, 65, 67, 69, 71]; // C4, D4, E4, F4, G4, A4, B4
 
-    let note_to_name = |note: u8| -> String {
-        let names = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
-        let octave = (note / 12) - 1;
-        format!("{}{}", names[(note % 12) as usize], octave)
-    };
-
     view! {
         <div class="relative inline-block">
             // White keys - horizontal layout
@@ -53,10 +59,11 @@ fn Piano(active_notes: ReadSignal<HashMap<u8, bool>>) -> impl IntoView {
                     let is_active = move || active_notes.get().get(&note).copied().unwrap_or(false);
                     view! {
                         <div class={move || format!(
-                            "bg-white border border-black w-12 h-40 inline-block flex items-end justify-center pb-2 text-xs font-mono piano-key piano-key-transition {}",
+                            "relative bg-white border border-black w-12 h-40 inline-block flex items-end justify-center pb-2 text-xs font-mono piano-key piano-key-transition {}",
                             if is_active() { "!bg-green-500" } else { "" }
                         )}>
-                            <span class="text-gray-600">{note_to_name(note)}</span>
+                            <ExpressionMeter note expressions/>
+                            <span class="text-gray-600">{note_name(note)}</span>
                         </div>
                     }
                 }).collect::<Vec<_>>()}
@@ -73,7 +80,8 @@ fn Piano(active_notes: ReadSignal<HashMap<u8, bool>>) -> impl IntoView {
                             "bg-black w-8 h-24 absolute ml-[-12px] z-10 flex items-end justify-center pb-2 text-xs font-mono piano-key black-key piano-key-transition {}",
                             if is_active() { "!bg-green-700" } else { "" }
                         )} style="left: 32px;">
-                            <span class="text-gray-300">{note_to_name(note)}</span>
+                            <ExpressionMeter note expressions/>
+                            <span class="text-gray-300">{note_name(note)}</span>
                         </div>
                     }
                 }
@@ -86,7 +94,8 @@ fn Piano(active_notes: ReadSignal<HashMap<u8, bool>>) -> impl IntoView {
                             "bg-black w-8 h-24 absolute ml-[-12px] z-10 flex items-end justify-center pb-2 text-xs font-mono piano-key black-key piano-key-transition {}",
                             if is_active() { "!bg-green-700" } else { "" }
                         )} style="left: 80px;">
-                            <span class="text-gray-300">{note_to_name(note)}</span>
+                            <ExpressionMeter note expressions/>
+                            <span class="text-gray-300">{note_name(note)}</span>
                         </div>
                     }
                 }
@@ -99,7 +108,8 @@ fn Piano(active_notes: ReadSignal<HashMap<u8, bool>>) -> impl IntoView {
                             "bg-black w-8 h-24 absolute ml-[-12px] z-10 flex items-end justify-center pb-2 text-xs font-mono piano-key black-key piano-key-transition {}",
                             if is_active() { "!bg-green-700" } else { "" }
                         )} style="left: 176px;">
-                            <span class="text-gray-300">{note_to_name(note)}</span>
+                            <ExpressionMeter note expressions/>
+                            <span class="text-gray-300">{note_name(note)}</span>
                         </div>
                     }
                 }
@@ -112,7 +122,8 @@ fn Piano(active_notes: ReadSignal<HashMap<u8, bool>>) -> impl IntoView {
                             "bg-black w-8 h-24 absolute ml-[-12px] z-10 flex items-end justify-center pb-2 text-xs font-mono piano-key black-key piano-key-transition {}",
                             if is_active() { "!bg-green-700" } else { "" }
                         )} style="left: 224px;">
-                            <span class="text-gray-300">{note_to_name(note)}</span>
+                            <ExpressionMeter note expressions/>
+                            <span class="text-gray-300">{note_name(note)}</span>
                         </div>
                     }
                 }
@@ -125,7 +136,8 @@ fn Piano(active_notes: ReadSignal<HashMap<u8, bool>>) -> impl IntoView {
                             "bg-black w-8 h-24 absolute ml-[-12px] z-10 flex items-end justify-center pb-2 text-xs font-mono piano-key black-key piano-key-transition {}",
                             if is_active() { "!bg-green-700" } else { "" }
                         )} style="left: 272px;">
-                            <span class="text-gray-300">{note_to_name(note)}</span>
+                            <ExpressionMeter note expressions/>
+                            <span class="text-gray-300">{note_name(note)}</span>
                         </div>
                     }
                 }
@@ -134,45 +146,490 @@ fn Piano(active_notes: ReadSignal<HashMap<u8, bool>>) -> impl IntoView {
     }
 }
 
+/// Pressure and slide of an MPE note, drawn on its key and shifted sideways
+/// by its pitch bend.
+#[component]
+fn ExpressionMeter(note: u8, expressions: ReadSignal<HashMap<u8, NoteExpression>>) -> impl IntoView {
+    move || expressions.get().get(&note).cloned().map(|expression| view! {
+        <div
+            class="absolute bottom-6 left-1/2 flex flex-col items-center pointer-events-none z-20"
+            style={format!("transform: translateX(calc(-50% + {:.0}px));", bend_offset_px(expression.bend))}
+        >
+            <div class="w-2 h-12 bg-gray-200 rounded flex items-end overflow-hidden">
+                <div class="w-full rounded" style={expression_fill_style(&expression)}></div>
+            </div>
+            <span class="text-[10px] font-mono text-orange-600">{format!("{:+.1}", expression.bend)}</span>
+        </div>
+    })
+}
+
+/// Horizontal shift for a bend, roughly one semitone per key, kept within
+/// two keys either way.
+fn bend_offset_px(bend: f64) -> f64 {
+    (bend * 28.0).clamp(-56.0, 56.0)
+}
+
+/// Fill height from pressure, hue from slide (blue at 0, red at 127).
+fn expression_fill_style(expression: &NoteExpression) -> String {
+    format!(
+        "height: {:.0}%; background-color: hsl({:.0}, 80%, 50%);",
+        expression.pressure as f64 / 127.0 * 100.0,
+        240.0 - expression.slide as f64 / 127.0 * 240.0
+    )
+}
+
 #[component]
-fn MidiEventLog(events: ReadSignal<Vec<MidiEvent>>) -> impl IntoView {
+fn MidiEventLog(
+    events: ReadSignal<Vec<MidiEvent>>,
+    sources: ReadSignal<Vec<EventSource>>,
+) -> impl IntoView {
+    // Source id to show, or every source
+    let (source_filter, set_source_filter) = create_signal(None::<String>);
+
     view! {
         <div class="bg-gray-100 border rounded-lg p-4 h-64 overflow-y-auto">
-            <h3 class="text-lg font-semibold mb-2">"MIDI Event Log"</h3>
-            <div class="space-y-1 font-mono text-sm">
-                {move || events.get().into_iter().rev().take(50).map(|event| {
-                    let color_class = match event.message.message_type.as_str() {
-                        "NoteOn" => "text-green-600",
-                        "NoteOff" => "text-red-600",
-                        "ControlChange" => "text-blue-600",
-                        _ => "text-gray-600",
-                    };
-                    view! {
-                        <div class={format!("flex justify-between {}", color_class)}>
-                            <span class="font-semibold">{event.timestamp}</span>
-                            <span>{format_midi_message(&event.message)}</span>
-                        </div>
+            <div class="flex justify-between items-center mb-2">
+                <h3 class="text-lg font-semibold">"MIDI Event Log"</h3>
+                <select
+                    class="text-sm border rounded px-2 py-1"
+                    on:change=move |ev| {
+                        let id = event_target_value(&ev);
+                        set_source_filter.set((!id.is_empty()).then_some(id));
                     }
-                }).collect::<Vec<_>>()}
+                >
+                    <option value="">"All sources"</option>
+                    {move || sources.get().into_iter().map(|source| view! {
+                        <option
+                            value={source.id.clone()}
+                            selected={source_filter.get().as_ref() == Some(&source.id)}
+                        >
+                            {source.name}
+                        </option>
+                    }).collect::<Vec<_>>()}
+                </select>
+            </div>
+            <div class="space-y-1 font-mono text-sm">
+                {move || {
+                    let sources = sources.get();
+                    let source_filter = source_filter.get();
+                    // Time since the previous row shown, from the source timestamps
+                    let mut previous_us = None;
+                    let rows: Vec<(MidiEvent, Option<u64>)> = events.get().into_iter()
+                        .filter(|event| match (&source_filter, &event.source) {
+                            (None, _) => true,
+                            (Some(id), Some(source)) => source.id == *id,
+                            // Server notices aren't from any one source
+                            (Some(_), None) => false,
+                        })
+                        .map(|event| {
+                            let delta_us = event.timestamp_us
+                                .zip(previous_us)
+                                .map(|(timestamp_us, previous_us)| timestamp_us.saturating_sub(previous_us));
+                            previous_us = event.timestamp_us;
+                            (event, delta_us)
+                        })
+                        .collect();
+                    rows.into_iter().rev()
+                        .take(MAX_LOG_EVENTS)
+                        .map(|(event, delta_us)| {
+                            let color_class = format!(
+                                "{} {}",
+                                event_color(&event.event),
+                                source_border(&sources, event.source.as_ref())
+                            );
+                            if let ServerEvent::Midi(MidiMessage::SysEx(sysex)) = &event.event {
+                                // SysEx rows expand to show the full message
+                                view! {
+                                    <details class={color_class}>
+                                        <summary class="flex justify-between cursor-pointer">
+                                            <span class="font-semibold">{event.timestamp.clone()}</span>
+                                            <span class="text-gray-400">{delta_us.map(format_delta).unwrap_or_default()}</span>
+                                            <span>{event.description.clone().unwrap_or_else(|| format_event(&event.event))}</span>
+                                        </summary>
+                                        <pre class="text-xs bg-white border rounded p-2 mt-1 overflow-x-auto">
+                                            {hex_dump(&sysex.data)}
+                                        </pre>
+                                    </details>
+                                }.into_view()
+                            } else {
+                                view! {
+                                    <div class={format!("flex justify-between {}", color_class)}>
+                                        <span class="font-semibold">{event.timestamp.clone()}</span>
+                                        <span class="text-gray-400">{delta_us.map(format_delta).unwrap_or_default()}</span>
+                                        <span>{event.description.clone().unwrap_or_else(|| format_event(&event.event))}</span>
+                                    </div>
+                                }.into_view()
+                            }
+                        }).collect::<Vec<_>>()
+                }}
+            </div>
+        </div>
+    }
+}
+
+/// Events kept in the log; older ones are dropped.
+const MAX_LOG_EVENTS: usize = 100;
+
+/// Left border colours telling sources apart, in the order they were first
+/// seen.
+const SOURCE_BORDERS: [&str; 6] = [
+    "border-l-4 pl-1 border-sky-400",
+    "border-l-4 pl-1 border-amber-400",
+    "border-l-4 pl-1 border-emerald-400",
+    "border-l-4 pl-1 border-fuchsia-400",
+    "border-l-4 pl-1 border-lime-400",
+    "border-l-4 pl-1 border-rose-400",
+];
+
+fn source_border(sources: &[EventSource], source: Option<&EventSource>) -> &'static str {
+    source
+        .and_then(|source| sources.iter().position(|known| known.id == source.id))
+        .map(|index| SOURCE_BORDERS[index % SOURCE_BORDERS.len()])
+        .unwrap_or("")
+}
+
+fn event_color(event: &ServerEvent) -> &'static str {
+    match event {
+        ServerEvent::Midi(message) => match message {
+            MidiMessage::NoteOn { .. } => "text-green-600",
+            MidiMessage::NoteOff { .. } => "text-red-600",
+            MidiMessage::ControlChange { .. } => "text-blue-600",
+            MidiMessage::ProgramChange { .. } => "text-purple-600",
+            MidiMessage::PitchBend { .. } => "text-orange-600",
+            MidiMessage::PolyPressure { .. }
+            | MidiMessage::ChannelPressure { .. } => "text-yellow-600",
+            MidiMessage::Start
+            | MidiMessage::Continue
+            | MidiMessage::Stop
+            | MidiMessage::SongPositionPointer { .. } => "text-indigo-600",
+            MidiMessage::SysEx(_) => "text-teal-600",
+            _ => "text-gray-600",
+        },
+        ServerEvent::Parameter(_) => "text-pink-600",
+        ServerEvent::Transport(_) | ServerEvent::Timecode(_) => "text-indigo-600",
+        ServerEvent::MpeLayout(_) | ServerEvent::NoteExpression(_) => "text-orange-600",
+        // Highlighted so a flaky device's garbage stands out
+        ServerEvent::Malformed(_) => "text-red-800 bg-red-100 font-semibold",
+        ServerEvent::Inputs { .. } | ServerEvent::DeviceConnected(_) => "text-cyan-700",
+        ServerEvent::DeviceDisconnected(_) => "text-gray-500",
+        ServerEvent::Error { .. } => "text-red-800 bg-red-100 font-semibold",
+        ServerEvent::Reply(_) => "text-gray-500",
+    }
+}
+
+/// Gap between two events, e.g. `+0.512 ms`.
+fn format_delta(delta_us: u64) -> String {
+    if delta_us >= 1_000_000 {
+        format!("+{:.3} s", delta_us as f64 / 1_000_000.0)
+    } else {
+        format!("+{:.3} ms", delta_us as f64 / 1000.0)
+    }
+}
+
+/// Log text for events the server sent without a description.
+fn format_event(event: &ServerEvent) -> String {
+    match event {
+        ServerEvent::Midi(message) => Describer::new().peek(message),
+        ServerEvent::Parameter(parameter) => format!("Ch{} {}", parameter.channel + 1, parameter),
+        ServerEvent::Transport(_) => "Transport".to_string(),
+        ServerEvent::Timecode(state) => format!("Timecode {}", state.timecode),
+        ServerEvent::MpeLayout(layout) => layout.to_string(),
+        ServerEvent::NoteExpression(expression) => format!(
+            "Ch{} MPE {}: bend {:+.2} slide {} pressure {}",
+            expression.channel + 1,
+            note_name(expression.note),
+            expression.bend,
+            expression.slide,
+            expression.pressure,
+        ),
+        ServerEvent::Malformed(malformed) => malformed.to_string(),
+        ServerEvent::Inputs { ports } => format!("Inputs: {}", port_names(ports)),
+        ServerEvent::DeviceConnected(port) => format!("Connected: {}", port.name),
+        ServerEvent::DeviceDisconnected(port) => format!("Disconnected: {}", port.name),
+        ServerEvent::Error { message } => format!("Error: {}", message),
+        ServerEvent::Reply(_) => "Reply".to_string(),
+    }
+}
+
+fn port_names(ports: &[PortInfo]) -> String {
+    ports.iter().map(|port| port.name.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+/// Classic 16-bytes-per-line hex dump with an ASCII column.
+fn hex_dump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(line, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02X}", b)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                .collect();
+            format!("{:04X}  {:<47}  {}", line * 16, hex.join(" "), ascii)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Song position as bar:beat:sixteenth, assuming 4/4.
+fn format_song_position(beats: u16) -> String {
+    format!("{}:{}:{}", beats / 16 + 1, (beats / 4) % 4 + 1, beats % 4 + 1)
+}
+
+#[component]
+fn TransportDisplay(transport: ReadSignal<TransportState>) -> impl IntoView {
+    view! {
+        <div class="grid grid-cols-3 gap-4 text-center">
+            <div class="bg-indigo-50 p-4 rounded">
+                <div class="text-2xl font-bold text-indigo-600 font-mono">
+                    {move || transport.get().bpm
+                        .map(|bpm| format!("{:.1}", bpm))
+                        .unwrap_or_else(|| "--".to_string())}
+                </div>
+                <div class="text-sm text-gray-600">"BPM"</div>
+            </div>
+            <div class="bg-indigo-50 p-4 rounded">
+                <div class="text-2xl font-bold text-indigo-600 font-mono">
+                    {move || format_song_position(transport.get().song_position)}
+                </div>
+                <div class="text-sm text-gray-600">
+                    {move || match transport.get().status {
+                        TransportStatus::Playing => "Playing",
+                        TransportStatus::Stopped => "Stopped",
+                    }}
+                </div>
+            </div>
+            <div class="bg-indigo-50 p-4 rounded">
+                <div class={move || format!(
+                    "text-2xl font-bold font-mono {}",
+                    if transport.get().clock { "text-green-600" } else { "text-gray-400" }
+                )}>
+                    {move || transport.get().jitter_ms
+                        .map(|jitter| format!("±{:.2}ms", jitter))
+                        .unwrap_or_else(|| "--".to_string())}
+                </div>
+                <div class="text-sm text-gray-600">
+                    {move || if transport.get().clock { "Clock Jitter" } else { "No Clock" }}
+                </div>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn TimecodeDisplay(timecode: ReadSignal<TimecodeState>) -> impl IntoView {
+    view! {
+        <div class="text-center">
+            <div class={move || format!(
+                "text-5xl font-bold font-mono tracking-wider {}",
+                if timecode.get().running { "text-gray-900" } else { "text-gray-400" }
+            )}>
+                {move || timecode.get().timecode.to_string()}
+            </div>
+            <div class="flex justify-center space-x-4 mt-2 text-sm text-gray-600">
+                <span>{move || format!("{} fps", timecode.get().timecode.rate)}</span>
+                <span>
+                    {move || {
+                        let state = timecode.get();
+                        match (state.running, state.direction) {
+                            (false, _) => "Stopped",
+                            (true, TimecodeDirection::Forward) => "▶ Forward",
+                            (true, TimecodeDirection::Reverse) => "◀ Reverse",
+                        }
+                    }}
+                </span>
+                <span class={move || if timecode.get().dropouts > 0 { "text-red-600" } else { "" }}>
+                    {move || format!("{} dropouts", timecode.get().dropouts)}
+                </span>
             </div>
         </div>
     }
 }
 
-fn format_midi_message(msg: &MidiMessage) -> String {
-    match msg.message_type.as_str() {
-        "NoteOn" => format!("Note On: {} (vel: {})", 
-            msg.note.unwrap_or(0), msg.velocity.unwrap_or(0)),
-        "NoteOff" => format!("Note Off: {} (vel: {})", 
-            msg.note.unwrap_or(0), msg.velocity.unwrap_or(0)),
-        "ControlChange" => format!("CC: {} = {}", 
-            msg.control.unwrap_or(0), msg.value.unwrap_or(0)),
-        _ => format!("{:?}", msg.message_type),
+/// The backend's address when the page can't tell: the desktop app's.
+const DEFAULT_BACKEND: &str = "localhost:3000";
+
+/// Where the backend is, as whether it uses TLS and its `host:port`: the
+/// page URL's `?backend=host:port` if given, else the server the page came
+/// from (Trunk's dev server passes `/api` and `/ws` on), else
+/// [`DEFAULT_BACKEND`] for the desktop app's bundled pages.
+fn backend_address(protocol: &str, host: &str, search: &str) -> (bool, String) {
+    let secure = protocol == "https:";
+    let given = search
+        .trim_start_matches('?')
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("backend="))
+        .filter(|backend| !backend.is_empty());
+    if let Some(backend) = given {
+        return (secure, backend.to_string());
+    }
+    // Tauri serves its bundle from tauri://localhost, or http://tauri.localhost on Windows
+    let served = (protocol == "http:" || secure) && host != "tauri.localhost";
+    if served {
+        (secure, host.to_string())
+    } else {
+        (false, DEFAULT_BACKEND.to_string())
+    }
+}
+
+/// URL of a backend path over HTTP, or over a WebSocket.
+fn backend_url(path: &str, websocket: bool) -> String {
+    let location = web_sys::window().map(|window| window.location());
+    let field = |read: fn(&web_sys::Location) -> Result<String, JsValue>| {
+        location.as_ref().and_then(|location| read(location).ok()).unwrap_or_default()
+    };
+    let (secure, host) = backend_address(
+        &field(web_sys::Location::protocol),
+        &field(web_sys::Location::host),
+        &field(web_sys::Location::search),
+    );
+    let scheme = match (websocket, secure) {
+        (false, false) => "http",
+        (false, true) => "https",
+        (true, false) => "ws",
+        (true, true) => "wss",
+    };
+    format!("{}://{}{}", scheme, host, path)
+}
+
+/// Output ports from the backend's `/api/ports`.
+async fn fetch_outputs() -> Result<Vec<PortInfo>, JsValue> {
+    let window = web_sys::window().ok_or("no window")?;
+    let response: web_sys::Response = JsFuture::from(window.fetch_with_str(&backend_url("/api/ports", false)))
+        .await?
+        .dyn_into()?;
+    let text = JsFuture::from(response.text()?).await?.as_string().unwrap_or_default();
+    let ports: Vec<PortInfo> = serde_json::from_str(&text).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(ports.into_iter().filter(|port| port.direction == PortDirection::Output).collect())
+}
+
+/// Message types the send panel offers, with the labels of their data fields.
+const SEND_KINDS: [(&str, &str, &str); 5] = [
+    ("note_on", "Note", "Velocity"),
+    ("note_off", "Note", "Velocity"),
+    ("control_change", "Controller", "Value"),
+    ("program_change", "Program", ""),
+    ("pitch_bend", "", "Bend"),
+];
+
+/// Builds the message the send panel describes. `channel` and `program` are
+/// 1-based, as shown everywhere else in the UI, so program 0 is no program.
+fn build_message(kind: &str, channel: u8, data1: u8, data2: i16) -> Option<MidiMessage> {
+    let channel = channel.checked_sub(1).filter(|&channel| channel < 16)?;
+    let data1_7bit = data1.min(127);
+    let data2_7bit = data2.clamp(0, 127) as u8;
+    let message = match kind {
+        "note_on" => MidiMessage::NoteOn { channel, note: data1_7bit, velocity: data2_7bit },
+        "note_off" => MidiMessage::NoteOff { channel, note: data1_7bit, velocity: data2_7bit },
+        "control_change" => MidiMessage::ControlChange { channel, controller: data1_7bit, value: data2_7bit },
+        "program_change" => MidiMessage::ProgramChange { channel, program: data1.checked_sub(1)?.min(127) },
+        "pitch_bend" => MidiMessage::PitchBend { channel, value: data2.clamp(-8192, 8191) },
+        _ => return None,
+    };
+    Some(message)
+}
+
+#[component]
+fn SendPanel(
+    outputs: ReadSignal<Vec<PortInfo>>,
+    websocket: ReadSignal<Option<WebSocket>>,
+) -> impl IntoView {
+    let (output, set_output) = create_signal(String::new());
+    let (kind, set_kind) = create_signal("note_on".to_string());
+    let (channel, set_channel) = create_signal(1u8);
+    let (data1, set_data1) = create_signal(60u8);
+    let (data2, set_data2) = create_signal(100i16);
+
+    let labels = move || {
+        let kind = kind.get();
+        SEND_KINDS.iter().find(|(name, _, _)| *name == kind).map(|&(_, data1, data2)| (data1, data2)).unwrap_or(("", ""))
+    };
+    let send = move |_| {
+        // Until one is picked, the first output listed
+        let output = Some(output.get_untracked())
+            .filter(|output| !output.is_empty())
+            .or_else(|| outputs.get_untracked().first().map(|port| port.id.clone()));
+        let (Some(output), Some(message)) = (
+            output,
+            build_message(&kind.get_untracked(), channel.get_untracked(), data1.get_untracked(), data2.get_untracked()),
+        ) else {
+            return;
+        };
+        if let Ok(json) = serde_json::to_string(&ClientMessage::Send { output, message }) {
+            websocket.with_untracked(|ws| {
+                if let Some(ws) = ws {
+                    let _ = ws.send_with_str(&json);
+                }
+            });
+        }
+    };
+
+    view! {
+        <div class="space-y-3 text-sm">
+            <select class="w-full border rounded px-2 py-1"
+                on:change=move |ev| set_output.set(event_target_value(&ev))
+            >
+                {move || {
+                    let outputs = outputs.get();
+                    if outputs.is_empty() {
+                        view! { <option value="">"No MIDI outputs"</option> }.into_view()
+                    } else {
+                        outputs.into_iter().map(|port| view! {
+                            <option value={port.id.clone()} selected={output.get() == port.id}>{port.name}</option>
+                        }).collect::<Vec<_>>().into_view()
+                    }
+                }}
+            </select>
+            <div class="grid grid-cols-4 gap-2 items-end">
+                <label class="flex flex-col">
+                    <span class="text-gray-600">"Type"</span>
+                    <select class="border rounded px-2 py-1"
+                        on:change=move |ev| set_kind.set(event_target_value(&ev))
+                    >
+                        <option value="note_on">"Note On"</option>
+                        <option value="note_off">"Note Off"</option>
+                        <option value="control_change">"Control Change"</option>
+                        <option value="program_change">"Program Change"</option>
+                        <option value="pitch_bend">"Pitch Bend"</option>
+                    </select>
+                </label>
+                <label class="flex flex-col">
+                    <span class="text-gray-600">"Channel"</span>
+                    <input type="number" min="1" max="16" class="border rounded px-2 py-1"
+                        prop:value=move || channel.get()
+                        on:input=move |ev| set_channel.set(event_target_value(&ev).parse().unwrap_or(1))
+                    />
+                </label>
+                <label class="flex flex-col" class:invisible=move || labels().0.is_empty()>
+                    <span class="text-gray-600">{move || labels().0}</span>
+                    <input type="number" class="border rounded px-2 py-1"
+                        min=move || if kind.get() == "program_change" { 1 } else { 0 }
+                        max=move || if kind.get() == "program_change" { 128 } else { 127 }
+                        prop:value=move || data1.get()
+                        on:input=move |ev| set_data1.set(event_target_value(&ev).parse().unwrap_or(0))
+                    />
+                </label>
+                <label class="flex flex-col" class:invisible=move || labels().1.is_empty()>
+                    <span class="text-gray-600">{move || labels().1}</span>
+                    <input type="number" min="-8192" max="8191" class="border rounded px-2 py-1"
+                        prop:value=move || data2.get()
+                        on:input=move |ev| set_data2.set(event_target_value(&ev).parse().unwrap_or(0))
+                    />
+                </label>
+            </div>
+            <button class="w-full bg-blue-600 hover:bg-blue-700 text-white font-medium rounded px-3 py-2 disabled:opacity-50"
+                disabled=move || outputs.get().is_empty()
+                on:click=send
+            >
+                "Send"
+            </button>
+        </div>
     }
 }
 
 #[component]
-fn ConnectionStatus(connected: ReadSignal<bool>) -> impl IntoView {
+fn ConnectionStatus(connected: ReadSignal<bool>, inputs: ReadSignal<Vec<PortInfo>>) -> impl IntoView {
     view! {
         <div class={move || format!(
             "flex items-center space-x-2 px-3 py-2 rounded-lg {}",
@@ -185,6 +642,12 @@ fn ConnectionStatus(connected: ReadSignal<bool>) -> impl IntoView {
             <span class="font-medium">
                 {move || if connected.get() { "Connected" } else { "Disconnected" }}
             </span>
+            <span class="text-sm">
+                {move || {
+                    let inputs = inputs.get();
+                    if inputs.is_empty() { "Simulation".to_string() } else { port_names(&inputs) }
+                }}
+            </span>
         </div>
     }
 }
@@ -196,10 +659,27 @@ fn App() -> impl IntoView {
     let (events, set_events) = create_signal(Vec::<MidiEvent>::new());
     let (active_notes, set_active_notes) = create_signal(HashMap::<u8, bool>::new());
     let (connected, set_connected) = create_signal(false);
-    let (_websocket, set_websocket) = create_signal(None::<WebSocket>);
+    let (transport, set_transport) = create_signal(TransportState::default());
+    let (timecode, set_timecode) = create_signal(TimecodeState::default());
+    let (mpe_layout, set_mpe_layout) = create_signal(MpeLayout::default());
+    let (expressions, set_expressions) = create_signal(HashMap::<u8, NoteExpression>::new());
+    let (inputs, set_inputs) = create_signal(Vec::<PortInfo>::new());
+    // Every source seen so far, in order, for the log's colours and filter
+    let (sources, set_sources) = create_signal(Vec::<EventSource>::new());
+    let (outputs, set_outputs) = create_signal(Vec::<PortInfo>::new());
+    let (websocket, set_websocket) = create_signal(None::<WebSocket>);
+
+    let refresh_outputs = move || {
+        wasm_bindgen_futures::spawn_local(async move {
+            match fetch_outputs().await {
+                Ok(ports) => set_outputs.set(ports),
+                Err(e) => web_sys::console::log_2(&"Failed to list MIDI outputs:".into(), &e),
+            }
+        });
+    };
 
     let connect_websocket = move || {
-        let ws = WebSocket::new("ws://localhost:3000/ws");
+        let ws = WebSocket::new(&backend_url("/ws", true));
         
         match ws {
             Ok(ws) => {
@@ -217,32 +697,84 @@ fn App() -> impl IntoView {
                 let onmessage_callback = Closure::wrap(Box::new(move |e: MessageEvent| {
                     if let Ok(text) = e.data().dyn_into::<js_sys::JsString>() {
                         let message_str = String::from(text);
-                        if let Ok(midi_message) = serde_json::from_str::<MidiMessage>(&message_str) {
-                            let event = MidiEvent::new(midi_message.clone());
-                            
-                            set_events.update(|events| {
-                                events.push(event);
-                                if events.len() > 100 {
-                                    events.remove(0);
+                        if let Ok(wire) = serde_json::from_str::<WireMessage>(&message_str) {
+                            if wire.version != PROTOCOL_VERSION {
+                                web_sys::console::error_1(&format!(
+                                    "Unsupported protocol version {} (expected {})",
+                                    wire.version, PROTOCOL_VERSION
+                                ).into());
+                                return;
+                            }
+                            match &wire.event {
+                                ServerEvent::Transport(state) => {
+                                    set_transport.set(state.clone());
+                                    return;
                                 }
-                            });
+                                ServerEvent::Timecode(state) => {
+                                    set_timecode.set(state.clone());
+                                    return;
+                                }
+                                ServerEvent::MpeLayout(layout) => {
+                                    set_mpe_layout.set(layout.clone());
+                                    set_expressions.update(|expressions| expressions.clear());
+                                }
+                                ServerEvent::Inputs { ports } => {
+                                    // Only log real changes, not the snapshot sent on connect
+                                    if inputs.get_untracked() == *ports {
+                                        return;
+                                    }
+                                    set_inputs.set(ports.clone());
+                                }
+                                ServerEvent::DeviceConnected(port) | ServerEvent::DeviceDisconnected(port)
+                                    if port.direction == PortDirection::Output =>
+                                {
+                                    refresh_outputs();
+                                }
+                                // Expression streams continuously; the piano draws it
+                                ServerEvent::NoteExpression(expression) => {
+                                    set_expressions.update(|expressions| {
+                                        if expression.active {
+                                            expressions.insert(expression.note, expression.clone());
+                                        } else {
+                                            expressions.remove(&expression.note);
+                                        }
+                                    });
+                                    return;
+                                }
+                                // Acknowledges our own sends; failures come as errors
+                                ServerEvent::Reply(_) => return,
+                                // Clock, active sensing and MTC arrive many times a second;
+                                // the transport and timecode readouts summarise them instead
+                                ServerEvent::Midi(
+                                    MidiMessage::TimingClock
+                                    | MidiMessage::ActiveSensing
+                                    | MidiMessage::QuarterFrame { .. }
+                                ) => return,
+                                // Update active notes for piano display
+                                ServerEvent::Midi(MidiMessage::NoteOn { note, .. }) => {
+                                    set_active_notes.update(|notes| {
+                                        notes.insert(*note, true);
+                                    });
+                                },
+                                ServerEvent::Midi(MidiMessage::NoteOff { note, .. }) => {
+                                    set_active_notes.update(|notes| {
+                                        notes.insert(*note, false);
+                                    });
+                                },
+                                _ => {}
+                            }
 
-                            // Update active notes for piano display
-                            if let Some(note) = midi_message.note {
-                                match midi_message.message_type.as_str() {
-                                    "NoteOn" => {
-                                        set_active_notes.update(|notes| {
-                                            notes.insert(note, true);
-                                        });
-                                    },
-                                    "NoteOff" => {
-                                        set_active_notes.update(|notes| {
-                                            notes.insert(note, false);
-                                        });
-                                    },
-                                    _ => {}
+                            if let Some(source) = &wire.source {
+                                if !sources.get_untracked().iter().any(|known| known.id == source.id) {
+                                    set_sources.update(|sources| sources.push(source.clone()));
                                 }
                             }
+                            set_events.update(|events| {
+                                events.push(MidiEvent::new(wire));
+                                if events.len() > MAX_LOG_EVENTS {
+                                    events.remove(0);
+                                }
+                            });
                         }
                     }
                 }) as Box<dyn FnMut(MessageEvent)>);
@@ -276,6 +808,7 @@ fn App() -> impl IntoView {
     // Auto-connect on component mount
     create_effect(move |_| {
         connect_websocket();
+        refresh_outputs();
     });
 
     view! {
@@ -289,7 +822,7 @@ fn App() -> impl IntoView {
                 <header class="mb-8">
                     <div class="flex justify-between items-center">
                         <h1 class="text-3xl font-bold text-gray-800">"MIDI Monitor"</h1>
-                        <ConnectionStatus connected/>
+                        <ConnectionStatus connected inputs/>
                     </div>
                     <p class="text-gray-600 mt-2">
                         "Real-time MIDI event monitoring with virtual piano display"
@@ -304,8 +837,11 @@ fn App() -> impl IntoView {
                                 "Keys light up green when MIDI Note On events are received"
                             </p>
                             <div class="flex justify-center">
-                                <Piano active_notes/>
+                                <Piano active_notes expressions/>
                             </div>
+                            <p class="text-xs text-gray-500 mt-2 text-center">
+                                {move || mpe_layout.get().to_string()}
+                            </p>
                         </div>
 
                         <div class="bg-white border rounded-lg p-6 shadow-sm">
@@ -325,11 +861,26 @@ fn App() -> impl IntoView {
                                 </div>
                             </div>
                         </div>
+
+                        <div class="bg-white border rounded-lg p-6 shadow-sm">
+                            <h2 class="text-xl font-semibold mb-4">"Tempo & Transport"</h2>
+                            <TransportDisplay transport/>
+                        </div>
+
+                        <div class="bg-white border rounded-lg p-6 shadow-sm">
+                            <h2 class="text-xl font-semibold mb-4">"MIDI Time Code"</h2>
+                            <TimecodeDisplay timecode/>
+                        </div>
                     </div>
 
                     <div class="space-y-6">
                         <div class="bg-white border rounded-lg p-6 shadow-sm">
-                            <MidiEventLog events/>
+                            <MidiEventLog events sources/>
+                        </div>
+
+                        <div class="bg-white border rounded-lg p-6 shadow-sm">
+                            <h2 class="text-xl font-semibold mb-4">"Send MIDI"</h2>
+                            <SendPanel outputs websocket/>
                         </div>
 
                         <div class="bg-white border rounded-lg p-6 shadow-sm">
@@ -338,7 +889,10 @@ fn App() -> impl IntoView {
                                 <li>"• Connect a MIDI device to see real events"</li>
                                 <li>"• Without a device, simulated events will play"</li>
                                 <li>"• Green keys indicate active notes"</li>
-                                <li>"• Event log shows the latest 50 events"</li>
+                                <li>"• MPE notes show pressure, slide (color) and pitch bend on their key"</li>
+                                <li>{format!("• Event log shows the latest {} events", MAX_LOG_EVENTS)}</li>
+                                <li>"• Each input gets its own border colour; filter the log by source"</li>
+                                <li>"• Send MIDI plays notes or changes programs on a connected synth"</li>
                             </ul>
                         </div>
                     </div>
@@ -352,3 +906,26 @@ fn main() {
     console_error_panic_hook::set_once();
     leptos::mount_to_body(App);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_address() {
+        assert_eq!(backend_address("http:", "studio.local:3100", ""), (false, "studio.local:3100".to_string()));
+        assert_eq!(backend_address("https:", "monitor.example.com", ""), (true, "monitor.example.com".to_string()));
+        assert_eq!(backend_address("http:", "localhost:3001", "?theme=dark&backend=localhost:3200"), (false, "localhost:3200".to_string()));
+        assert_eq!(backend_address("tauri:", "localhost", ""), (false, DEFAULT_BACKEND.to_string()));
+        assert_eq!(backend_address("http:", "tauri.localhost", ""), (false, DEFAULT_BACKEND.to_string()));
+    }
+
+    #[test]
+    fn test_program_change_is_one_based() {
+        assert_eq!(build_message("program_change", 1, 1, 0), Some(MidiMessage::ProgramChange { channel: 0, program: 0 }));
+        assert_eq!(build_message("program_change", 1, 128, 0), Some(MidiMessage::ProgramChange { channel: 0, program: 127 }));
+        assert_eq!(build_message("program_change", 16, 200, 0), Some(MidiMessage::ProgramChange { channel: 15, program: 127 }));
+        assert_eq!(build_message("program_change", 1, 0, 0), None);
+        assert_eq!(build_message("program_change", 17, 1, 0), None);
+    }
+}
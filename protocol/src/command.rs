@@ -0,0 +1,114 @@
+//! Commands clients send the backend on `/ws`, and its replies.
+
+use crate::{MidiMessage, PortInfo, WireMessage};
+use serde::{Deserialize, Serialize};
+
+/// A command with the `id` its reply or error will carry in `reply_to`.
+///
+/// `{"id":7,"kind":"subscribe","filter":{"kinds":["midi"]}}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClientRequest {
+    /// Chosen by the client; a command without one is still answered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(flatten)]
+    pub command: ClientMessage,
+}
+
+impl ClientRequest {
+    pub fn new(id: u64, command: ClientMessage) -> Self {
+        Self {
+            id: Some(id),
+            command,
+        }
+    }
+}
+
+/// What clients can send the server on `/ws`, tagged by `kind` like
+/// [`ServerEvent`](crate::ServerEvent).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Sends a MIDI message out of the output port with this `/api/ports` id.
+    Send {
+        output: String,
+        message: MidiMessage,
+    },
+    /// Limits the events this client gets to those matching any of its
+    /// subscriptions. Without any, it gets everything.
+    Subscribe { filter: EventFilter },
+    /// Drops a subscription by the number it was given.
+    Unsubscribe { subscription: u32 },
+    /// Switches to the input ports a selector matches, like `PUT /api/input`.
+    SelectInput { selector: String },
+    /// Sends All Sound Off and All Notes Off on every channel of an output
+    /// port, or of every open output without one.
+    Panic {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        output: Option<String>,
+    },
+    /// Starts keeping the MIDI events this client gets.
+    StartRecording,
+    /// Stops recording and replies with what was recorded.
+    StopRecording,
+    /// Turns the simulation used while no input is open on or off.
+    Simulation { enabled: bool },
+}
+
+/// Which events a subscription passes; an empty list passes everything.
+///
+/// `{"kinds":["midi"],"channels":[0],"types":["ControlChange"],"controllers":[1,74]}`
+///
+/// Each criterion only applies to events it can describe: `channels` to
+/// channel messages, parameters and note expression, `notes` to messages
+/// about a note, `controllers` to Control Changes and `sources` to events
+/// from an input. A filter on channel 1 still passes clock unless `kinds`
+/// or `types` leave it out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventFilter {
+    /// Event `kind`s to pass, e.g. `midi` or `transport`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub kinds: Vec<String>,
+    /// Zero-based channels to pass.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub channels: Vec<u8>,
+    /// MIDI message `type`s to pass, e.g. `ControlChange`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub types: Vec<String>,
+    /// Lowest and highest note to pass.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<[u8; 2]>,
+    /// Control Change controller numbers to pass.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub controllers: Vec<u8>,
+    /// Source ids to pass, as in events' `source`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+}
+
+/// The outcome of a command, tagged by `result`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum CommandReply {
+    Sent,
+    Subscribed {
+        subscription: u32,
+    },
+    Unsubscribed,
+    /// The input ports now open.
+    InputSelected {
+        ports: Vec<PortInfo>,
+    },
+    /// The output port ids the panic went out of.
+    Panicked {
+        outputs: Vec<String>,
+    },
+    RecordingStarted,
+    RecordingStopped {
+        events: Vec<WireMessage>,
+    },
+    Simulation {
+        enabled: bool,
+    },
+}
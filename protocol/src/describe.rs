@@ -0,0 +1,289 @@
+//! Human-readable descriptions of MIDI messages using the General MIDI name
+//! tables, e.g. `Ch10 Kick Drum 1 vel 100` or `Ch1 Program 41 Violin`.
+
+use crate::gm::{
+    controller_name, drum_kit_name, drum_name, is_switch_controller, program_name, DRUM_CHANNEL,
+    GM2_MELODY_BANK, GM2_RHYTHM_BANK,
+};
+use crate::message::MidiMessage;
+use crate::note::note_name;
+
+#[derive(Debug, Clone, Copy)]
+struct ChannelState {
+    bank_msb: u8,
+    bank_lsb: u8,
+    rhythm: bool,
+}
+
+/// Describes messages in the context of what came before them.
+///
+/// Follows Bank Select per channel so Program Changes are named against the
+/// right bank, and which channels are rhythm channels: channel 10 by
+/// default, or any channel switched by a GM2 rhythm bank select.
+#[derive(Debug, Clone)]
+pub struct Describer {
+    channels: [ChannelState; 16],
+}
+
+impl Default for Describer {
+    fn default() -> Self {
+        let mut channels = [ChannelState {
+            bank_msb: 0,
+            bank_lsb: 0,
+            rhythm: false,
+        }; 16];
+        channels[DRUM_CHANNEL as usize].rhythm = true;
+        Self { channels }
+    }
+}
+
+impl Describer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Describes a message, updating bank and rhythm channel state from it.
+    pub fn describe(&mut self, message: &MidiMessage) -> String {
+        match *message {
+            MidiMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            } => {
+                let state = &mut self.channels[channel as usize & 0x0F];
+                match controller {
+                    0 => state.bank_msb = value,
+                    32 => state.bank_lsb = value,
+                    _ => {}
+                }
+            }
+            MidiMessage::ProgramChange { channel, .. } => {
+                // GM2: the bank takes effect, rhythm or melodic, on the Program Change
+                let state = &mut self.channels[channel as usize & 0x0F];
+                state.rhythm = match state.bank_msb {
+                    GM2_RHYTHM_BANK => true,
+                    GM2_MELODY_BANK => false,
+                    _ => channel == DRUM_CHANNEL,
+                };
+            }
+            MidiMessage::SystemReset => *self = Self::default(),
+            _ => {}
+        }
+        self.peek(message)
+    }
+
+    /// Describes a message without updating any state.
+    pub fn peek(&self, message: &MidiMessage) -> String {
+        match *message {
+            MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => match self.drum(channel, note) {
+                Some(drum) => format!("Ch{} {} vel {}", channel + 1, drum, velocity),
+                None => format!(
+                    "Ch{} Note On {} vel {}",
+                    channel + 1,
+                    note_name(note),
+                    velocity
+                ),
+            },
+            MidiMessage::NoteOff {
+                channel,
+                note,
+                velocity,
+            } => match self.drum(channel, note) {
+                Some(drum) => format!("Ch{} {} off", channel + 1, drum),
+                None => format!(
+                    "Ch{} Note Off {} vel {}",
+                    channel + 1,
+                    note_name(note),
+                    velocity
+                ),
+            },
+            MidiMessage::PolyPressure {
+                channel,
+                note,
+                pressure,
+            } => {
+                let note = self
+                    .drum(channel, note)
+                    .map_or_else(|| note_name(note), str::to_string);
+                format!("Ch{} Poly Pressure {} = {}", channel + 1, note, pressure)
+            }
+            MidiMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            } => match controller_name(controller) {
+                Some(name) if is_switch_controller(controller) => format!(
+                    "Ch{} {} {}",
+                    channel + 1,
+                    name,
+                    if value >= 64 { "On" } else { "Off" }
+                ),
+                Some(name) => format!("Ch{} {} = {}", channel + 1, name, value),
+                None => format!("Ch{} CC {} = {}", channel + 1, controller, value),
+            },
+            MidiMessage::ProgramChange { channel, program } => {
+                format!("Ch{} {}", channel + 1, self.program(channel, program))
+            }
+            MidiMessage::ChannelPressure { channel, pressure } => {
+                format!("Ch{} Channel Pressure = {}", channel + 1, pressure)
+            }
+            MidiMessage::PitchBend { channel, value } => {
+                format!("Ch{} Pitch Bend {:+}", channel + 1, value)
+            }
+            MidiMessage::SysEx(ref sysex) => match &sysex.universal {
+                Some(universal) => format!(
+                    "SysEx {}: {}",
+                    if universal.realtime {
+                        "Universal RT"
+                    } else {
+                        "Universal Non-RT"
+                    },
+                    universal.description.as_deref().unwrap_or("Unknown"),
+                ),
+                None => format!(
+                    "SysEx {} ({} bytes)",
+                    sysex
+                        .manufacturer
+                        .as_deref()
+                        .unwrap_or("Unknown manufacturer"),
+                    sysex.payload_len,
+                ),
+            },
+            MidiMessage::QuarterFrame { piece, value } => {
+                format!("MTC QF {}: {:X}", piece, value)
+            }
+            MidiMessage::SongPositionPointer { beats } => format!("Song Position: {}", beats),
+            MidiMessage::SongSelect { song } => format!("Song Select: {}", song),
+            MidiMessage::TuneRequest => "Tune Request".to_string(),
+            MidiMessage::TimingClock => "Clock".to_string(),
+            MidiMessage::Start => "Start".to_string(),
+            MidiMessage::Continue => "Continue".to_string(),
+            MidiMessage::Stop => "Stop".to_string(),
+            MidiMessage::ActiveSensing => "Active Sensing".to_string(),
+            MidiMessage::SystemReset => "System Reset".to_string(),
+            MidiMessage::Unknown { status, ref data } => {
+                format!("Unknown: {:02X} {:02X?}", status, data)
+            }
+        }
+    }
+
+    fn drum(&self, channel: u8, note: u8) -> Option<&'static str> {
+        if self.channels[channel as usize & 0x0F].rhythm {
+            drum_name(note)
+        } else {
+            None
+        }
+    }
+
+    /// Program numbers are shown 1-based, as GM lists them.
+    fn program(&self, channel: u8, program: u8) -> String {
+        let state = &self.channels[channel as usize & 0x0F];
+        if state.rhythm {
+            return match drum_kit_name(program) {
+                Some(kit) => kit.to_string(),
+                None => format!("Drum Kit {}", program + 1),
+            };
+        }
+        match (state.bank_msb, state.bank_lsb) {
+            (0, 0) | (GM2_MELODY_BANK, 0) => {
+                format!("Program {} {}", program + 1, program_name(program))
+            }
+            (GM2_MELODY_BANK, variation) => format!(
+                "Program {} {} (variation {})",
+                program + 1,
+                program_name(program),
+                variation
+            ),
+            // Outside GM the bank's sounds are the instrument's own
+            (msb, lsb) => format!("Program {} (bank {}/{})", program + 1, msb, lsb),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cc(channel: u8, controller: u8, value: u8) -> MidiMessage {
+        MidiMessage::ControlChange {
+            channel,
+            controller,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_notes_and_drums() {
+        let mut describer = Describer::new();
+        let kick = MidiMessage::NoteOn {
+            channel: 9,
+            note: 36,
+            velocity: 100,
+        };
+        assert_eq!(describer.describe(&kick), "Ch10 Kick Drum 1 vel 100");
+        assert_eq!(
+            describer.describe(&MidiMessage::NoteOn {
+                channel: 0,
+                note: 60,
+                velocity: 64
+            }),
+            "Ch1 Note On C4 vel 64"
+        );
+        assert_eq!(
+            describer.describe(&MidiMessage::NoteOff {
+                channel: 9,
+                note: 42,
+                velocity: 0
+            }),
+            "Ch10 Closed Hi-Hat off"
+        );
+    }
+
+    #[test]
+    fn test_controllers() {
+        let mut describer = Describer::new();
+        assert_eq!(describer.describe(&cc(0, 7, 100)), "Ch1 Volume = 100");
+        assert_eq!(describer.describe(&cc(0, 64, 127)), "Ch1 Sustain On");
+        assert_eq!(describer.describe(&cc(0, 64, 0)), "Ch1 Sustain Off");
+        assert_eq!(describer.describe(&cc(0, 3, 5)), "Ch1 CC 3 = 5");
+    }
+
+    #[test]
+    fn test_programs_with_bank_select() {
+        let mut describer = Describer::new();
+        let program = |channel, program| MidiMessage::ProgramChange { channel, program };
+
+        assert_eq!(describer.describe(&program(0, 40)), "Ch1 Program 41 Violin");
+
+        describer.describe(&cc(0, 0, GM2_MELODY_BANK));
+        describer.describe(&cc(0, 32, 1));
+        assert_eq!(
+            describer.describe(&program(0, 0)),
+            "Ch1 Program 1 Acoustic Grand Piano (variation 1)"
+        );
+
+        describer.describe(&cc(1, 0, 3));
+        assert_eq!(
+            describer.describe(&program(1, 0)),
+            "Ch2 Program 1 (bank 3/0)"
+        );
+
+        assert_eq!(describer.describe(&program(9, 32)), "Ch10 Jazz Kit");
+
+        // GM2 rhythm bank turns another channel into a drum channel
+        describer.describe(&cc(2, 0, GM2_RHYTHM_BANK));
+        describer.describe(&program(2, 0));
+        assert_eq!(
+            describer.describe(&MidiMessage::NoteOn {
+                channel: 2,
+                note: 38,
+                velocity: 90
+            }),
+            "Ch3 Acoustic Snare vel 90"
+        );
+    }
+}
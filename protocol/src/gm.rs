@@ -0,0 +1,344 @@
+//! General MIDI (GM and GM2) name tables: controllers, programs, drum kits
+//! and percussion notes.
+
+/// Zero-based channel GM reserves for percussion (channel 10).
+pub const DRUM_CHANNEL: u8 = 9;
+/// GM2 Bank Select MSB for melodic sounds.
+pub const GM2_MELODY_BANK: u8 = 0x79;
+/// GM2 Bank Select MSB that turns a channel into a rhythm channel.
+pub const GM2_RHYTHM_BANK: u8 = 0x78;
+
+const PROGRAM_NAMES: [&str; 128] = [
+    // Piano
+    "Acoustic Grand Piano",
+    "Bright Acoustic Piano",
+    "Electric Grand Piano",
+    "Honky-tonk Piano",
+    "Electric Piano 1",
+    "Electric Piano 2",
+    "Harpsichord",
+    "Clavi",
+    // Chromatic Percussion
+    "Celesta",
+    "Glockenspiel",
+    "Music Box",
+    "Vibraphone",
+    "Marimba",
+    "Xylophone",
+    "Tubular Bells",
+    "Dulcimer",
+    // Organ
+    "Drawbar Organ",
+    "Percussive Organ",
+    "Rock Organ",
+    "Church Organ",
+    "Reed Organ",
+    "Accordion",
+    "Harmonica",
+    "Tango Accordion",
+    // Guitar
+    "Acoustic Guitar (nylon)",
+    "Acoustic Guitar (steel)",
+    "Electric Guitar (jazz)",
+    "Electric Guitar (clean)",
+    "Electric Guitar (muted)",
+    "Overdriven Guitar",
+    "Distortion Guitar",
+    "Guitar Harmonics",
+    // Bass
+    "Acoustic Bass",
+    "Electric Bass (finger)",
+    "Electric Bass (pick)",
+    "Fretless Bass",
+    "Slap Bass 1",
+    "Slap Bass 2",
+    "Synth Bass 1",
+    "Synth Bass 2",
+    // Strings
+    "Violin",
+    "Viola",
+    "Cello",
+    "Contrabass",
+    "Tremolo Strings",
+    "Pizzicato Strings",
+    "Orchestral Harp",
+    "Timpani",
+    // Ensemble
+    "String Ensemble 1",
+    "String Ensemble 2",
+    "Synth Strings 1",
+    "Synth Strings 2",
+    "Choir Aahs",
+    "Voice Oohs",
+    "Synth Voice",
+    "Orchestra Hit",
+    // Brass
+    "Trumpet",
+    "Trombone",
+    "Tuba",
+    "Muted Trumpet",
+    "French Horn",
+    "Brass Section",
+    "Synth Brass 1",
+    "Synth Brass 2",
+    // Reed
+    "Soprano Sax",
+    "Alto Sax",
+    "Tenor Sax",
+    "Baritone Sax",
+    "Oboe",
+    "English Horn",
+    "Bassoon",
+    "Clarinet",
+    // Pipe
+    "Piccolo",
+    "Flute",
+    "Recorder",
+    "Pan Flute",
+    "Blown Bottle",
+    "Shakuhachi",
+    "Whistle",
+    "Ocarina",
+    // Synth Lead
+    "Lead 1 (square)",
+    "Lead 2 (sawtooth)",
+    "Lead 3 (calliope)",
+    "Lead 4 (chiff)",
+    "Lead 5 (charang)",
+    "Lead 6 (voice)",
+    "Lead 7 (fifths)",
+    "Lead 8 (bass + lead)",
+    // Synth Pad
+    "Pad 1 (new age)",
+    "Pad 2 (warm)",
+    "Pad 3 (polysynth)",
+    "Pad 4 (choir)",
+    "Pad 5 (bowed)",
+    "Pad 6 (metallic)",
+    "Pad 7 (halo)",
+    "Pad 8 (sweep)",
+    // Synth Effects
+    "FX 1 (rain)",
+    "FX 2 (soundtrack)",
+    "FX 3 (crystal)",
+    "FX 4 (atmosphere)",
+    "FX 5 (brightness)",
+    "FX 6 (goblins)",
+    "FX 7 (echoes)",
+    "FX 8 (sci-fi)",
+    // Ethnic
+    "Sitar",
+    "Banjo",
+    "Shamisen",
+    "Koto",
+    "Kalimba",
+    "Bag Pipe",
+    "Fiddle",
+    "Shanai",
+    // Percussive
+    "Tinkle Bell",
+    "Agogo",
+    "Steel Drums",
+    "Woodblock",
+    "Taiko Drum",
+    "Melodic Tom",
+    "Synth Drum",
+    "Reverse Cymbal",
+    // Sound Effects
+    "Guitar Fret Noise",
+    "Breath Noise",
+    "Seashore",
+    "Bird Tweet",
+    "Telephone Ring",
+    "Helicopter",
+    "Applause",
+    "Gunshot",
+];
+
+/// GM program name for a zero-based program number.
+pub fn program_name(program: u8) -> &'static str {
+    PROGRAM_NAMES[(program & 0x7F) as usize]
+}
+
+/// GM2 drum kit selected by a Program Change on a rhythm channel.
+pub fn drum_kit_name(program: u8) -> Option<&'static str> {
+    let name = match program {
+        0 => "Standard Kit",
+        8 => "Room Kit",
+        16 => "Power Kit",
+        24 => "Electronic Kit",
+        25 => "Analog Kit",
+        32 => "Jazz Kit",
+        40 => "Brush Kit",
+        48 => "Orchestra Kit",
+        56 => "SFX Kit",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Percussion sound for a note on a rhythm channel: the GM map (35-81)
+/// plus the GM2 additions at either end (27-34, 82-87).
+pub fn drum_name(note: u8) -> Option<&'static str> {
+    let name = match note {
+        27 => "High Q",
+        28 => "Slap",
+        29 => "Scratch Push",
+        30 => "Scratch Pull",
+        31 => "Sticks",
+        32 => "Square Click",
+        33 => "Metronome Click",
+        34 => "Metronome Bell",
+        35 => "Kick Drum 2",
+        36 => "Kick Drum 1",
+        37 => "Side Stick",
+        38 => "Acoustic Snare",
+        39 => "Hand Clap",
+        40 => "Electric Snare",
+        41 => "Low Floor Tom",
+        42 => "Closed Hi-Hat",
+        43 => "High Floor Tom",
+        44 => "Pedal Hi-Hat",
+        45 => "Low Tom",
+        46 => "Open Hi-Hat",
+        47 => "Low-Mid Tom",
+        48 => "Hi-Mid Tom",
+        49 => "Crash Cymbal 1",
+        50 => "High Tom",
+        51 => "Ride Cymbal 1",
+        52 => "Chinese Cymbal",
+        53 => "Ride Bell",
+        54 => "Tambourine",
+        55 => "Splash Cymbal",
+        56 => "Cowbell",
+        57 => "Crash Cymbal 2",
+        58 => "Vibraslap",
+        59 => "Ride Cymbal 2",
+        60 => "Hi Bongo",
+        61 => "Low Bongo",
+        62 => "Mute Hi Conga",
+        63 => "Open Hi Conga",
+        64 => "Low Conga",
+        65 => "High Timbale",
+        66 => "Low Timbale",
+        67 => "High Agogo",
+        68 => "Low Agogo",
+        69 => "Cabasa",
+        70 => "Maracas",
+        71 => "Short Whistle",
+        72 => "Long Whistle",
+        73 => "Short Guiro",
+        74 => "Long Guiro",
+        75 => "Claves",
+        76 => "Hi Wood Block",
+        77 => "Low Wood Block",
+        78 => "Mute Cuica",
+        79 => "Open Cuica",
+        80 => "Mute Triangle",
+        81 => "Open Triangle",
+        82 => "Shaker",
+        83 => "Jingle Bell",
+        84 => "Belltree",
+        85 => "Castanets",
+        86 => "Mute Surdo",
+        87 => "Open Surdo",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Name of a Control Change number as defined by MIDI 1.0 and GM2.
+pub fn controller_name(controller: u8) -> Option<&'static str> {
+    let name = match controller {
+        0 => "Bank Select",
+        1 => "Modulation",
+        2 => "Breath Controller",
+        4 => "Foot Controller",
+        5 => "Portamento Time",
+        6 => "Data Entry",
+        7 => "Volume",
+        8 => "Balance",
+        10 => "Pan",
+        11 => "Expression",
+        12 => "Effect Control 1",
+        13 => "Effect Control 2",
+        16 => "General Purpose 1",
+        17 => "General Purpose 2",
+        18 => "General Purpose 3",
+        19 => "General Purpose 4",
+        32 => "Bank Select LSB",
+        33 => "Modulation LSB",
+        38 => "Data Entry LSB",
+        39 => "Volume LSB",
+        42 => "Pan LSB",
+        43 => "Expression LSB",
+        64 => "Sustain",
+        65 => "Portamento",
+        66 => "Sostenuto",
+        67 => "Soft Pedal",
+        68 => "Legato Footswitch",
+        69 => "Hold 2",
+        70 => "Sound Variation",
+        71 => "Resonance",
+        72 => "Release Time",
+        73 => "Attack Time",
+        74 => "Brightness",
+        75 => "Decay Time",
+        76 => "Vibrato Rate",
+        77 => "Vibrato Depth",
+        78 => "Vibrato Delay",
+        84 => "Portamento Control",
+        88 => "High Resolution Velocity Prefix",
+        91 => "Reverb Send",
+        92 => "Tremolo Depth",
+        93 => "Chorus Send",
+        94 => "Celeste Depth",
+        95 => "Phaser Depth",
+        96 => "Data Increment",
+        97 => "Data Decrement",
+        98 => "NRPN LSB",
+        99 => "NRPN MSB",
+        100 => "RPN LSB",
+        101 => "RPN MSB",
+        120 => "All Sound Off",
+        121 => "Reset All Controllers",
+        122 => "Local Control",
+        123 => "All Notes Off",
+        124 => "Omni Off",
+        125 => "Omni On",
+        126 => "Mono On",
+        127 => "Poly On",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Whether a controller is an on/off switch (values below 64 are off).
+pub fn is_switch_controller(controller: u8) -> bool {
+    matches!(controller, 64..=69 | 122)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_program_names() {
+        assert_eq!(program_name(0), "Acoustic Grand Piano");
+        assert_eq!(program_name(40), "Violin");
+        assert_eq!(program_name(127), "Gunshot");
+        assert_eq!(drum_kit_name(25), Some("Analog Kit"));
+        assert_eq!(drum_kit_name(1), None);
+    }
+
+    #[test]
+    fn test_drum_and_controller_names() {
+        assert_eq!(drum_name(36), Some("Kick Drum 1"));
+        assert_eq!(drum_name(42), Some("Closed Hi-Hat"));
+        assert_eq!(drum_name(87), Some("Open Surdo"));
+        assert_eq!(drum_name(26), None);
+        assert_eq!(controller_name(7), Some("Volume"));
+        assert_eq!(controller_name(64), Some("Sustain"));
+        assert_eq!(controller_name(3), None);
+    }
+}
@@ -0,0 +1,451 @@
+//! MIDI message types, parsing and the JSON wire format shared by the
+//! MIDI Monitor backend, the Tauri app and the Leptos frontend.
+//!
+//! This crate has no platform dependencies so it builds for both native
+//! targets and `wasm32-unknown-unknown`.
+
+mod command;
+pub mod describe;
+pub mod gm;
+mod message;
+pub mod mpe;
+pub mod note;
+pub mod parameter;
+mod port;
+pub mod sysex;
+mod timecode;
+mod transport;
+pub mod ump;
+
+pub use command::{ClientMessage, ClientRequest, CommandReply, EventFilter};
+pub use describe::Describer;
+pub use message::{MalformedMessage, MalformedReason, MidiMessage};
+pub use mpe::{MpeLayout, MpeZone, MpeZoneSide, NoteExpression};
+pub use note::note_name;
+pub use parameter::{ParameterChange, ParameterType};
+pub use port::{PortDirection, PortInfo};
+use serde::{Deserialize, Serialize};
+pub use sysex::{SysEx, UniversalSysEx};
+pub use timecode::{FrameRate, Timecode, TimecodeDirection, TimecodeState};
+pub use transport::{TransportState, TransportStatus};
+pub use ump::{Midi2ChannelVoice, Ump};
+
+/// Version of the JSON schema spoken on `/ws`.
+///
+/// Bumped whenever the wire format changes incompatibly, so clients can
+/// refuse to talk to a server they don't understand. New event kinds and
+/// message types are added without a bump; clients should skip frames they
+/// can't parse.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Everything the server sends WebSocket clients.
+///
+/// Tagged by `kind`; the payload's own fields sit next to the tag.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ServerEvent {
+    /// A decoded MIDI message, itself tagged by `type`.
+    Midi(MidiMessage),
+    /// Tempo and transport state derived from MIDI clock.
+    Transport(TransportState),
+    /// An RPN, NRPN or 14-bit controller value assembled from Control Changes.
+    Parameter(ParameterChange),
+    /// SMPTE time reconstructed from MIDI Time Code.
+    Timecode(TimecodeState),
+    /// MPE zones, sent when a controller configures them.
+    MpeLayout(MpeLayout),
+    /// Per-note expression of a note on an MPE member channel.
+    NoteExpression(NoteExpression),
+    /// Input that couldn't be decoded, with its raw bytes.
+    Malformed(MalformedMessage),
+    /// The MIDI input ports the backend is now listening to.
+    Inputs { ports: Vec<PortInfo> },
+    /// A MIDI port appeared on the host.
+    DeviceConnected(PortInfo),
+    /// A MIDI port went away.
+    DeviceDisconnected(PortInfo),
+    /// A client's request couldn't be carried out; sent to that client only.
+    Error { message: String },
+    /// A client's command was carried out; sent to that client only.
+    Reply(CommandReply),
+}
+
+impl ServerEvent {
+    /// Every `kind` tag.
+    pub const KINDS: [&'static str; 12] = [
+        "midi",
+        "transport",
+        "parameter",
+        "timecode",
+        "mpe_layout",
+        "note_expression",
+        "malformed",
+        "inputs",
+        "device_connected",
+        "device_disconnected",
+        "error",
+        "reply",
+    ];
+
+    /// The `kind` tag this event is serialized with.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ServerEvent::Midi(_) => "midi",
+            ServerEvent::Transport(_) => "transport",
+            ServerEvent::Parameter(_) => "parameter",
+            ServerEvent::Timecode(_) => "timecode",
+            ServerEvent::MpeLayout(_) => "mpe_layout",
+            ServerEvent::NoteExpression(_) => "note_expression",
+            ServerEvent::Malformed(_) => "malformed",
+            ServerEvent::Inputs { .. } => "inputs",
+            ServerEvent::DeviceConnected(_) => "device_connected",
+            ServerEvent::DeviceDisconnected(_) => "device_disconnected",
+            ServerEvent::Error { .. } => "error",
+            ServerEvent::Reply(_) => "reply",
+        }
+    }
+}
+
+impl From<MidiMessage> for ServerEvent {
+    fn from(message: MidiMessage) -> Self {
+        ServerEvent::Midi(message)
+    }
+}
+
+/// Where an event came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventSource {
+    /// Port id as listed by `/api/ports`, or `simulation`.
+    pub id: String,
+    pub name: String,
+}
+
+/// The JSON object sent to clients for every event.
+///
+/// The event's fields are flattened next to `version`:
+/// `{"version":2,"description":"Ch1 Volume = 100","kind":"midi","type":"ControlChange","channel":0,"controller":7,"value":100}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WireMessage {
+    pub version: u32,
+    /// Human-readable summary, e.g. `Ch10 Kick Drum 1 vel 100`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Monotonic source timestamp in microseconds. For device input this
+    /// keeps the driver's own spacing between events; events derived from a
+    /// message share its timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_us: Option<u64>,
+    /// Wall-clock time the backend captured the event, in microseconds
+    /// since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub captured_at_us: Option<u64>,
+    /// Input the event arrived on; events the server makes up on its own,
+    /// such as clock timeouts, have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<EventSource>,
+    /// The `id` of the client request a reply or error answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<u64>,
+    #[serde(flatten)]
+    pub event: ServerEvent,
+}
+
+impl WireMessage {
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_timestamps(mut self, timestamp_us: u64, captured_at_us: u64) -> Self {
+        self.timestamp_us = Some(timestamp_us);
+        self.captured_at_us = Some(captured_at_us);
+        self
+    }
+
+    pub fn with_source(mut self, source: EventSource) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn with_reply_to(mut self, id: Option<u64>) -> Self {
+        self.reply_to = id;
+        self
+    }
+}
+
+impl From<ServerEvent> for WireMessage {
+    fn from(event: ServerEvent) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            description: None,
+            timestamp_us: None,
+            captured_at_us: None,
+            source: None,
+            reply_to: None,
+            event,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wire_format() {
+        let wire = WireMessage::from(ServerEvent::from(MidiMessage::NoteOn {
+            channel: 9,
+            note: 36,
+            velocity: 100,
+        }));
+        let json = serde_json::to_value(&wire).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": PROTOCOL_VERSION,
+                "kind": "midi",
+                "type": "NoteOn",
+                "channel": 9,
+                "note": 36,
+                "velocity": 100
+            })
+        );
+
+        let parsed: WireMessage = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, wire);
+
+        let unknown =
+            ServerEvent::from(MidiMessage::from_raw_message(&[0xF4, 0x10, 0x20]).unwrap());
+        let json = serde_json::to_value(WireMessage::from(unknown)).unwrap();
+        assert_eq!(json["type"], "Unknown");
+        assert_eq!(json["status"], 0xF4);
+        assert_eq!(json["data"], serde_json::json!([0x10, 0x20]));
+
+        let clock = WireMessage::from(ServerEvent::from(MidiMessage::TimingClock));
+        let json = serde_json::to_value(&clock).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"version": PROTOCOL_VERSION, "kind": "midi", "type": "TimingClock"})
+        );
+        assert_eq!(serde_json::from_value::<WireMessage>(json).unwrap(), clock);
+    }
+
+    #[test]
+    fn test_described_timestamped_and_sourced_wire_format() {
+        let wire = WireMessage::from(ServerEvent::from(MidiMessage::NoteOn {
+            channel: 9,
+            note: 36,
+            velocity: 100,
+        }))
+        .with_description("Ch10 Kick Drum 1 vel 100")
+        .with_timestamps(1_500, 1_700_000_000_000_000)
+        .with_source(EventSource {
+            id: "input:MPD218".to_string(),
+            name: "MPD218".to_string(),
+        });
+        let json = serde_json::to_value(&wire).unwrap();
+        assert_eq!(json["description"], "Ch10 Kick Drum 1 vel 100");
+        assert_eq!(json["timestamp_us"], 1_500);
+        assert_eq!(json["captured_at_us"], 1_700_000_000_000_000u64);
+        assert_eq!(
+            json["source"],
+            serde_json::json!({"id": "input:MPD218", "name": "MPD218"})
+        );
+        assert_eq!(json["note"], 36);
+        assert_eq!(serde_json::from_value::<WireMessage>(json).unwrap(), wire);
+    }
+
+    #[test]
+    fn test_sysex_wire_format() {
+        let message = MidiMessage::from_raw_message(&[0xF0, 0x43, 0x10, 0xF7]).unwrap();
+        let wire = WireMessage::from(ServerEvent::from(message));
+        let json = serde_json::to_value(&wire).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": PROTOCOL_VERSION,
+                "kind": "midi",
+                "type": "SysEx",
+                "manufacturer_id": [0x43],
+                "manufacturer": "Yamaha",
+                "universal": null,
+                "payload_len": 1,
+                "data": [0xF0, 0x43, 0x10, 0xF7]
+            })
+        );
+        assert_eq!(serde_json::from_value::<WireMessage>(json).unwrap(), wire);
+    }
+
+    #[test]
+    fn test_malformed_wire_format() {
+        let malformed = MidiMessage::parse(&[0x90, 0x80, 100]).unwrap_err();
+        let wire = WireMessage::from(ServerEvent::Malformed(malformed));
+        let json = serde_json::to_value(&wire).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": PROTOCOL_VERSION,
+                "kind": "malformed",
+                "reason": "data_byte_high_bit",
+                "data": [0x90, 0x80, 100]
+            })
+        );
+        assert_eq!(serde_json::from_value::<WireMessage>(json).unwrap(), wire);
+    }
+
+    #[test]
+    fn test_client_message_format() {
+        let json = r#"{"kind":"send","output":"output:Synth","message":{"type":"ProgramChange","channel":0,"program":5}}"#;
+        let send = ClientMessage::Send {
+            output: "output:Synth".to_string(),
+            message: MidiMessage::ProgramChange {
+                channel: 0,
+                program: 5,
+            },
+        };
+        assert_eq!(serde_json::from_str::<ClientMessage>(json).unwrap(), send);
+        // Commands without an id are still requests
+        assert_eq!(
+            serde_json::from_str::<ClientRequest>(json).unwrap(),
+            ClientRequest {
+                id: None,
+                command: send
+            }
+        );
+
+        let json = r#"{"id":7,"kind":"subscribe","filter":{"kinds":["midi"]}}"#;
+        let subscribe = ClientRequest::new(
+            7,
+            ClientMessage::Subscribe {
+                filter: EventFilter {
+                    kinds: vec!["midi".to_string()],
+                    ..EventFilter::default()
+                },
+            },
+        );
+        assert_eq!(
+            serde_json::from_str::<ClientRequest>(json).unwrap(),
+            subscribe
+        );
+        assert_eq!(serde_json::to_string(&subscribe).unwrap(), json);
+        let json = r#"{"kind":"subscribe","filter":{"channels":[9],"notes":[36,51],"sources":["input:MPD218"]}}"#;
+        assert_eq!(
+            serde_json::from_str::<ClientMessage>(json).unwrap(),
+            ClientMessage::Subscribe {
+                filter: EventFilter {
+                    channels: vec![9],
+                    notes: Some([36, 51]),
+                    sources: vec!["input:MPD218".to_string()],
+                    ..EventFilter::default()
+                },
+            }
+        );
+        assert!(serde_json::from_str::<ClientMessage>(
+            r#"{"kind":"subscribe","filter":{"channel":9}}"#
+        )
+        .is_err());
+        assert_eq!(
+            serde_json::from_str::<ClientRequest>(r#"{"id":8,"kind":"panic"}"#).unwrap(),
+            ClientRequest::new(8, ClientMessage::Panic { output: None })
+        );
+        assert!(serde_json::from_str::<ClientRequest>(r#"{"id":9,"kind":"reboot"}"#).is_err());
+    }
+
+    #[test]
+    fn test_reply_wire_format() {
+        let recorded = WireMessage::from(ServerEvent::from(MidiMessage::TimingClock))
+            .with_timestamps(1_500, 1_700_000_000_000_000);
+        let wire = WireMessage::from(ServerEvent::Reply(CommandReply::RecordingStopped {
+            events: vec![recorded],
+        }))
+        .with_reply_to(Some(3));
+        let json = serde_json::to_value(&wire).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": PROTOCOL_VERSION,
+                "reply_to": 3,
+                "kind": "reply",
+                "result": "recording_stopped",
+                "events": [{
+                    "version": PROTOCOL_VERSION,
+                    "timestamp_us": 1_500,
+                    "captured_at_us": 1_700_000_000_000_000u64,
+                    "kind": "midi",
+                    "type": "TimingClock"
+                }]
+            })
+        );
+        assert_eq!(serde_json::from_value::<WireMessage>(json).unwrap(), wire);
+
+        let wire = WireMessage::from(ServerEvent::Reply(CommandReply::Sent));
+        let json = serde_json::to_value(&wire).unwrap();
+        assert_eq!(json["result"], "sent");
+        assert_eq!(serde_json::from_value::<WireMessage>(json).unwrap(), wire);
+    }
+
+    #[test]
+    fn test_kinds_match_wire_tags() {
+        let events = [
+            ServerEvent::from(MidiMessage::Start),
+            ServerEvent::Inputs { ports: Vec::new() },
+            ServerEvent::Error {
+                message: "No MIDI output".to_string(),
+            },
+            ServerEvent::Reply(CommandReply::Unsubscribed),
+        ];
+        for event in events {
+            let json = serde_json::to_value(&event).unwrap();
+            assert_eq!(json["kind"], event.kind());
+            assert!(ServerEvent::KINDS.contains(&event.kind()));
+        }
+    }
+
+    #[test]
+    fn test_device_wire_format() {
+        let wire = WireMessage::from(ServerEvent::DeviceConnected(PortInfo {
+            id: "input:Keystation 49".to_string(),
+            name: "Keystation 49".to_string(),
+            direction: PortDirection::Input,
+            connected: false,
+        }));
+        let json = serde_json::to_value(&wire).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": PROTOCOL_VERSION,
+                "kind": "device_connected",
+                "id": "input:Keystation 49",
+                "name": "Keystation 49",
+                "direction": "input",
+                "connected": false
+            })
+        );
+        assert_eq!(serde_json::from_value::<WireMessage>(json).unwrap(), wire);
+    }
+
+    #[test]
+    fn test_transport_wire_format() {
+        let wire = WireMessage::from(ServerEvent::Transport(TransportState {
+            status: TransportStatus::Playing,
+            clock: true,
+            bpm: Some(120.0),
+            jitter_ms: Some(0.25),
+            song_position: 16,
+        }));
+        let json = serde_json::to_value(&wire).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": PROTOCOL_VERSION,
+                "kind": "transport",
+                "status": "playing",
+                "clock": true,
+                "bpm": 120.0,
+                "jitter_ms": 0.25,
+                "song_position": 16
+            })
+        );
+        assert_eq!(serde_json::from_value::<WireMessage>(json).unwrap(), wire);
+    }
+}
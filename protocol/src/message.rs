@@ -0,0 +1,695 @@
+use crate::sysex::{SysEx, SYSEX_END, SYSEX_START};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A decoded MIDI 1.0 message.
+///
+/// Serialized as an internally tagged JSON object: the `type` field holds
+/// the variant name and the remaining fields sit next to it, e.g.
+/// `{"type":"NoteOn","channel":0,"note":60,"velocity":100}`. Channels are
+/// zero-based (0-15) and pitch bend is signed around the center, -8192..=8191.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum MidiMessage {
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    PolyPressure {
+        channel: u8,
+        note: u8,
+        pressure: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+    ChannelPressure {
+        channel: u8,
+        pressure: u8,
+    },
+    PitchBend {
+        channel: u8,
+        value: i16,
+    },
+    /// System Exclusive, kept in full.
+    SysEx(SysEx),
+    /// System Common: MIDI Time Code quarter frame. `piece` (0-7) says which
+    /// nibble of the timecode `value` carries.
+    QuarterFrame {
+        piece: u8,
+        value: u8,
+    },
+    /// System Common: position in MIDI beats (sixteenth notes) since the
+    /// start of the song.
+    SongPositionPointer {
+        beats: u16,
+    },
+    /// System Common
+    SongSelect {
+        song: u8,
+    },
+    /// System Common
+    TuneRequest,
+    /// System Real-Time: sent 24 times per quarter note.
+    TimingClock,
+    /// System Real-Time
+    Start,
+    /// System Real-Time
+    Continue,
+    /// System Real-Time
+    Stop,
+    /// System Real-Time
+    ActiveSensing,
+    /// System Real-Time
+    SystemReset,
+    /// Anything the decoder doesn't understand yet, kept as raw bytes.
+    Unknown {
+        status: u8,
+        data: Vec<u8>,
+    },
+}
+
+impl MidiMessage {
+    /// Decodes one complete MIDI message, as delivered by midir.
+    ///
+    /// Rejects an empty slice, a message that doesn't start with a status
+    /// byte, a truncated message and data bytes with the high bit set,
+    /// keeping the raw bytes. Bytes past the end of a complete message are
+    /// ignored.
+    pub fn parse(message: &[u8]) -> Result<Self, MalformedMessage> {
+        let malformed = |reason| MalformedMessage {
+            reason,
+            data: message.to_vec(),
+        };
+        let Some(&status) = message.first() else {
+            return Err(malformed(MalformedReason::Truncated));
+        };
+        if status & 0x80 == 0 || status == SYSEX_END {
+            return Err(malformed(MalformedReason::UnexpectedStatus));
+        }
+
+        let data = if status == SYSEX_START {
+            // The End of Exclusive is optional, but nothing else may follow
+            match message.last() {
+                Some(&SYSEX_END) if message.len() > 1 => &message[1..message.len() - 1],
+                _ => &message[1..],
+            }
+        } else {
+            let len = Self::data_len(status);
+            if message.len() < 1 + len {
+                return Err(malformed(MalformedReason::Truncated));
+            }
+            &message[1..1 + len]
+        };
+        if data.iter().any(|byte| byte & 0x80 != 0) {
+            return Err(malformed(MalformedReason::DataByteHighBit));
+        }
+
+        let channel = status & 0x0F;
+        let message = match status & 0xF0 {
+            0x90 => {
+                // Note On; velocity 0 is actually Note Off
+                let (note, velocity) = (data[0], data[1]);
+                if velocity == 0 {
+                    MidiMessage::NoteOff {
+                        channel,
+                        note,
+                        velocity,
+                    }
+                } else {
+                    MidiMessage::NoteOn {
+                        channel,
+                        note,
+                        velocity,
+                    }
+                }
+            }
+            0x80 => MidiMessage::NoteOff {
+                channel,
+                note: data[0],
+                velocity: data[1],
+            },
+            // Polyphonic Key Pressure (aftertouch)
+            0xA0 => MidiMessage::PolyPressure {
+                channel,
+                note: data[0],
+                pressure: data[1],
+            },
+            0xB0 => MidiMessage::ControlChange {
+                channel,
+                controller: data[0],
+                value: data[1],
+            },
+            0xC0 => MidiMessage::ProgramChange {
+                channel,
+                program: data[0],
+            },
+            // Channel Pressure (aftertouch)
+            0xD0 => MidiMessage::ChannelPressure {
+                channel,
+                pressure: data[0],
+            },
+            0xE0 => {
+                // Pitch Bend: 14-bit value, LSB first, centered on 8192
+                let raw = data[0] as i16 | (data[1] as i16) << 7;
+                MidiMessage::PitchBend {
+                    channel,
+                    value: raw - 8192,
+                }
+            }
+            _ => Self::from_system_message(message, data)?,
+        };
+        Ok(message)
+    }
+
+    /// Like [`MidiMessage::parse`], for callers that just skip malformed
+    /// input.
+    pub fn from_raw_message(message: &[u8]) -> Option<Self> {
+        Self::parse(message).ok()
+    }
+
+    /// Encodes the message as raw MIDI 1.0 bytes, status byte first.
+    ///
+    /// Channels and data bytes are masked into range, so the output of
+    /// other messages always decodes. SysEx and unknown messages are written
+    /// back as received, which need not decode.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (status, data): (u8, &[u8]) = match *self {
+            MidiMessage::NoteOff {
+                channel,
+                note,
+                velocity,
+            } => (0x80 | (channel & 0x0F), &[note, velocity]),
+            MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => (0x90 | (channel & 0x0F), &[note, velocity]),
+            MidiMessage::PolyPressure {
+                channel,
+                note,
+                pressure,
+            } => (0xA0 | (channel & 0x0F), &[note, pressure]),
+            MidiMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            } => (0xB0 | (channel & 0x0F), &[controller, value]),
+            MidiMessage::ProgramChange { channel, program } => {
+                (0xC0 | (channel & 0x0F), &[program])
+            }
+            MidiMessage::ChannelPressure { channel, pressure } => {
+                (0xD0 | (channel & 0x0F), &[pressure])
+            }
+            MidiMessage::PitchBend { channel, value } => {
+                let value = (value as i32 + 8192).clamp(0, 0x3FFF) as u16;
+                (0xE0 | (channel & 0x0F), &[value as u8, (value >> 7) as u8])
+            }
+            MidiMessage::SysEx(ref sysex) => return sysex.data.clone(),
+            MidiMessage::QuarterFrame { piece, value } => {
+                (0xF1, &[(piece & 0x07) << 4 | (value & 0x0F)])
+            }
+            MidiMessage::SongPositionPointer { beats } => {
+                (0xF2, &[beats as u8, (beats >> 7) as u8])
+            }
+            MidiMessage::SongSelect { song } => (0xF3, &[song]),
+            MidiMessage::TuneRequest => (0xF6, &[]),
+            MidiMessage::TimingClock => (0xF8, &[]),
+            MidiMessage::Start => (0xFA, &[]),
+            MidiMessage::Continue => (0xFB, &[]),
+            MidiMessage::Stop => (0xFC, &[]),
+            MidiMessage::ActiveSensing => (0xFE, &[]),
+            MidiMessage::SystemReset => (0xFF, &[]),
+            MidiMessage::Unknown { status, ref data } => {
+                return std::iter::once(status)
+                    .chain(data.iter().copied())
+                    .collect()
+            }
+        };
+        std::iter::once(status)
+            .chain(data.iter().map(|byte| byte & 0x7F))
+            .collect()
+    }
+
+    /// Number of data bytes following a status byte, other than SysEx's.
+    pub fn data_len(status: u8) -> usize {
+        match status & 0xF0 {
+            0xC0 | 0xD0 => 1,
+            0x80 | 0x90 | 0xA0 | 0xB0 | 0xE0 => 2,
+            _ => match status {
+                0xF1 | 0xF3 => 1,
+                0xF2 => 2,
+                _ => 0,
+            },
+        }
+    }
+
+    fn from_system_message(message: &[u8], data: &[u8]) -> Result<Self, MalformedMessage> {
+        let status = message[0];
+        let message = match status {
+            SYSEX_START => {
+                MidiMessage::SysEx(SysEx::parse(message).ok_or_else(|| MalformedMessage {
+                    reason: MalformedReason::Truncated,
+                    data: message.to_vec(),
+                })?)
+            }
+            // MTC Quarter Frame: 0nnndddd
+            0xF1 => MidiMessage::QuarterFrame {
+                piece: data[0] >> 4,
+                value: data[0] & 0x0F,
+            },
+            // Song Position Pointer: 14-bit value, LSB first
+            0xF2 => MidiMessage::SongPositionPointer {
+                beats: data[0] as u16 | (data[1] as u16) << 7,
+            },
+            0xF3 => MidiMessage::SongSelect { song: data[0] },
+            0xF6 => MidiMessage::TuneRequest,
+            0xF8 => MidiMessage::TimingClock,
+            0xFA => MidiMessage::Start,
+            0xFB => MidiMessage::Continue,
+            0xFC => MidiMessage::Stop,
+            0xFE => MidiMessage::ActiveSensing,
+            0xFF => MidiMessage::SystemReset,
+            // Undefined statuses (F4, F5, F9, FD) are kept with whatever follows
+            _ => MidiMessage::Unknown {
+                status,
+                data: message[1..].to_vec(),
+            },
+        };
+        Ok(message)
+    }
+
+    /// Whether this is a single-byte System Real-Time message, which may
+    /// appear anywhere in a stream.
+    pub fn is_realtime(&self) -> bool {
+        matches!(
+            self,
+            MidiMessage::TimingClock
+                | MidiMessage::Start
+                | MidiMessage::Continue
+                | MidiMessage::Stop
+                | MidiMessage::ActiveSensing
+                | MidiMessage::SystemReset
+        )
+    }
+
+    /// Zero-based channel for channel voice messages.
+    pub fn channel(&self) -> Option<u8> {
+        match self {
+            MidiMessage::NoteOff { channel, .. }
+            | MidiMessage::NoteOn { channel, .. }
+            | MidiMessage::PolyPressure { channel, .. }
+            | MidiMessage::ControlChange { channel, .. }
+            | MidiMessage::ProgramChange { channel, .. }
+            | MidiMessage::ChannelPressure { channel, .. }
+            | MidiMessage::PitchBend { channel, .. } => Some(*channel),
+            _ => None,
+        }
+    }
+
+    /// Note number for messages about a single note.
+    pub fn note(&self) -> Option<u8> {
+        match self {
+            MidiMessage::NoteOff { note, .. }
+            | MidiMessage::NoteOn { note, .. }
+            | MidiMessage::PolyPressure { note, .. } => Some(*note),
+            _ => None,
+        }
+    }
+
+    /// Every `type` name a message can have.
+    pub const TYPES: [&'static str; 19] = [
+        "NoteOff",
+        "NoteOn",
+        "PolyPressure",
+        "ControlChange",
+        "ProgramChange",
+        "ChannelPressure",
+        "PitchBend",
+        "SysEx",
+        "QuarterFrame",
+        "SongPositionPointer",
+        "SongSelect",
+        "TuneRequest",
+        "TimingClock",
+        "Start",
+        "Continue",
+        "Stop",
+        "ActiveSensing",
+        "SystemReset",
+        "Unknown",
+    ];
+
+    /// The `type` the message is serialized with, e.g. `NoteOn`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            MidiMessage::NoteOff { .. } => "NoteOff",
+            MidiMessage::NoteOn { .. } => "NoteOn",
+            MidiMessage::PolyPressure { .. } => "PolyPressure",
+            MidiMessage::ControlChange { .. } => "ControlChange",
+            MidiMessage::ProgramChange { .. } => "ProgramChange",
+            MidiMessage::ChannelPressure { .. } => "ChannelPressure",
+            MidiMessage::PitchBend { .. } => "PitchBend",
+            MidiMessage::SysEx(_) => "SysEx",
+            MidiMessage::QuarterFrame { .. } => "QuarterFrame",
+            MidiMessage::SongPositionPointer { .. } => "SongPositionPointer",
+            MidiMessage::SongSelect { .. } => "SongSelect",
+            MidiMessage::TuneRequest => "TuneRequest",
+            MidiMessage::TimingClock => "TimingClock",
+            MidiMessage::Start => "Start",
+            MidiMessage::Continue => "Continue",
+            MidiMessage::Stop => "Stop",
+            MidiMessage::ActiveSensing => "ActiveSensing",
+            MidiMessage::SystemReset => "SystemReset",
+            MidiMessage::Unknown { .. } => "Unknown",
+        }
+    }
+}
+
+/// Why bytes couldn't be decoded as a MIDI message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MalformedReason {
+    /// Fewer data bytes than the status byte calls for, or no bytes at all.
+    Truncated,
+    /// A byte with the high bit set where a data byte belongs.
+    DataByteHighBit,
+    /// The message starts with a data byte or a lone End of Exclusive.
+    UnexpectedStatus,
+}
+
+impl fmt::Display for MalformedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MalformedReason::Truncated => "truncated",
+            MalformedReason::DataByteHighBit => "data byte with high bit set",
+            MalformedReason::UnexpectedStatus => "unexpected status",
+        })
+    }
+}
+
+/// Bytes that don't form a valid MIDI message, kept as received.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MalformedMessage {
+    pub reason: MalformedReason,
+    pub data: Vec<u8>,
+}
+
+impl fmt::Display for MalformedMessage {
+    /// e.g. `Malformed (truncated): 90 3C`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Malformed ({}):", self.reason)?;
+        for byte in &self.data {
+            write!(f, " {:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_midi_message_parsing() {
+        // Test Note On
+        let note_on = vec![0x90, 60, 64]; // Note On, C4, velocity 64
+        let msg = MidiMessage::from_raw_message(&note_on).unwrap();
+        assert_eq!(
+            msg,
+            MidiMessage::NoteOn {
+                channel: 0,
+                note: 60,
+                velocity: 64
+            }
+        );
+
+        // Test Note Off
+        let note_off = vec![0x80, 60, 0]; // Note Off, C4
+        let msg = MidiMessage::from_raw_message(&note_off).unwrap();
+        assert_eq!(
+            msg,
+            MidiMessage::NoteOff {
+                channel: 0,
+                note: 60,
+                velocity: 0
+            }
+        );
+
+        // Note On with velocity 0 is a Note Off
+        let msg = MidiMessage::from_raw_message(&[0x90, 60, 0]).unwrap();
+        assert!(matches!(msg, MidiMessage::NoteOff { note: 60, .. }));
+
+        // Test Control Change
+        let cc = vec![0xB0, 7, 100]; // CC, Volume, value 100
+        let msg = MidiMessage::from_raw_message(&cc).unwrap();
+        assert_eq!(
+            msg,
+            MidiMessage::ControlChange {
+                channel: 0,
+                controller: 7,
+                value: 100
+            }
+        );
+    }
+
+    #[test]
+    fn test_channel_is_preserved() {
+        let msg = MidiMessage::from_raw_message(&[0x9A, 36, 100]).unwrap();
+        assert!(matches!(msg, MidiMessage::NoteOn { .. }));
+        assert_eq!(msg.channel(), Some(10));
+
+        let msg = MidiMessage::from_raw_message(&[0x8F, 36, 0]).unwrap();
+        assert_eq!(msg.channel(), Some(15));
+    }
+
+    #[test]
+    fn test_channel_voice_messages() {
+        // Polyphonic Key Pressure, channel 2, note 60, pressure 90
+        let msg = MidiMessage::from_raw_message(&[0xA1, 60, 90]).unwrap();
+        assert_eq!(
+            msg,
+            MidiMessage::PolyPressure {
+                channel: 1,
+                note: 60,
+                pressure: 90
+            }
+        );
+
+        // Program Change, channel 1, program 5
+        let msg = MidiMessage::from_raw_message(&[0xC0, 5]).unwrap();
+        assert_eq!(
+            msg,
+            MidiMessage::ProgramChange {
+                channel: 0,
+                program: 5
+            }
+        );
+
+        // Channel Pressure, channel 4, pressure 127
+        let msg = MidiMessage::from_raw_message(&[0xD3, 127]).unwrap();
+        assert_eq!(
+            msg,
+            MidiMessage::ChannelPressure {
+                channel: 3,
+                pressure: 127
+            }
+        );
+
+        // Truncated messages are rejected
+        assert!(MidiMessage::from_raw_message(&[0xC0]).is_none());
+        assert!(MidiMessage::from_raw_message(&[0xA0, 60]).is_none());
+    }
+
+    #[test]
+    fn test_pitch_bend_range() {
+        let center = MidiMessage::from_raw_message(&[0xE0, 0x00, 0x40]).unwrap();
+        assert_eq!(
+            center,
+            MidiMessage::PitchBend {
+                channel: 0,
+                value: 0
+            }
+        );
+
+        let min = MidiMessage::from_raw_message(&[0xE0, 0x00, 0x00]).unwrap();
+        assert_eq!(
+            min,
+            MidiMessage::PitchBend {
+                channel: 0,
+                value: -8192
+            }
+        );
+
+        let max = MidiMessage::from_raw_message(&[0xE5, 0x7F, 0x7F]).unwrap();
+        assert_eq!(
+            max,
+            MidiMessage::PitchBend {
+                channel: 5,
+                value: 8191
+            }
+        );
+    }
+
+    #[test]
+    fn test_system_common_messages() {
+        let msg = MidiMessage::from_raw_message(&[0xF1, 0x73]).unwrap();
+        assert_eq!(msg, MidiMessage::QuarterFrame { piece: 7, value: 3 });
+        assert!(MidiMessage::from_raw_message(&[0xF1]).is_none());
+
+        let msg = MidiMessage::from_raw_message(&[0xF2, 0x10, 0x02]).unwrap();
+        assert_eq!(msg, MidiMessage::SongPositionPointer { beats: 0x110 });
+        assert_eq!(msg.channel(), None);
+
+        let msg = MidiMessage::from_raw_message(&[0xF3, 4]).unwrap();
+        assert_eq!(msg, MidiMessage::SongSelect { song: 4 });
+
+        let msg = MidiMessage::from_raw_message(&[0xF6]).unwrap();
+        assert_eq!(msg, MidiMessage::TuneRequest);
+
+        assert!(MidiMessage::from_raw_message(&[0xF2, 0x10]).is_none());
+        assert!(MidiMessage::from_raw_message(&[0xF3]).is_none());
+    }
+
+    #[test]
+    fn test_system_realtime_messages() {
+        let cases = [
+            (0xF8, MidiMessage::TimingClock),
+            (0xFA, MidiMessage::Start),
+            (0xFB, MidiMessage::Continue),
+            (0xFC, MidiMessage::Stop),
+            (0xFE, MidiMessage::ActiveSensing),
+            (0xFF, MidiMessage::SystemReset),
+        ];
+        for (status, expected) in cases {
+            let msg = MidiMessage::from_raw_message(&[status]).unwrap();
+            assert_eq!(msg, expected);
+            assert!(msg.is_realtime());
+        }
+
+        // Undefined system statuses are kept as Unknown
+        let msg = MidiMessage::from_raw_message(&[0xF9]).unwrap();
+        assert_eq!(
+            msg,
+            MidiMessage::Unknown {
+                status: 0xF9,
+                data: vec![]
+            }
+        );
+        assert!(!msg.is_realtime());
+    }
+
+    #[test]
+    fn test_sysex_message() {
+        let bytes = [0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7];
+        let msg = MidiMessage::from_raw_message(&bytes).unwrap();
+        match msg {
+            MidiMessage::SysEx(sysex) => {
+                assert_eq!(sysex.data, bytes.to_vec());
+                assert_eq!(sysex.manufacturer_id, vec![0x7E]);
+            }
+            other => panic!("expected SysEx, got {:?}", other),
+        }
+        assert!(MidiMessage::from_raw_message(&[0xF0]).is_none());
+    }
+
+    #[test]
+    fn test_to_bytes_round_trip() {
+        let cases: [&[u8]; 16] = [
+            &[0x90, 60, 100],
+            &[0x8F, 36, 0],
+            &[0xA1, 60, 90],
+            &[0xB0, 7, 100],
+            &[0xC0, 5],
+            &[0xD3, 127],
+            &[0xE0, 0x00, 0x40],
+            &[0xE5, 0x7F, 0x7F],
+            &[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7],
+            &[0xF1, 0x73],
+            &[0xF2, 0x10, 0x02],
+            &[0xF3, 4],
+            &[0xF6],
+            &[0xF8],
+            &[0xFF],
+            &[0xF4, 0x10, 0x20],
+        ];
+        for bytes in cases {
+            let message = MidiMessage::from_raw_message(bytes).unwrap();
+            assert_eq!(message.to_bytes(), bytes, "{:?}", message);
+        }
+
+        // Note On with velocity 0 comes back as the Note Off it means
+        let message = MidiMessage::from_raw_message(&[0x90, 60, 0]).unwrap();
+        assert_eq!(message.to_bytes(), vec![0x80, 60, 0]);
+    }
+
+    #[test]
+    fn test_type_names_match_wire_tags() {
+        let cases: [&[u8]; 8] = [
+            &[0x90, 60, 100],
+            &[0xA1, 60, 90],
+            &[0xE0, 0x00, 0x40],
+            &[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7],
+            &[0xF2, 0x10, 0x02],
+            &[0xF8],
+            &[0xFF],
+            &[0xF4, 0x10, 0x20],
+        ];
+        for bytes in cases {
+            let message = MidiMessage::from_raw_message(bytes).unwrap();
+            let json = serde_json::to_value(&message).unwrap();
+            assert_eq!(json["type"], message.type_name());
+            assert!(MidiMessage::TYPES.contains(&message.type_name()));
+        }
+    }
+
+    #[test]
+    fn test_to_bytes_masks_out_of_range_fields() {
+        let message = MidiMessage::NoteOn {
+            channel: 17,
+            note: 200,
+            velocity: 100,
+        };
+        assert_eq!(message.to_bytes(), vec![0x91, 0x48, 100]);
+        let message = MidiMessage::PitchBend {
+            channel: 0,
+            value: i16::MAX,
+        };
+        assert_eq!(message.to_bytes(), vec![0xE0, 0x7F, 0x7F]);
+    }
+
+    #[test]
+    fn test_malformed_messages() {
+        let reason = |bytes: &[u8]| MidiMessage::parse(bytes).unwrap_err().reason;
+        assert_eq!(reason(&[]), MalformedReason::Truncated);
+        assert_eq!(reason(&[0x90, 60]), MalformedReason::Truncated);
+        assert_eq!(
+            reason(&[0xF0, 0x00, 0x20, 0xF7]),
+            MalformedReason::Truncated
+        );
+        assert_eq!(reason(&[0x90, 0x80, 100]), MalformedReason::DataByteHighBit);
+        assert_eq!(reason(&[0xB0, 7, 0xF8]), MalformedReason::DataByteHighBit);
+        assert_eq!(
+            reason(&[0xF0, 0x7E, 0x90, 0x01, 0xF7]),
+            MalformedReason::DataByteHighBit
+        );
+        assert_eq!(reason(&[0x3C, 100]), MalformedReason::UnexpectedStatus);
+        assert_eq!(reason(&[0xF7]), MalformedReason::UnexpectedStatus);
+
+        let malformed = MidiMessage::parse(&[0x90, 0x3C]).unwrap_err();
+        assert_eq!(malformed.data, vec![0x90, 0x3C]);
+        assert_eq!(malformed.to_string(), "Malformed (truncated): 90 3C");
+    }
+}
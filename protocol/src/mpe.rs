@@ -0,0 +1,262 @@
+//! MPE (MIDI Polyphonic Expression) zone layouts and per-note expression.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Controller MPE uses for the third dimension of expression ("slide").
+pub const MPE_SLIDE_CONTROLLER: u8 = 74;
+/// Pitch bend range of member channels after an MPE Configuration Message.
+pub const DEFAULT_MEMBER_BEND_RANGE: u8 = 48;
+/// Pitch bend range of the manager channel after an MPE Configuration Message.
+pub const DEFAULT_MANAGER_BEND_RANGE: u8 = 2;
+
+/// Which end of the channel range a zone occupies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MpeZoneSide {
+    /// Manager on channel 1, members counting up from channel 2.
+    Lower,
+    /// Manager on channel 16, members counting down from channel 15.
+    Upper,
+}
+
+impl MpeZoneSide {
+    /// Zero-based manager channel.
+    pub fn manager_channel(self) -> u8 {
+        match self {
+            MpeZoneSide::Lower => 0,
+            MpeZoneSide::Upper => 15,
+        }
+    }
+}
+
+/// One MPE zone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MpeZone {
+    /// Number of member channels, 1-15.
+    pub member_channels: u8,
+    /// Pitch bend range of the member channels, in semitones.
+    pub member_bend_range: u8,
+    /// Pitch bend range of the manager channel, in semitones.
+    pub manager_bend_range: u8,
+}
+
+impl MpeZone {
+    pub fn new(member_channels: u8) -> Self {
+        Self {
+            member_channels,
+            member_bend_range: DEFAULT_MEMBER_BEND_RANGE,
+            manager_bend_range: DEFAULT_MANAGER_BEND_RANGE,
+        }
+    }
+}
+
+/// What part a channel plays in an MPE layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MpeChannelRole {
+    Manager(MpeZoneSide),
+    Member(MpeZoneSide),
+}
+
+/// The Lower and Upper zones a controller has configured. Both are `None`
+/// when MPE is off.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct MpeLayout {
+    pub lower: Option<MpeZone>,
+    pub upper: Option<MpeZone>,
+}
+
+impl MpeLayout {
+    pub fn is_active(&self) -> bool {
+        self.lower.is_some() || self.upper.is_some()
+    }
+
+    pub fn zone(&self, side: MpeZoneSide) -> Option<&MpeZone> {
+        match side {
+            MpeZoneSide::Lower => self.lower.as_ref(),
+            MpeZoneSide::Upper => self.upper.as_ref(),
+        }
+    }
+
+    pub fn zone_mut(&mut self, side: MpeZoneSide) -> Option<&mut MpeZone> {
+        match side {
+            MpeZoneSide::Lower => self.lower.as_mut(),
+            MpeZoneSide::Upper => self.upper.as_mut(),
+        }
+    }
+
+    /// Applies an MPE Configuration Message: sets up the zone with the given
+    /// number of member channels (0 removes it), shrinking the other zone if
+    /// the two would overlap.
+    pub fn configure(&mut self, side: MpeZoneSide, member_channels: u8) {
+        let member_channels = member_channels.min(15);
+        let zone = (member_channels > 0).then(|| MpeZone::new(member_channels));
+        let other = match side {
+            MpeZoneSide::Lower => {
+                self.lower = zone;
+                &mut self.upper
+            }
+            MpeZoneSide::Upper => {
+                self.upper = zone;
+                &mut self.lower
+            }
+        };
+        // Together the zones have 14 channels left once both managers are taken
+        let available = 14u8.saturating_sub(member_channels);
+        if let Some(zone) = other {
+            if zone.member_channels > available {
+                *other = (available > 0).then(|| MpeZone::new(available));
+            }
+        }
+    }
+
+    /// Role of a zero-based channel, if it belongs to a zone.
+    pub fn role(&self, channel: u8) -> Option<MpeChannelRole> {
+        if let Some(lower) = &self.lower {
+            if channel == MpeZoneSide::Lower.manager_channel() {
+                return Some(MpeChannelRole::Manager(MpeZoneSide::Lower));
+            }
+            if (1..=lower.member_channels).contains(&channel) {
+                return Some(MpeChannelRole::Member(MpeZoneSide::Lower));
+            }
+        }
+        if let Some(upper) = &self.upper {
+            if channel == MpeZoneSide::Upper.manager_channel() {
+                return Some(MpeChannelRole::Manager(MpeZoneSide::Upper));
+            }
+            if (15 - upper.member_channels..15).contains(&channel) {
+                return Some(MpeChannelRole::Member(MpeZoneSide::Upper));
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for MpeLayout {
+    /// e.g. `MPE lower zone 7 members, upper zone 7 members`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.is_active() {
+            return write!(f, "MPE off");
+        }
+        let zones: Vec<String> = [("lower", &self.lower), ("upper", &self.upper)]
+            .into_iter()
+            .filter_map(|(name, zone)| {
+                zone.as_ref()
+                    .map(|zone| format!("{} zone {} members", name, zone.member_channels))
+            })
+            .collect();
+        write!(f, "MPE {}", zones.join(", "))
+    }
+}
+
+impl FromStr for MpeLayout {
+    type Err = String;
+
+    /// Parses a zone list such as `lower=15` or `lower=7,upper=7`; `off` or
+    /// an empty string means no zones.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut layout = MpeLayout::default();
+        let s = s.trim();
+        if s.is_empty() || s.eq_ignore_ascii_case("off") {
+            return Ok(layout);
+        }
+        for part in s.split(',') {
+            let (side, members) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected zone=members, got `{}`", part.trim()))?;
+            let side = match side.trim().to_ascii_lowercase().as_str() {
+                "lower" => MpeZoneSide::Lower,
+                "upper" => MpeZoneSide::Upper,
+                other => return Err(format!("unknown MPE zone `{}`", other)),
+            };
+            let members: u8 = members
+                .trim()
+                .parse()
+                .ok()
+                .filter(|members| *members <= 15)
+                .ok_or_else(|| format!("invalid member channel count `{}`", members.trim()))?;
+            layout.configure(side, members);
+        }
+        Ok(layout)
+    }
+}
+
+/// Expression of one sounding note on an MPE member channel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NoteExpression {
+    pub zone: MpeZoneSide,
+    /// Zero-based member channel the note is on.
+    pub channel: u8,
+    pub note: u8,
+    /// Note On velocity.
+    pub velocity: u8,
+    /// Pitch offset in semitones: the member channel's pitch bend plus the
+    /// manager channel's.
+    pub bend: f64,
+    /// CC74, 0-127.
+    pub slide: u8,
+    /// Channel pressure, 0-127.
+    pub pressure: u8,
+    /// `false` once the note has been released.
+    pub active: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zone_roles() {
+        let layout: MpeLayout = "lower=5,upper=3".parse().unwrap();
+        assert_eq!(
+            layout.role(0),
+            Some(MpeChannelRole::Manager(MpeZoneSide::Lower))
+        );
+        assert_eq!(
+            layout.role(5),
+            Some(MpeChannelRole::Member(MpeZoneSide::Lower))
+        );
+        assert_eq!(layout.role(6), None);
+        assert_eq!(
+            layout.role(12),
+            Some(MpeChannelRole::Member(MpeZoneSide::Upper))
+        );
+        assert_eq!(layout.role(11), None);
+        assert_eq!(
+            layout.role(15),
+            Some(MpeChannelRole::Manager(MpeZoneSide::Upper))
+        );
+        assert_eq!(
+            layout.to_string(),
+            "MPE lower zone 5 members, upper zone 3 members"
+        );
+    }
+
+    #[test]
+    fn test_configure_shrinks_overlapping_zone() {
+        let mut layout: MpeLayout = "upper=10".parse().unwrap();
+        layout.configure(MpeZoneSide::Lower, 6);
+        assert_eq!(layout.lower.as_ref().unwrap().member_channels, 6);
+        assert_eq!(layout.upper.as_ref().unwrap().member_channels, 8);
+
+        layout.configure(MpeZoneSide::Lower, 15);
+        assert_eq!(layout.upper, None);
+        assert_eq!(
+            layout.role(15),
+            Some(MpeChannelRole::Member(MpeZoneSide::Lower))
+        );
+
+        layout.configure(MpeZoneSide::Lower, 0);
+        assert!(!layout.is_active());
+        assert_eq!(layout.to_string(), "MPE off");
+    }
+
+    #[test]
+    fn test_parse_layout_errors() {
+        assert_eq!("off".parse::<MpeLayout>().unwrap(), MpeLayout::default());
+        assert!("middle=4".parse::<MpeLayout>().is_err());
+        assert!("lower=16".parse::<MpeLayout>().is_err());
+        assert!("lower".parse::<MpeLayout>().is_err());
+    }
+}
@@ -0,0 +1,39 @@
+//! Helpers for turning MIDI note numbers into human-readable names.
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Scientific pitch name for a MIDI note number, with middle C (60) as `C4`.
+///
+/// Note 0 is `C-1` and note 127 is `G9`.
+pub fn note_name(note: u8) -> String {
+    let octave = (note / 12) as i8 - 1;
+    format!("{}{}", NOTE_NAMES[(note % 12) as usize], octave)
+}
+
+/// Whether the note falls on a black key of a piano keyboard.
+pub fn is_black_key(note: u8) -> bool {
+    NOTE_NAMES[(note % 12) as usize].ends_with('#')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note_names() {
+        assert_eq!(note_name(60), "C4");
+        assert_eq!(note_name(61), "C#4");
+        assert_eq!(note_name(69), "A4");
+        assert_eq!(note_name(0), "C-1");
+        assert_eq!(note_name(11), "B-1");
+        assert_eq!(note_name(127), "G9");
+    }
+
+    #[test]
+    fn test_black_keys() {
+        let black: Vec<u8> = (60..72).filter(|&n| is_black_key(n)).collect();
+        assert_eq!(black, vec![61, 63, 66, 68, 70]);
+    }
+}
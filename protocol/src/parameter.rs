@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Which controller scheme a [`ParameterChange`] was assembled from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterType {
+    /// Registered Parameter Number (CC 101/100 + Data Entry).
+    Rpn,
+    /// Non-Registered Parameter Number (CC 99/98 + Data Entry).
+    Nrpn,
+    /// 14-bit controller: MSB on CC 0-31 paired with LSB on CC 32-63.
+    Controller,
+}
+
+/// RPN Null (127/127): deselects the current parameter.
+pub const RPN_NULL: u16 = 0x3FFF;
+/// RPN 0: Pitch Bend Sensitivity.
+pub const RPN_PITCH_BEND_SENSITIVITY: u16 = 0x0000;
+/// RPN 6: MPE Configuration Message.
+pub const RPN_MPE_CONFIGURATION: u16 = 0x0006;
+
+/// A parameter edit assembled from a sequence of Control Change messages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParameterChange {
+    pub channel: u8,
+    pub parameter_type: ParameterType,
+    /// 14-bit parameter number (MSB << 7 | LSB) for RPN/NRPN, or the MSB
+    /// controller number (0-31) for 14-bit controllers.
+    pub number: u16,
+    /// 14-bit value (MSB << 7 | LSB).
+    pub value: u16,
+}
+
+impl ParameterChange {
+    pub fn value_msb(&self) -> u8 {
+        (self.value >> 7) as u8
+    }
+
+    pub fn value_lsb(&self) -> u8 {
+        (self.value & 0x7F) as u8
+    }
+
+    /// Name of a registered parameter, if known.
+    pub fn name(&self) -> Option<&'static str> {
+        if self.parameter_type != ParameterType::Rpn {
+            return None;
+        }
+        let name = match self.number {
+            RPN_PITCH_BEND_SENSITIVITY => "Pitch Bend Sensitivity",
+            0x0001 => "Channel Fine Tuning",
+            0x0002 => "Channel Coarse Tuning",
+            0x0003 => "Tuning Program Change",
+            0x0004 => "Tuning Bank Select",
+            0x0005 => "Modulation Depth Range",
+            RPN_MPE_CONFIGURATION => "MPE Configuration",
+            _ => return None,
+        };
+        Some(name)
+    }
+
+    /// The value in the parameter's own units where the meaning is defined.
+    fn formatted_value(&self) -> String {
+        if self.parameter_type != ParameterType::Rpn {
+            return self.value.to_string();
+        }
+        match self.number {
+            RPN_PITCH_BEND_SENSITIVITY => match self.value_lsb() {
+                0 => format!("{} semitones", self.value_msb()),
+                cents => format!("{} semitones {} cents", self.value_msb(), cents),
+            },
+            0x0001 => format!(
+                "{:+.1} cents",
+                (self.value as f64 - 8192.0) * 100.0 / 8192.0
+            ),
+            0x0002 => format!("{:+} semitones", self.value_msb() as i16 - 64),
+            RPN_MPE_CONFIGURATION => format!("{} member channels", self.value_msb()),
+            _ => self.value.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParameterChange {
+    /// e.g. `RPN 0 Pitch Bend Sensitivity = 12 semitones`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.parameter_type {
+            ParameterType::Rpn => write!(f, "RPN {}", self.number)?,
+            ParameterType::Nrpn => write!(f, "NRPN {}", self.number)?,
+            ParameterType::Controller => write!(f, "CC {}/{}", self.number, self.number + 32)?,
+        }
+        if let Some(name) = self.name() {
+            write!(f, " {}", name)?;
+        }
+        write!(f, " = {}", self.formatted_value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rpn(number: u16, value: u16) -> ParameterChange {
+        ParameterChange {
+            channel: 0,
+            parameter_type: ParameterType::Rpn,
+            number,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            rpn(0, 12 << 7).to_string(),
+            "RPN 0 Pitch Bend Sensitivity = 12 semitones"
+        );
+        assert_eq!(
+            rpn(0, (2 << 7) | 50).to_string(),
+            "RPN 0 Pitch Bend Sensitivity = 2 semitones 50 cents"
+        );
+        assert_eq!(
+            rpn(1, 8192 + 4096).to_string(),
+            "RPN 1 Channel Fine Tuning = +50.0 cents"
+        );
+        assert_eq!(
+            rpn(2, 62 << 7).to_string(),
+            "RPN 2 Channel Coarse Tuning = -2 semitones"
+        );
+        assert_eq!(
+            rpn(6, 15 << 7).to_string(),
+            "RPN 6 MPE Configuration = 15 member channels"
+        );
+        assert_eq!(rpn(42, 100).to_string(), "RPN 42 = 100");
+
+        let nrpn = ParameterChange {
+            parameter_type: ParameterType::Nrpn,
+            ..rpn(1234, 8192)
+        };
+        assert_eq!(nrpn.to_string(), "NRPN 1234 = 8192");
+
+        let controller = ParameterChange {
+            parameter_type: ParameterType::Controller,
+            ..rpn(1, 16383)
+        };
+        assert_eq!(controller.to_string(), "CC 1/33 = 16383");
+    }
+}
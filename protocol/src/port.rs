@@ -0,0 +1,31 @@
+//! MIDI ports as reported by the backend.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PortDirection {
+    Input,
+    Output,
+}
+
+impl PortDirection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PortDirection::Input => "input",
+            PortDirection::Output => "output",
+        }
+    }
+}
+
+/// A MIDI input or output port the backend's host can see.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortInfo {
+    /// Identifier that stays the same across restarts and re-enumeration
+    /// as long as the port's name does, e.g. `input:Keystation 49 MIDI 1`.
+    pub id: String,
+    pub name: String,
+    pub direction: PortDirection,
+    /// Whether the backend currently has the port open.
+    pub connected: bool,
+}
@@ -0,0 +1,255 @@
+//! System Exclusive decoding: manufacturer IDs and Universal SysEx sub-IDs.
+
+use serde::{Deserialize, Serialize};
+
+/// Start of a System Exclusive message.
+pub const SYSEX_START: u8 = 0xF0;
+/// End of Exclusive.
+pub const SYSEX_END: u8 = 0xF7;
+
+const UNIVERSAL_NON_REAL_TIME: u8 = 0x7E;
+const UNIVERSAL_REAL_TIME: u8 = 0x7F;
+
+/// A complete System Exclusive message. Only `data` is needed to send one;
+/// the other fields describe it and default when left out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SysEx {
+    /// One byte, or three bytes starting with 0x00. `[0x7E]` and `[0x7F]`
+    /// mark Universal Non-Real-Time and Real-Time messages.
+    #[serde(default)]
+    pub manufacturer_id: Vec<u8>,
+    /// Name resolved from the built-in manufacturer table.
+    pub manufacturer: Option<String>,
+    /// Set for Universal SysEx messages.
+    pub universal: Option<UniversalSysEx>,
+    /// Number of bytes between the manufacturer ID and the closing 0xF7.
+    #[serde(default)]
+    pub payload_len: usize,
+    /// The whole message, including the 0xF0 and 0xF7 framing bytes.
+    pub data: Vec<u8>,
+}
+
+/// Header of a Universal SysEx message (manufacturer ID 0x7E or 0x7F).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UniversalSysEx {
+    /// `true` for Universal Real-Time (0x7F), `false` for Non-Real-Time (0x7E).
+    pub realtime: bool,
+    /// Target device, 0x7F meaning "all devices".
+    pub device_id: u8,
+    pub sub_id1: u8,
+    pub sub_id2: Option<u8>,
+    /// What the sub-IDs identify, e.g. "Identity Request".
+    pub description: Option<String>,
+}
+
+impl SysEx {
+    /// Decodes a SysEx message starting with 0xF0. The closing 0xF7 is
+    /// optional; returns `None` if the manufacturer ID is incomplete.
+    pub fn parse(message: &[u8]) -> Option<Self> {
+        if message.first() != Some(&SYSEX_START) {
+            return None;
+        }
+        let body = match message.last() {
+            Some(&SYSEX_END) if message.len() > 1 => &message[1..message.len() - 1],
+            _ => &message[1..],
+        };
+
+        let id_len = match body.first() {
+            None => return None,
+            Some(0x00) => 3,
+            Some(_) => 1,
+        };
+        if body.len() < id_len {
+            return None;
+        }
+        let manufacturer_id = body[..id_len].to_vec();
+        let payload = &body[id_len..];
+
+        let universal = match manufacturer_id[0] {
+            UNIVERSAL_NON_REAL_TIME | UNIVERSAL_REAL_TIME if payload.len() >= 2 => {
+                let realtime = manufacturer_id[0] == UNIVERSAL_REAL_TIME;
+                let sub_id1 = payload[1];
+                let sub_id2 = payload.get(2).copied();
+                Some(UniversalSysEx {
+                    realtime,
+                    device_id: payload[0],
+                    sub_id1,
+                    sub_id2,
+                    description: universal_description(realtime, sub_id1, sub_id2)
+                        .map(str::to_string),
+                })
+            }
+            _ => None,
+        };
+
+        Some(SysEx {
+            manufacturer: manufacturer_name(&manufacturer_id).map(str::to_string),
+            manufacturer_id,
+            universal,
+            payload_len: payload.len(),
+            data: message.to_vec(),
+        })
+    }
+
+    /// Bytes between the manufacturer ID and the closing 0xF7.
+    pub fn payload(&self) -> &[u8] {
+        let start = 1 + self.manufacturer_id.len();
+        &self.data[start..start + self.payload_len]
+    }
+}
+
+/// Looks up a 1- or 3-byte manufacturer ID in the built-in table.
+pub fn manufacturer_name(id: &[u8]) -> Option<&'static str> {
+    let name = match id {
+        [0x01] => "Sequential Circuits",
+        [0x04] => "Moog",
+        [0x06] => "Lexicon",
+        [0x07] => "Kurzweil",
+        [0x0F] => "Ensoniq",
+        [0x10] => "Oberheim",
+        [0x11] => "Apple",
+        [0x18] => "E-mu",
+        [0x1C] => "Eventide",
+        [0x33] => "Clavia",
+        [0x3E] => "Waldorf",
+        [0x40] => "Kawai",
+        [0x41] => "Roland",
+        [0x42] => "Korg",
+        [0x43] => "Yamaha",
+        [0x44] => "Casio",
+        [0x47] => "Akai",
+        [0x52] => "Zoom",
+        [0x7D] => "Non-Commercial",
+        [0x7E] => "Universal Non-Real-Time",
+        [0x7F] => "Universal Real-Time",
+        [0x00, 0x00, 0x0E] => "Alesis",
+        [0x00, 0x00, 0x3B] => "Mark of the Unicorn",
+        [0x00, 0x20, 0x29] => "Focusrite/Novation",
+        [0x00, 0x20, 0x32] => "Behringer",
+        [0x00, 0x20, 0x33] => "Access Music",
+        [0x00, 0x20, 0x3C] => "Elektron",
+        [0x00, 0x20, 0x6B] => "Arturia",
+        [0x00, 0x21, 0x09] => "Native Instruments",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Describes the sub-IDs of a Universal SysEx message.
+pub fn universal_description(
+    realtime: bool,
+    sub_id1: u8,
+    sub_id2: Option<u8>,
+) -> Option<&'static str> {
+    let description = if realtime {
+        match (sub_id1, sub_id2) {
+            (0x01, Some(0x01)) => "MTC Full Message",
+            (0x01, Some(0x02)) => "MTC User Bits",
+            (0x01, _) => "MIDI Time Code",
+            (0x02, _) => "MIDI Show Control",
+            (0x03, _) => "Notation Information",
+            (0x04, Some(0x01)) => "Master Volume",
+            (0x04, Some(0x02)) => "Master Balance",
+            (0x04, Some(0x03)) => "Master Fine Tuning",
+            (0x04, Some(0x04)) => "Master Coarse Tuning",
+            (0x04, _) => "Device Control",
+            (0x05, _) => "Real-Time MTC Cueing",
+            (0x06, _) => "MIDI Machine Control Command",
+            (0x07, _) => "MIDI Machine Control Response",
+            (0x08, _) => "MIDI Tuning Standard",
+            (0x09, _) => "Controller Destination Setting",
+            (0x0A, _) => "Key-Based Instrument Control",
+            (0x0B, _) => "Scalable Polyphony MIP",
+            (0x0C, _) => "Mobile Phone Control",
+            _ => return None,
+        }
+    } else {
+        match (sub_id1, sub_id2) {
+            (0x01, _) => "Sample Dump Header",
+            (0x02, _) => "Sample Data Packet",
+            (0x03, _) => "Sample Dump Request",
+            (0x04, _) => "MIDI Time Code Cueing",
+            (0x05, _) => "Sample Dump Extensions",
+            (0x06, Some(0x01)) => "Identity Request",
+            (0x06, Some(0x02)) => "Identity Reply",
+            (0x06, _) => "General Information",
+            (0x07, _) => "File Dump",
+            (0x08, _) => "MIDI Tuning Standard",
+            (0x09, Some(0x01)) => "General MIDI 1 On",
+            (0x09, Some(0x02)) => "General MIDI Off",
+            (0x09, Some(0x03)) => "General MIDI 2 On",
+            (0x09, _) => "General MIDI",
+            (0x0A, _) => "Downloadable Sounds",
+            (0x0B, _) => "File Reference",
+            (0x0C, _) => "MIDI Visual Control",
+            (0x0D, _) => "MIDI Capability Inquiry",
+            (0x7B, _) => "End of File",
+            (0x7C, _) => "Wait",
+            (0x7D, _) => "Cancel",
+            (0x7E, _) => "NAK",
+            (0x7F, _) => "ACK",
+            _ => return None,
+        }
+    };
+    Some(description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_byte_manufacturer() {
+        // Roland DT1: F0 41 10 42 12 40 00 7F 00 41 F7
+        let bytes = [
+            0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7,
+        ];
+        let sysex = SysEx::parse(&bytes).unwrap();
+        assert_eq!(sysex.manufacturer_id, vec![0x41]);
+        assert_eq!(sysex.manufacturer.as_deref(), Some("Roland"));
+        assert_eq!(sysex.payload_len, 8);
+        assert_eq!(sysex.payload(), &bytes[2..10]);
+        assert_eq!(sysex.data, bytes.to_vec());
+        assert!(sysex.universal.is_none());
+    }
+
+    #[test]
+    fn test_three_byte_manufacturer() {
+        let bytes = [0xF0, 0x00, 0x20, 0x29, 0x02, 0x0C, 0xF7];
+        let sysex = SysEx::parse(&bytes).unwrap();
+        assert_eq!(sysex.manufacturer_id, vec![0x00, 0x20, 0x29]);
+        assert_eq!(sysex.manufacturer.as_deref(), Some("Focusrite/Novation"));
+        assert_eq!(sysex.payload(), &[0x02, 0x0C]);
+
+        let unknown = SysEx::parse(&[0xF0, 0x00, 0x7F, 0x7F, 0x01, 0xF7]).unwrap();
+        assert_eq!(unknown.manufacturer, None);
+
+        // Incomplete IDs are rejected
+        assert!(SysEx::parse(&[0xF0, 0x00, 0x20, 0xF7]).is_none());
+        assert!(SysEx::parse(&[0xF0, 0xF7]).is_none());
+    }
+
+    #[test]
+    fn test_universal_sub_ids() {
+        let identity_request = SysEx::parse(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap();
+        let universal = identity_request.universal.unwrap();
+        assert!(!universal.realtime);
+        assert_eq!(universal.device_id, 0x7F);
+        assert_eq!(universal.sub_id1, 0x06);
+        assert_eq!(universal.sub_id2, Some(0x01));
+        assert_eq!(universal.description.as_deref(), Some("Identity Request"));
+
+        let master_volume =
+            SysEx::parse(&[0xF0, 0x7F, 0x7F, 0x04, 0x01, 0x00, 0x40, 0xF7]).unwrap();
+        let universal = master_volume.universal.unwrap();
+        assert!(universal.realtime);
+        assert_eq!(universal.description.as_deref(), Some("Master Volume"));
+    }
+
+    #[test]
+    fn test_missing_end_byte() {
+        let sysex = SysEx::parse(&[0xF0, 0x43, 0x10, 0x4C]).unwrap();
+        assert_eq!(sysex.manufacturer.as_deref(), Some("Yamaha"));
+        assert_eq!(sysex.payload(), &[0x10, 0x4C]);
+    }
+}
@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// SMPTE frame rate as encoded in MIDI Time Code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FrameRate {
+    #[serde(rename = "24")]
+    Fps24,
+    #[serde(rename = "25")]
+    Fps25,
+    #[serde(rename = "29.97df")]
+    Fps2997DropFrame,
+    #[default]
+    #[serde(rename = "30")]
+    Fps30,
+}
+
+impl FrameRate {
+    /// Decodes the two rate bits carried with the hours value.
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0x03 {
+            0 => FrameRate::Fps24,
+            1 => FrameRate::Fps25,
+            2 => FrameRate::Fps2997DropFrame,
+            _ => FrameRate::Fps30,
+        }
+    }
+
+    /// Frame numbers per second; 30 for 29.97 drop-frame.
+    pub fn nominal_fps(self) -> u32 {
+        match self {
+            FrameRate::Fps24 => 24,
+            FrameRate::Fps25 => 25,
+            FrameRate::Fps2997DropFrame | FrameRate::Fps30 => 30,
+        }
+    }
+
+    fn frames_per_day(self) -> i64 {
+        match self {
+            // 144 ten-minute blocks of 17982 frames
+            FrameRate::Fps2997DropFrame => 144 * 17_982,
+            _ => self.nominal_fps() as i64 * 86_400,
+        }
+    }
+}
+
+impl fmt::Display for FrameRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FrameRate::Fps24 => "24",
+            FrameRate::Fps25 => "25",
+            FrameRate::Fps2997DropFrame => "29.97df",
+            FrameRate::Fps30 => "30",
+        })
+    }
+}
+
+/// An SMPTE time of day: hours, minutes, seconds and frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Timecode {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+    pub rate: FrameRate,
+}
+
+impl Timecode {
+    /// Number of frames since midnight, accounting for dropped frame numbers.
+    pub fn to_frame_count(&self) -> i64 {
+        let fps = self.rate.nominal_fps() as i64;
+        let total_minutes = self.hours as i64 * 60 + self.minutes as i64;
+        let nominal = (total_minutes * 60 + self.seconds as i64) * fps + self.frames as i64;
+        match self.rate {
+            // Frame numbers 0 and 1 are skipped every minute except every tenth
+            FrameRate::Fps2997DropFrame => nominal - 2 * (total_minutes - total_minutes / 10),
+            _ => nominal,
+        }
+    }
+
+    /// Builds a timecode from a frame count, wrapping around midnight.
+    pub fn from_frame_count(count: i64, rate: FrameRate) -> Self {
+        let mut count = count.rem_euclid(rate.frames_per_day());
+        if rate == FrameRate::Fps2997DropFrame {
+            let blocks = count / 17_982;
+            let remainder = count % 17_982;
+            count += 18 * blocks;
+            if remainder >= 2 {
+                count += 2 * ((remainder - 2) / 1798);
+            }
+        }
+        let fps = rate.nominal_fps() as i64;
+        Timecode {
+            hours: (count / (fps * 3600) % 24) as u8,
+            minutes: (count / (fps * 60) % 60) as u8,
+            seconds: (count / fps % 60) as u8,
+            frames: (count % fps) as u8,
+            rate,
+        }
+    }
+
+    /// This timecode moved by `frames`, which may be negative.
+    pub fn offset(&self, frames: i64) -> Self {
+        Self::from_frame_count(self.to_frame_count() + frames, self.rate)
+    }
+}
+
+impl fmt::Display for Timecode {
+    /// `HH:MM:SS:FF`, with `;` before the frames for drop-frame.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if self.rate == FrameRate::Fps2997DropFrame {
+            ';'
+        } else {
+            ':'
+        };
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{}{:02}",
+            self.hours, self.minutes, self.seconds, separator, self.frames
+        )
+    }
+}
+
+/// Direction MIDI Time Code quarter frames are running in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimecodeDirection {
+    #[default]
+    Forward,
+    Reverse,
+}
+
+/// Timecode reconstructed by the backend from MTC quarter frames and Full
+/// Frame messages.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct TimecodeState {
+    pub timecode: Timecode,
+    pub direction: TimecodeDirection,
+    /// Whether quarter frames are currently arriving.
+    pub running: bool,
+    /// Quarter frames found missing or out of sequence since startup.
+    pub dropouts: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tc(hours: u8, minutes: u8, seconds: u8, frames: u8, rate: FrameRate) -> Timecode {
+        Timecode {
+            hours,
+            minutes,
+            seconds,
+            frames,
+            rate,
+        }
+    }
+
+    #[test]
+    fn test_non_drop_frame_arithmetic() {
+        let time = tc(0, 0, 59, 23, FrameRate::Fps24);
+        assert_eq!(time.offset(1), tc(0, 1, 0, 0, FrameRate::Fps24));
+        assert_eq!(time.offset(2).offset(-2), time);
+
+        // Wraps around midnight both ways
+        let midnight = tc(0, 0, 0, 0, FrameRate::Fps25);
+        assert_eq!(midnight.offset(-1), tc(23, 59, 59, 24, FrameRate::Fps25));
+        assert_eq!(midnight.offset(-1).offset(1), midnight);
+    }
+
+    #[test]
+    fn test_drop_frame_skips_frame_numbers() {
+        let df = FrameRate::Fps2997DropFrame;
+        // Frames 00 and 01 are skipped at the start of minute 1...
+        assert_eq!(tc(0, 0, 59, 29, df).offset(1), tc(0, 1, 0, 2, df));
+        // ...but not at minute 10
+        assert_eq!(tc(0, 9, 59, 29, df).offset(1), tc(0, 10, 0, 0, df));
+        assert_eq!(tc(0, 1, 0, 2, df).offset(-1), tc(0, 0, 59, 29, df));
+
+        // One hour of drop-frame is 107892 frames
+        assert_eq!(tc(1, 0, 0, 0, df).to_frame_count(), 107_892);
+        for count in [0, 1, 1799, 1800, 17_981, 17_982, 107_892, 2_589_407] {
+            assert_eq!(
+                Timecode::from_frame_count(count, df).to_frame_count(),
+                count
+            );
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(tc(1, 2, 3, 4, FrameRate::Fps25).to_string(), "01:02:03:04");
+        assert_eq!(
+            tc(10, 0, 0, 2, FrameRate::Fps2997DropFrame).to_string(),
+            "10:00:00;02"
+        );
+        assert_eq!(FrameRate::Fps2997DropFrame.to_string(), "29.97df");
+    }
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+/// Whether the sequencer driving the clock is playing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransportStatus {
+    #[default]
+    Stopped,
+    Playing,
+}
+
+/// Tempo and transport derived by the backend from incoming MIDI clock,
+/// Start/Stop/Continue and Song Position Pointer messages.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct TransportState {
+    pub status: TransportStatus,
+    /// Whether Timing Clock ticks are currently arriving.
+    pub clock: bool,
+    /// Smoothed tempo from the 24 PPQN clock, once enough ticks have arrived.
+    pub bpm: Option<f64>,
+    /// Average deviation of the tick interval from its mean, in milliseconds.
+    pub jitter_ms: Option<f64>,
+    /// Current position in MIDI beats (sixteenth notes).
+    pub song_position: u16,
+}
@@ -0,0 +1,1437 @@
+//! MIDI 2.0 Universal MIDI Packets (UMP) and the default translation between
+//! MIDI 1.0 messages and MIDI 2.0 channel voice messages.
+//!
+//! Packets are 32, 64, 96 or 128 bits long; the message type in the top
+//! nibble of the first word decides which. Byte streams (captures, files)
+//! hold each 32-bit word big-endian.
+
+use crate::message::MidiMessage;
+use crate::parameter::{ParameterChange, ParameterType};
+use crate::sysex::{SysEx, SYSEX_END, SYSEX_START};
+use serde::{Deserialize, Serialize};
+
+/// Utility messages (JR clock, timestamps, NOOP).
+pub const MT_UTILITY: u8 = 0x0;
+/// System Real-Time and System Common messages.
+pub const MT_SYSTEM: u8 = 0x1;
+/// MIDI 1.0 channel voice messages.
+pub const MT_MIDI1_CHANNEL_VOICE: u8 = 0x2;
+/// 7-bit System Exclusive data.
+pub const MT_DATA_64: u8 = 0x3;
+/// MIDI 2.0 channel voice messages.
+pub const MT_MIDI2_CHANNEL_VOICE: u8 = 0x4;
+/// 8-bit System Exclusive and Mixed Data Set.
+pub const MT_DATA_128: u8 = 0x5;
+/// Flex Data (tempo, time signature, lyrics, ...).
+pub const MT_FLEX_DATA: u8 = 0xD;
+/// UMP Stream messages (endpoint and function block discovery).
+pub const MT_STREAM: u8 = 0xF;
+
+/// Number of 32-bit words in a packet of the given message type.
+pub fn word_count(message_type: u8) -> usize {
+    match message_type & 0x0F {
+        0x0..=0x2 | 0x6 | 0x7 => 1,
+        0x3 | 0x4 | 0x8..=0xA => 2,
+        0xB | 0xC => 3,
+        _ => 4,
+    }
+}
+
+/// Where a System Exclusive packet sits in a message split across several
+/// packets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SysExPacketStatus {
+    /// The whole message fits in this packet.
+    Complete,
+    Start,
+    Continue,
+    End,
+}
+
+impl SysExPacketStatus {
+    fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            0x0 => Some(SysExPacketStatus::Complete),
+            0x1 => Some(SysExPacketStatus::Start),
+            0x2 => Some(SysExPacketStatus::Continue),
+            0x3 => Some(SysExPacketStatus::End),
+            _ => None,
+        }
+    }
+
+    fn bits(self) -> u8 {
+        match self {
+            SysExPacketStatus::Complete => 0x0,
+            SysExPacketStatus::Start => 0x1,
+            SysExPacketStatus::Continue => 0x2,
+            SysExPacketStatus::End => 0x3,
+        }
+    }
+}
+
+/// A MIDI 2.0 Protocol channel voice message (UMP message type 4).
+///
+/// Velocities are 16-bit and controller values 32-bit. Pitch bend values are
+/// unsigned with the center at `0x8000_0000`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Midi2ChannelVoice {
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u16,
+        attribute_type: u8,
+        attribute: u16,
+    },
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u16,
+        attribute_type: u8,
+        attribute: u16,
+    },
+    PolyPressure {
+        channel: u8,
+        note: u8,
+        pressure: u32,
+    },
+    RegisteredPerNoteController {
+        channel: u8,
+        note: u8,
+        index: u8,
+        value: u32,
+    },
+    AssignablePerNoteController {
+        channel: u8,
+        note: u8,
+        index: u8,
+        value: u32,
+    },
+    /// RPN, addressed directly by bank (MSB) and index (LSB).
+    RegisteredController {
+        channel: u8,
+        bank: u8,
+        index: u8,
+        value: u32,
+    },
+    /// NRPN, addressed directly by bank (MSB) and index (LSB).
+    AssignableController {
+        channel: u8,
+        bank: u8,
+        index: u8,
+        value: u32,
+    },
+    RelativeRegisteredController {
+        channel: u8,
+        bank: u8,
+        index: u8,
+        value: i32,
+    },
+    RelativeAssignableController {
+        channel: u8,
+        bank: u8,
+        index: u8,
+        value: i32,
+    },
+    PerNotePitchBend {
+        channel: u8,
+        note: u8,
+        value: u32,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u32,
+    },
+    /// `bank` is the 14-bit Bank Select (MSB << 7 | LSB), if one was sent
+    /// along with the program.
+    ProgramChange {
+        channel: u8,
+        program: u8,
+        bank: Option<u16>,
+    },
+    ChannelPressure {
+        channel: u8,
+        pressure: u32,
+    },
+    PitchBend {
+        channel: u8,
+        value: u32,
+    },
+    PerNoteManagement {
+        channel: u8,
+        note: u8,
+        detach: bool,
+        reset: bool,
+    },
+}
+
+/// One decoded Universal MIDI Packet.
+///
+/// Message types the monitor doesn't decode further (Flex Data, UMP Stream,
+/// Mixed Data Set) keep their words so nothing is lost.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Ump {
+    /// Message type 0: NOOP (0), JR Clock (1), JR Timestamp (2), Delta
+    /// Clockstamp Ticks Per Quarter Note (3) or Delta Clockstamp (4).
+    Utility {
+        status: u8,
+        /// The 20 bits following the status.
+        data: u32,
+    },
+    /// Message type 1: a System Common or System Real-Time message.
+    System { group: u8, message: MidiMessage },
+    /// Message type 2: a MIDI 1.0 channel voice message.
+    Midi1ChannelVoice { group: u8, message: MidiMessage },
+    /// Message type 3: up to six bytes of 7-bit System Exclusive, without
+    /// the 0xF0/0xF7 framing.
+    SysEx7 {
+        group: u8,
+        status: SysExPacketStatus,
+        data: Vec<u8>,
+    },
+    /// Message type 4: a MIDI 2.0 channel voice message.
+    Midi2ChannelVoice {
+        group: u8,
+        message: Midi2ChannelVoice,
+    },
+    /// Message type 5: up to thirteen bytes of 8-bit System Exclusive.
+    SysEx8 {
+        group: u8,
+        status: SysExPacketStatus,
+        stream_id: u8,
+        data: Vec<u8>,
+    },
+    /// Message type 5: a Mixed Data Set header (status 8) or payload (9).
+    MixedDataSet {
+        group: u8,
+        status: u8,
+        words: [u32; 4],
+    },
+    /// Message type D.
+    FlexData {
+        group: u8,
+        /// 0 complete, 1 start, 2 continue, 3 end.
+        form: u8,
+        /// 0 channel, 1 group.
+        address: u8,
+        channel: u8,
+        status_bank: u8,
+        status: u8,
+        data: [u32; 3],
+    },
+    /// Message type F. Stream messages have no group.
+    Stream {
+        /// 0 complete, 1 start, 2 continue, 3 end.
+        form: u8,
+        status: u16,
+        words: [u32; 4],
+    },
+    /// A reserved message type, or a status this decoder doesn't recognise.
+    Reserved { words: Vec<u32> },
+}
+
+impl Ump {
+    /// Decodes the packet at the start of `words`, returning it with the
+    /// number of words it took.
+    ///
+    /// Returns `None` if `words` is empty or the packet is truncated.
+    pub fn parse(words: &[u32]) -> Option<(Self, usize)> {
+        let first = *words.first()?;
+        let message_type = (first >> 28) as u8;
+        let len = word_count(message_type);
+        if words.len() < len {
+            return None;
+        }
+        let words = &words[..len];
+        let group = ((first >> 24) & 0x0F) as u8;
+        let status = ((first >> 20) & 0x0F) as u8;
+
+        let ump = match message_type {
+            MT_UTILITY => Some(Ump::Utility {
+                status,
+                data: first & 0x000F_FFFF,
+            }),
+            MT_SYSTEM | MT_MIDI1_CHANNEL_VOICE => {
+                let bytes = [(first >> 16) as u8, (first >> 8) as u8, first as u8];
+                let expected = if message_type == MT_SYSTEM {
+                    bytes[0] >= 0xF0 && bytes[0] != SYSEX_START && bytes[0] != SYSEX_END
+                } else {
+                    (0x80..0xF0).contains(&bytes[0])
+                };
+                let message = if expected {
+                    MidiMessage::from_raw_message(&bytes[..1 + MidiMessage::data_len(bytes[0])])
+                } else {
+                    None
+                };
+                message.map(|message| {
+                    if message_type == MT_SYSTEM {
+                        Ump::System { group, message }
+                    } else {
+                        Ump::Midi1ChannelVoice { group, message }
+                    }
+                })
+            }
+            MT_DATA_64 => SysExPacketStatus::from_bits(status).map(|status| {
+                let count = (((first >> 16) & 0x0F) as usize).min(6);
+                Ump::SysEx7 {
+                    group,
+                    status,
+                    data: words_to_bytes(words)[2..2 + count].to_vec(),
+                }
+            }),
+            MT_MIDI2_CHANNEL_VOICE => Midi2ChannelVoice::from_words(words[0], words[1])
+                .map(|message| Ump::Midi2ChannelVoice { group, message }),
+            MT_DATA_128 => match (SysExPacketStatus::from_bits(status), status) {
+                (Some(sysex_status), _) => {
+                    // The byte count includes the stream ID
+                    let count = (((first >> 16) & 0x0F) as usize).saturating_sub(1).min(13);
+                    Some(Ump::SysEx8 {
+                        group,
+                        status: sysex_status,
+                        stream_id: (first >> 8) as u8,
+                        data: words_to_bytes(words)[3..3 + count].to_vec(),
+                    })
+                }
+                (None, 0x8 | 0x9) => Some(Ump::MixedDataSet {
+                    group,
+                    status,
+                    words: [words[0], words[1], words[2], words[3]],
+                }),
+                _ => None,
+            },
+            MT_FLEX_DATA => Some(Ump::FlexData {
+                group,
+                form: ((first >> 22) & 0x03) as u8,
+                address: ((first >> 20) & 0x03) as u8,
+                channel: ((first >> 16) & 0x0F) as u8,
+                status_bank: (first >> 8) as u8,
+                status: first as u8,
+                data: [words[1], words[2], words[3]],
+            }),
+            MT_STREAM => Some(Ump::Stream {
+                form: ((first >> 26) & 0x03) as u8,
+                status: ((first >> 16) & 0x03FF) as u16,
+                words: [words[0], words[1], words[2], words[3]],
+            }),
+            _ => None,
+        };
+
+        let ump = ump.unwrap_or_else(|| Ump::Reserved {
+            words: words.to_vec(),
+        });
+        Some((ump, len))
+    }
+
+    /// Decodes a whole sequence of packets. Returns `None` if the last one
+    /// is truncated.
+    pub fn parse_all(mut words: &[u32]) -> Option<Vec<Self>> {
+        let mut packets = Vec::new();
+        while !words.is_empty() {
+            let (ump, len) = Self::parse(words)?;
+            packets.push(ump);
+            words = &words[len..];
+        }
+        Some(packets)
+    }
+
+    /// Decodes packets from big-endian bytes, as found in UMP captures.
+    /// Returns `None` unless the bytes hold a whole number of packets.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Vec<Self>> {
+        if bytes.len() % 4 != 0 {
+            return None;
+        }
+        let words: Vec<u32> = bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_be_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+        Self::parse_all(&words)
+    }
+
+    /// Encodes the packet back into its 32-bit words.
+    pub fn to_words(&self) -> Vec<u32> {
+        match self {
+            Ump::Utility { status, data } => {
+                vec![((*status as u32 & 0x0F) << 20) | (data & 0x000F_FFFF)]
+            }
+            Ump::System { group, message } | Ump::Midi1ChannelVoice { group, message } => {
+                let message_type = if matches!(self, Ump::System { .. }) {
+                    MT_SYSTEM
+                } else {
+                    MT_MIDI1_CHANNEL_VOICE
+                };
+                match message {
+                    // Neither fits in a single 32-bit packet
+                    MidiMessage::SysEx(_) | MidiMessage::Unknown { .. } => {
+                        vec![(message_type as u32) << 28 | (*group as u32 & 0x0F) << 24]
+                    }
+                    _ => {
+                        let mut word = [(message_type << 4) | (group & 0x0F), 0, 0, 0];
+                        let bytes = message.to_bytes();
+                        word[1..1 + bytes.len()].copy_from_slice(&bytes);
+                        vec![u32::from_be_bytes(word)]
+                    }
+                }
+            }
+            Ump::SysEx7 {
+                group,
+                status,
+                data,
+            } => {
+                let data = &data[..data.len().min(6)];
+                let mut bytes = vec![
+                    (MT_DATA_64 << 4) | (group & 0x0F),
+                    (status.bits() << 4) | data.len() as u8,
+                ];
+                bytes.extend_from_slice(data);
+                bytes_to_words(&bytes, 2)
+            }
+            Ump::Midi2ChannelVoice { group, message } => message.to_words(*group).to_vec(),
+            Ump::SysEx8 {
+                group,
+                status,
+                stream_id,
+                data,
+            } => {
+                let data = &data[..data.len().min(13)];
+                let mut bytes = vec![
+                    (MT_DATA_128 << 4) | (group & 0x0F),
+                    (status.bits() << 4) | (data.len() as u8 + 1),
+                    *stream_id,
+                ];
+                bytes.extend_from_slice(data);
+                bytes_to_words(&bytes, 4)
+            }
+            Ump::FlexData {
+                group,
+                form,
+                address,
+                channel,
+                status_bank,
+                status,
+                data,
+            } => {
+                let first = (MT_FLEX_DATA as u32) << 28
+                    | (*group as u32 & 0x0F) << 24
+                    | (*form as u32 & 0x03) << 22
+                    | (*address as u32 & 0x03) << 20
+                    | (*channel as u32 & 0x0F) << 16
+                    | (*status_bank as u32) << 8
+                    | *status as u32;
+                vec![first, data[0], data[1], data[2]]
+            }
+            Ump::MixedDataSet { words, .. } | Ump::Stream { words, .. } => words.to_vec(),
+            Ump::Reserved { words } => words.clone(),
+        }
+    }
+
+    /// Encodes the packet as big-endian bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        words_to_bytes(&self.to_words())
+    }
+
+    /// The message type nibble (0x0-0xF).
+    pub fn message_type(&self) -> u8 {
+        match self {
+            Ump::Utility { .. } => MT_UTILITY,
+            Ump::System { .. } => MT_SYSTEM,
+            Ump::Midi1ChannelVoice { .. } => MT_MIDI1_CHANNEL_VOICE,
+            Ump::SysEx7 { .. } => MT_DATA_64,
+            Ump::Midi2ChannelVoice { .. } => MT_MIDI2_CHANNEL_VOICE,
+            Ump::SysEx8 { .. } | Ump::MixedDataSet { .. } => MT_DATA_128,
+            Ump::FlexData { .. } => MT_FLEX_DATA,
+            Ump::Stream { .. } => MT_STREAM,
+            Ump::Reserved { words } => (words.first().copied().unwrap_or(0) >> 28) as u8,
+        }
+    }
+
+    /// Group (0-15) the packet is addressed to. Utility and Stream messages
+    /// are groupless.
+    pub fn group(&self) -> Option<u8> {
+        match self {
+            Ump::Utility { .. } | Ump::Stream { .. } => None,
+            Ump::System { group, .. }
+            | Ump::Midi1ChannelVoice { group, .. }
+            | Ump::SysEx7 { group, .. }
+            | Ump::Midi2ChannelVoice { group, .. }
+            | Ump::SysEx8 { group, .. }
+            | Ump::MixedDataSet { group, .. }
+            | Ump::FlexData { group, .. } => Some(*group),
+            Ump::Reserved { words } => words.first().map(|word| ((word >> 24) & 0x0F) as u8),
+        }
+    }
+
+    /// Packs a MIDI 1.0 message into MIDI 1.0 Protocol packets: channel
+    /// voice as message type 2, system messages as type 1 and SysEx split
+    /// across as many type 3 packets as it needs.
+    ///
+    /// Returns no packets for [`MidiMessage::Unknown`].
+    pub fn from_midi1(group: u8, message: &MidiMessage) -> Vec<Self> {
+        let group = group & 0x0F;
+        match message {
+            MidiMessage::SysEx(sysex) => sysex7_packets(group, &sysex.data),
+            MidiMessage::Unknown { .. } => Vec::new(),
+            message if message.channel().is_some() => vec![Ump::Midi1ChannelVoice {
+                group,
+                message: message.clone(),
+            }],
+            message => vec![Ump::System {
+                group,
+                message: message.clone(),
+            }],
+        }
+    }
+
+    /// Like [`Ump::from_midi1`], but for MIDI 2.0 Protocol: channel voice
+    /// messages go through the default translation into message type 4.
+    pub fn from_midi1_translated(group: u8, message: &MidiMessage) -> Vec<Self> {
+        match Midi2ChannelVoice::from_midi1(message) {
+            Some(message) => vec![Ump::Midi2ChannelVoice {
+                group: group & 0x0F,
+                message,
+            }],
+            None => Self::from_midi1(group, message),
+        }
+    }
+
+    /// The MIDI 1.0 messages this packet stands for.
+    ///
+    /// MIDI 2.0 channel voice messages go through the default translation.
+    /// Only complete SysEx7 packets become a [`MidiMessage::SysEx`]; split
+    /// ones need reassembling first. Everything else has no MIDI 1.0
+    /// equivalent and yields nothing.
+    pub fn to_midi1(&self) -> Vec<MidiMessage> {
+        match self {
+            Ump::System { message, .. } | Ump::Midi1ChannelVoice { message, .. } => {
+                vec![message.clone()]
+            }
+            Ump::SysEx7 {
+                status: SysExPacketStatus::Complete,
+                data,
+                ..
+            } => {
+                let mut bytes = vec![SYSEX_START];
+                bytes.extend_from_slice(data);
+                bytes.push(SYSEX_END);
+                SysEx::parse(&bytes)
+                    .map(MidiMessage::SysEx)
+                    .into_iter()
+                    .collect()
+            }
+            Ump::Midi2ChannelVoice { message, .. } => message.to_midi1(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Midi2ChannelVoice {
+    /// Zero-based channel within the packet's group.
+    pub fn channel(&self) -> u8 {
+        match self {
+            Midi2ChannelVoice::NoteOff { channel, .. }
+            | Midi2ChannelVoice::NoteOn { channel, .. }
+            | Midi2ChannelVoice::PolyPressure { channel, .. }
+            | Midi2ChannelVoice::RegisteredPerNoteController { channel, .. }
+            | Midi2ChannelVoice::AssignablePerNoteController { channel, .. }
+            | Midi2ChannelVoice::RegisteredController { channel, .. }
+            | Midi2ChannelVoice::AssignableController { channel, .. }
+            | Midi2ChannelVoice::RelativeRegisteredController { channel, .. }
+            | Midi2ChannelVoice::RelativeAssignableController { channel, .. }
+            | Midi2ChannelVoice::PerNotePitchBend { channel, .. }
+            | Midi2ChannelVoice::ControlChange { channel, .. }
+            | Midi2ChannelVoice::ProgramChange { channel, .. }
+            | Midi2ChannelVoice::ChannelPressure { channel, .. }
+            | Midi2ChannelVoice::PitchBend { channel, .. }
+            | Midi2ChannelVoice::PerNoteManagement { channel, .. } => *channel,
+        }
+    }
+
+    fn from_words(first: u32, second: u32) -> Option<Self> {
+        let channel = ((first >> 16) & 0x0F) as u8;
+        let byte3 = (first >> 8) as u8;
+        let byte4 = first as u8;
+        let note = byte3 & 0x7F;
+        let message = match (first >> 20) & 0x0F {
+            0x0 => Midi2ChannelVoice::RegisteredPerNoteController {
+                channel,
+                note,
+                index: byte4,
+                value: second,
+            },
+            0x1 => Midi2ChannelVoice::AssignablePerNoteController {
+                channel,
+                note,
+                index: byte4,
+                value: second,
+            },
+            0x2 => Midi2ChannelVoice::RegisteredController {
+                channel,
+                bank: byte3 & 0x7F,
+                index: byte4 & 0x7F,
+                value: second,
+            },
+            0x3 => Midi2ChannelVoice::AssignableController {
+                channel,
+                bank: byte3 & 0x7F,
+                index: byte4 & 0x7F,
+                value: second,
+            },
+            0x4 => Midi2ChannelVoice::RelativeRegisteredController {
+                channel,
+                bank: byte3 & 0x7F,
+                index: byte4 & 0x7F,
+                value: second as i32,
+            },
+            0x5 => Midi2ChannelVoice::RelativeAssignableController {
+                channel,
+                bank: byte3 & 0x7F,
+                index: byte4 & 0x7F,
+                value: second as i32,
+            },
+            0x6 => Midi2ChannelVoice::PerNotePitchBend {
+                channel,
+                note,
+                value: second,
+            },
+            0x8 => Midi2ChannelVoice::NoteOff {
+                channel,
+                note,
+                velocity: (second >> 16) as u16,
+                attribute_type: byte4,
+                attribute: second as u16,
+            },
+            0x9 => Midi2ChannelVoice::NoteOn {
+                channel,
+                note,
+                velocity: (second >> 16) as u16,
+                attribute_type: byte4,
+                attribute: second as u16,
+            },
+            0xA => Midi2ChannelVoice::PolyPressure {
+                channel,
+                note,
+                pressure: second,
+            },
+            0xB => Midi2ChannelVoice::ControlChange {
+                channel,
+                controller: byte3 & 0x7F,
+                value: second,
+            },
+            0xC => Midi2ChannelVoice::ProgramChange {
+                channel,
+                program: ((second >> 24) & 0x7F) as u8,
+                // Option flag bit 0: Bank Valid
+                bank: (byte4 & 0x01 != 0)
+                    .then_some((((second >> 8) & 0x7F) << 7 | (second & 0x7F)) as u16),
+            },
+            0xD => Midi2ChannelVoice::ChannelPressure {
+                channel,
+                pressure: second,
+            },
+            0xE => Midi2ChannelVoice::PitchBend {
+                channel,
+                value: second,
+            },
+            0xF => Midi2ChannelVoice::PerNoteManagement {
+                channel,
+                note,
+                detach: byte4 & 0x02 != 0,
+                reset: byte4 & 0x01 != 0,
+            },
+            _ => return None,
+        };
+        Some(message)
+    }
+
+    fn to_words(&self, group: u8) -> [u32; 2] {
+        let (status, byte3, byte4, second) = match *self {
+            Midi2ChannelVoice::RegisteredPerNoteController {
+                note, index, value, ..
+            } => (0x0, note, index, value),
+            Midi2ChannelVoice::AssignablePerNoteController {
+                note, index, value, ..
+            } => (0x1, note, index, value),
+            Midi2ChannelVoice::RegisteredController {
+                bank, index, value, ..
+            } => (0x2, bank, index, value),
+            Midi2ChannelVoice::AssignableController {
+                bank, index, value, ..
+            } => (0x3, bank, index, value),
+            Midi2ChannelVoice::RelativeRegisteredController {
+                bank, index, value, ..
+            } => (0x4, bank, index, value as u32),
+            Midi2ChannelVoice::RelativeAssignableController {
+                bank, index, value, ..
+            } => (0x5, bank, index, value as u32),
+            Midi2ChannelVoice::PerNotePitchBend { note, value, .. } => (0x6, note, 0, value),
+            Midi2ChannelVoice::NoteOff {
+                note,
+                velocity,
+                attribute_type,
+                attribute,
+                ..
+            } => (
+                0x8,
+                note,
+                attribute_type,
+                (velocity as u32) << 16 | attribute as u32,
+            ),
+            Midi2ChannelVoice::NoteOn {
+                note,
+                velocity,
+                attribute_type,
+                attribute,
+                ..
+            } => (
+                0x9,
+                note,
+                attribute_type,
+                (velocity as u32) << 16 | attribute as u32,
+            ),
+            Midi2ChannelVoice::PolyPressure { note, pressure, .. } => (0xA, note, 0, pressure),
+            Midi2ChannelVoice::ControlChange {
+                controller, value, ..
+            } => (0xB, controller, 0, value),
+            Midi2ChannelVoice::ProgramChange { program, bank, .. } => {
+                let bank_bits = bank.map_or(0, |bank| {
+                    ((bank as u32 >> 7) & 0x7F) << 8 | (bank as u32 & 0x7F)
+                });
+                (
+                    0xC,
+                    0,
+                    bank.is_some() as u8,
+                    (program as u32 & 0x7F) << 24 | bank_bits,
+                )
+            }
+            Midi2ChannelVoice::ChannelPressure { pressure, .. } => (0xD, 0, 0, pressure),
+            Midi2ChannelVoice::PitchBend { value, .. } => (0xE, 0, 0, value),
+            Midi2ChannelVoice::PerNoteManagement {
+                note,
+                detach,
+                reset,
+                ..
+            } => (0xF, note, (detach as u8) << 1 | reset as u8, 0),
+        };
+        let first = u32::from_be_bytes([
+            (MT_MIDI2_CHANNEL_VOICE << 4) | (group & 0x0F),
+            (status << 4) | (self.channel() & 0x0F),
+            byte3,
+            byte4,
+        ]);
+        [first, second]
+    }
+
+    /// Default MIDI 1.0 to MIDI 2.0 translation of a single channel voice
+    /// message, upscaling values with the spec's min-center-max scaling.
+    ///
+    /// Control Changes are translated one by one; use
+    /// [`Midi2ChannelVoice::from_parameter`] for RPN/NRPN edits already
+    /// assembled from them. Returns `None` for non-channel messages.
+    pub fn from_midi1(message: &MidiMessage) -> Option<Self> {
+        let message = match *message {
+            // A MIDI 1.0 Note On with velocity 0 is a Note Off with the
+            // default release velocity
+            MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity: 0,
+            } => Midi2ChannelVoice::NoteOff {
+                channel,
+                note,
+                velocity: scale_up(64, 7, 16) as u16,
+                attribute_type: 0,
+                attribute: 0,
+            },
+            MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => Midi2ChannelVoice::NoteOn {
+                channel,
+                note,
+                velocity: scale_up(velocity as u32, 7, 16) as u16,
+                attribute_type: 0,
+                attribute: 0,
+            },
+            MidiMessage::NoteOff {
+                channel,
+                note,
+                velocity,
+            } => Midi2ChannelVoice::NoteOff {
+                channel,
+                note,
+                velocity: scale_up(velocity as u32, 7, 16) as u16,
+                attribute_type: 0,
+                attribute: 0,
+            },
+            MidiMessage::PolyPressure {
+                channel,
+                note,
+                pressure,
+            } => Midi2ChannelVoice::PolyPressure {
+                channel,
+                note,
+                pressure: scale_up(pressure as u32, 7, 32),
+            },
+            MidiMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            } => Midi2ChannelVoice::ControlChange {
+                channel,
+                controller,
+                value: scale_up(value as u32, 7, 32),
+            },
+            MidiMessage::ProgramChange { channel, program } => Midi2ChannelVoice::ProgramChange {
+                channel,
+                program,
+                bank: None,
+            },
+            MidiMessage::ChannelPressure { channel, pressure } => {
+                Midi2ChannelVoice::ChannelPressure {
+                    channel,
+                    pressure: scale_up(pressure as u32, 7, 32),
+                }
+            }
+            MidiMessage::PitchBend { channel, value } => Midi2ChannelVoice::PitchBend {
+                channel,
+                value: scale_up((value as i32 + 8192).clamp(0, 0x3FFF) as u32, 14, 32),
+            },
+            _ => return None,
+        };
+        Some(message)
+    }
+
+    /// Translates an RPN, NRPN or 14-bit controller edit into the single
+    /// MIDI 2.0 message that replaces its Control Change sequence.
+    pub fn from_parameter(change: &ParameterChange) -> Self {
+        let channel = change.channel;
+        let bank = ((change.number >> 7) & 0x7F) as u8;
+        let index = (change.number & 0x7F) as u8;
+        let value = scale_up(change.value as u32, 14, 32);
+        match change.parameter_type {
+            ParameterType::Rpn => Midi2ChannelVoice::RegisteredController {
+                channel,
+                bank,
+                index,
+                value,
+            },
+            ParameterType::Nrpn => Midi2ChannelVoice::AssignableController {
+                channel,
+                bank,
+                index,
+                value,
+            },
+            ParameterType::Controller => Midi2ChannelVoice::ControlChange {
+                channel,
+                controller: change.number as u8,
+                value,
+            },
+        }
+    }
+
+    /// Default MIDI 2.0 to MIDI 1.0 translation.
+    ///
+    /// Registered and assignable controllers become their RPN/NRPN Control
+    /// Change sequence, and a Program Change with a bank is preceded by Bank
+    /// Select. Messages with no MIDI 1.0 equivalent (per-note and relative
+    /// controllers, per-note pitch bend and management) yield nothing.
+    pub fn to_midi1(&self) -> Vec<MidiMessage> {
+        let cc = |channel, controller, value| MidiMessage::ControlChange {
+            channel,
+            controller,
+            value,
+        };
+        match *self {
+            Midi2ChannelVoice::NoteOff {
+                channel,
+                note,
+                velocity,
+                ..
+            } => vec![MidiMessage::NoteOff {
+                channel,
+                note,
+                velocity: scale_down(velocity as u32, 16, 7) as u8,
+            }],
+            // Velocity 0 would turn a MIDI 1.0 Note On into a Note Off
+            Midi2ChannelVoice::NoteOn {
+                channel,
+                note,
+                velocity,
+                ..
+            } => vec![MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity: (scale_down(velocity as u32, 16, 7) as u8).max(1),
+            }],
+            Midi2ChannelVoice::PolyPressure {
+                channel,
+                note,
+                pressure,
+            } => vec![MidiMessage::PolyPressure {
+                channel,
+                note,
+                pressure: scale_down(pressure, 32, 7) as u8,
+            }],
+            Midi2ChannelVoice::RegisteredController {
+                channel,
+                bank,
+                index,
+                value,
+            } => data_entry(channel, (101, 100), bank, index, value),
+            Midi2ChannelVoice::AssignableController {
+                channel,
+                bank,
+                index,
+                value,
+            } => data_entry(channel, (99, 98), bank, index, value),
+            Midi2ChannelVoice::ControlChange {
+                channel,
+                controller,
+                value,
+            } => vec![cc(channel, controller, scale_down(value, 32, 7) as u8)],
+            Midi2ChannelVoice::ProgramChange {
+                channel,
+                program,
+                bank,
+            } => {
+                let mut messages = Vec::new();
+                if let Some(bank) = bank {
+                    messages.push(cc(channel, 0, ((bank >> 7) & 0x7F) as u8));
+                    messages.push(cc(channel, 32, (bank & 0x7F) as u8));
+                }
+                messages.push(MidiMessage::ProgramChange { channel, program });
+                messages
+            }
+            Midi2ChannelVoice::ChannelPressure { channel, pressure } => {
+                vec![MidiMessage::ChannelPressure {
+                    channel,
+                    pressure: scale_down(pressure, 32, 7) as u8,
+                }]
+            }
+            Midi2ChannelVoice::PitchBend { channel, value } => vec![MidiMessage::PitchBend {
+                channel,
+                value: scale_down(value, 32, 14) as i16 - 8192,
+            }],
+            Midi2ChannelVoice::RegisteredPerNoteController { .. }
+            | Midi2ChannelVoice::AssignablePerNoteController { .. }
+            | Midi2ChannelVoice::RelativeRegisteredController { .. }
+            | Midi2ChannelVoice::RelativeAssignableController { .. }
+            | Midi2ChannelVoice::PerNotePitchBend { .. }
+            | Midi2ChannelVoice::PerNoteManagement { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Upscales a value with the MIDI 2.0 min-center-max algorithm: 0 stays 0,
+/// the center maps to the center and the maximum maps to the maximum.
+pub fn scale_up(value: u32, src_bits: u32, dst_bits: u32) -> u32 {
+    let scale_bits = dst_bits - src_bits;
+    let center = 1 << (src_bits - 1);
+    if value <= center {
+        return value << scale_bits;
+    }
+    // Above the center, fill the new low bits by repeating the bits below
+    // the source's top bit
+    let repeat_bits = src_bits - 1;
+    let mut repeat = value & ((1 << repeat_bits) - 1);
+    if scale_bits > repeat_bits {
+        repeat <<= scale_bits - repeat_bits;
+    } else {
+        repeat >>= repeat_bits - scale_bits;
+    }
+    let mut scaled = value << scale_bits;
+    while repeat != 0 {
+        scaled |= repeat;
+        repeat >>= repeat_bits;
+    }
+    scaled
+}
+
+/// Downscales a value by dropping its low bits.
+pub fn scale_down(value: u32, src_bits: u32, dst_bits: u32) -> u32 {
+    value >> (src_bits - dst_bits)
+}
+
+/// RPN/NRPN select followed by Data Entry MSB and LSB.
+fn data_entry(
+    channel: u8,
+    (msb_controller, lsb_controller): (u8, u8),
+    bank: u8,
+    index: u8,
+    value: u32,
+) -> Vec<MidiMessage> {
+    let value = scale_down(value, 32, 14);
+    [
+        (msb_controller, bank & 0x7F),
+        (lsb_controller, index & 0x7F),
+        (6, (value >> 7) as u8),
+        (38, (value & 0x7F) as u8),
+    ]
+    .into_iter()
+    .map(|(controller, value)| MidiMessage::ControlChange {
+        channel,
+        controller,
+        value,
+    })
+    .collect()
+}
+
+/// Splits a framed SysEx message into type 3 packets of up to six bytes.
+fn sysex7_packets(group: u8, data: &[u8]) -> Vec<Ump> {
+    let data = data.strip_prefix(&[SYSEX_START]).unwrap_or(data);
+    let data = data.strip_suffix(&[SYSEX_END]).unwrap_or(data);
+    if data.is_empty() {
+        return vec![Ump::SysEx7 {
+            group,
+            status: SysExPacketStatus::Complete,
+            data: Vec::new(),
+        }];
+    }
+    let chunks: Vec<&[u8]> = data.chunks(6).collect();
+    let last = chunks.len() - 1;
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| Ump::SysEx7 {
+            group,
+            status: match (i, last) {
+                (0, 0) => SysExPacketStatus::Complete,
+                (0, _) => SysExPacketStatus::Start,
+                (i, last) if i == last => SysExPacketStatus::End,
+                _ => SysExPacketStatus::Continue,
+            },
+            data: chunk.to_vec(),
+        })
+        .collect()
+}
+
+fn words_to_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_be_bytes()).collect()
+}
+
+/// Packs bytes into `len` words, zero-padding the remainder.
+fn bytes_to_words(bytes: &[u8], len: usize) -> Vec<u32> {
+    let mut padded = bytes.to_vec();
+    padded.resize(len * 4, 0);
+    padded
+        .chunks_exact(4)
+        .map(|word| u32::from_be_bytes([word[0], word[1], word[2], word[3]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_count() {
+        assert_eq!(word_count(MT_UTILITY), 1);
+        assert_eq!(word_count(MT_MIDI1_CHANNEL_VOICE), 1);
+        assert_eq!(word_count(MT_DATA_64), 2);
+        assert_eq!(word_count(MT_MIDI2_CHANNEL_VOICE), 2);
+        assert_eq!(word_count(0xB), 3);
+        assert_eq!(word_count(MT_DATA_128), 4);
+        assert_eq!(word_count(MT_STREAM), 4);
+    }
+
+    #[test]
+    fn test_parse_capture_fixture() {
+        // NOOP, MIDI 1.0 Note On on group 1, MIDI 2.0 Note On, Start,
+        // a Flex Data tempo and a Stream Endpoint Discovery
+        let bytes = [
+            0x00, 0x00, 0x00, 0x00, //
+            0x21, 0x90, 0x3C, 0x64, //
+            0x40, 0x93, 0x3C, 0x00, 0xFF, 0xFF, 0x00, 0x00, //
+            0x10, 0xFA, 0x00, 0x00, //
+            0xD0, 0x10, 0x00, 0x00, 0x02, 0xFA, 0xF0, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, //
+            0xF0, 0x00, 0x01, 0x01, 0x00, 0x00, 0x00, 0x1F, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let packets = Ump::from_bytes(&bytes).unwrap();
+        assert_eq!(packets.len(), 6);
+
+        assert_eq!(packets[0], Ump::Utility { status: 0, data: 0 });
+        assert_eq!(
+            packets[1],
+            Ump::Midi1ChannelVoice {
+                group: 1,
+                message: MidiMessage::NoteOn {
+                    channel: 0,
+                    note: 60,
+                    velocity: 100
+                }
+            }
+        );
+        assert_eq!(
+            packets[2],
+            Ump::Midi2ChannelVoice {
+                group: 0,
+                message: Midi2ChannelVoice::NoteOn {
+                    channel: 3,
+                    note: 60,
+                    velocity: 0xFFFF,
+                    attribute_type: 0,
+                    attribute: 0
+                }
+            }
+        );
+        assert_eq!(
+            packets[3],
+            Ump::System {
+                group: 0,
+                message: MidiMessage::Start
+            }
+        );
+        assert!(matches!(
+            packets[4],
+            Ump::FlexData {
+                group: 0,
+                address: 1,
+                status_bank: 0,
+                status: 0,
+                data: [0x02FA_F080, 0, 0],
+                ..
+            }
+        ));
+        assert!(matches!(
+            packets[5],
+            Ump::Stream {
+                form: 0,
+                status: 0,
+                ..
+            }
+        ));
+        assert_eq!(packets[5].group(), None);
+
+        let encoded: Vec<u8> = packets.iter().flat_map(Ump::to_bytes).collect();
+        assert_eq!(encoded, bytes);
+    }
+
+    #[test]
+    fn test_truncated_and_reserved() {
+        assert!(Ump::parse(&[]).is_none());
+        assert!(Ump::parse(&[0x4090_3C00]).is_none());
+        assert!(Ump::from_bytes(&[0x20, 0x90, 0x3C]).is_none());
+
+        // Message type 6 is reserved, 32 bits
+        let (ump, len) = Ump::parse(&[0x6123_4567, 0x2090_3C64]).unwrap();
+        assert_eq!(len, 1);
+        assert_eq!(
+            ump,
+            Ump::Reserved {
+                words: vec![0x6123_4567]
+            }
+        );
+        assert_eq!(ump.message_type(), 6);
+
+        // A channel voice status in a system packet is not valid
+        let (ump, _) = Ump::parse(&[0x1090_3C64]).unwrap();
+        assert!(matches!(ump, Ump::Reserved { .. }));
+    }
+
+    #[test]
+    fn test_sysex7_split_and_reassemble() {
+        let data: Vec<u8> = [SYSEX_START]
+            .into_iter()
+            .chain(0x01..=0x0E)
+            .chain([SYSEX_END])
+            .collect();
+        let message = MidiMessage::SysEx(SysEx::parse(&data).unwrap());
+        let packets = Ump::from_midi1(2, &message);
+        let statuses: Vec<_> = packets
+            .iter()
+            .map(|ump| match ump {
+                Ump::SysEx7 { status, data, .. } => (*status, data.len()),
+                other => panic!("expected SysEx7, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (SysExPacketStatus::Start, 6),
+                (SysExPacketStatus::Continue, 6),
+                (SysExPacketStatus::End, 2)
+            ]
+        );
+
+        let words: Vec<u32> = packets.iter().flat_map(Ump::to_words).collect();
+        assert_eq!(words[0], 0x3216_0102);
+        assert_eq!(words[1], 0x0304_0506);
+        assert_eq!(Ump::parse_all(&words).unwrap(), packets);
+
+        let complete = Ump::from_midi1(
+            0,
+            &MidiMessage::from_raw_message(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap(),
+        );
+        assert_eq!(complete.len(), 1);
+        assert_eq!(
+            complete[0].to_midi1(),
+            vec![MidiMessage::from_raw_message(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_sysex8_round_trip() {
+        let ump = Ump::SysEx8 {
+            group: 4,
+            status: SysExPacketStatus::Complete,
+            stream_id: 9,
+            data: vec![0xFF, 0x00, 0x80],
+        };
+        let words = ump.to_words();
+        assert_eq!(words, vec![0x5404_09FF, 0x0080_0000, 0, 0]);
+        assert_eq!(Ump::parse(&words).unwrap(), (ump, 4));
+    }
+
+    #[test]
+    fn test_midi2_channel_voice_round_trip() {
+        let messages = [
+            Midi2ChannelVoice::NoteOff {
+                channel: 1,
+                note: 64,
+                velocity: 0x1234,
+                attribute_type: 3,
+                attribute: 0x0200,
+            },
+            Midi2ChannelVoice::RegisteredPerNoteController {
+                channel: 2,
+                note: 60,
+                index: 0x80,
+                value: 0xDEAD_BEEF,
+            },
+            Midi2ChannelVoice::RelativeAssignableController {
+                channel: 3,
+                bank: 1,
+                index: 2,
+                value: -5,
+            },
+            Midi2ChannelVoice::ProgramChange {
+                channel: 4,
+                program: 10,
+                bank: Some(0x0102),
+            },
+            Midi2ChannelVoice::ProgramChange {
+                channel: 4,
+                program: 10,
+                bank: None,
+            },
+            Midi2ChannelVoice::PerNoteManagement {
+                channel: 5,
+                note: 61,
+                detach: true,
+                reset: false,
+            },
+        ];
+        for message in messages {
+            let ump = Ump::Midi2ChannelVoice {
+                group: 7,
+                message: message.clone(),
+            };
+            assert_eq!(
+                Ump::parse(&ump.to_words()).unwrap(),
+                (ump, 2),
+                "{:?}",
+                message
+            );
+        }
+
+        let pc = Ump::Midi2ChannelVoice {
+            group: 0,
+            message: Midi2ChannelVoice::ProgramChange {
+                channel: 0,
+                program: 5,
+                bank: Some(1 << 7 | 2),
+            },
+        };
+        assert_eq!(pc.to_words(), vec![0x40C0_0001, 0x0500_0102]);
+    }
+
+    #[test]
+    fn test_scaling() {
+        assert_eq!(scale_up(0, 7, 16), 0);
+        assert_eq!(scale_up(64, 7, 16), 0x8000);
+        assert_eq!(scale_up(127, 7, 16), 0xFFFF);
+        assert_eq!(scale_up(64, 7, 32), 0x8000_0000);
+        assert_eq!(scale_up(127, 7, 32), 0xFFFF_FFFF);
+        assert_eq!(scale_up(8192, 14, 32), 0x8000_0000);
+        assert_eq!(scale_up(16383, 14, 32), 0xFFFF_FFFF);
+        for value in 0..128 {
+            assert_eq!(scale_down(scale_up(value, 7, 32), 32, 7), value);
+            assert_eq!(scale_down(scale_up(value, 7, 16), 16, 7), value);
+        }
+        for value in 0..16384 {
+            assert_eq!(scale_down(scale_up(value, 14, 32), 32, 14), value);
+        }
+    }
+
+    #[test]
+    fn test_midi1_to_midi2_translation() {
+        assert_eq!(
+            Midi2ChannelVoice::from_midi1(&MidiMessage::NoteOn {
+                channel: 0,
+                note: 60,
+                velocity: 0
+            }),
+            Some(Midi2ChannelVoice::NoteOff {
+                channel: 0,
+                note: 60,
+                velocity: 0x8000,
+                attribute_type: 0,
+                attribute: 0
+            })
+        );
+        assert_eq!(
+            Midi2ChannelVoice::from_midi1(&MidiMessage::PitchBend {
+                channel: 2,
+                value: 0
+            }),
+            Some(Midi2ChannelVoice::PitchBend {
+                channel: 2,
+                value: 0x8000_0000
+            })
+        );
+        assert_eq!(Midi2ChannelVoice::from_midi1(&MidiMessage::Start), None);
+
+        let packets = Ump::from_midi1_translated(3, &MidiMessage::TimingClock);
+        assert_eq!(
+            packets,
+            vec![Ump::System {
+                group: 3,
+                message: MidiMessage::TimingClock
+            }]
+        );
+
+        let rpn = ParameterChange {
+            channel: 1,
+            parameter_type: ParameterType::Rpn,
+            number: 0,
+            value: 12 << 7,
+        };
+        let message = Midi2ChannelVoice::from_parameter(&rpn);
+        assert_eq!(
+            message,
+            Midi2ChannelVoice::RegisteredController {
+                channel: 1,
+                bank: 0,
+                index: 0,
+                value: 12 << 25
+            }
+        );
+        let cc = |controller, value| MidiMessage::ControlChange {
+            channel: 1,
+            controller,
+            value,
+        };
+        assert_eq!(
+            message.to_midi1(),
+            vec![cc(101, 0), cc(100, 0), cc(6, 12), cc(38, 0)]
+        );
+    }
+
+    #[test]
+    fn test_midi2_to_midi1_translation() {
+        // A quiet MIDI 2.0 Note On must not become a MIDI 1.0 Note Off
+        let note_on = Midi2ChannelVoice::NoteOn {
+            channel: 0,
+            note: 60,
+            velocity: 0x0100,
+            attribute_type: 0,
+            attribute: 0,
+        };
+        assert_eq!(
+            note_on.to_midi1(),
+            vec![MidiMessage::NoteOn {
+                channel: 0,
+                note: 60,
+                velocity: 1
+            }]
+        );
+
+        let program = Midi2ChannelVoice::ProgramChange {
+            channel: 9,
+            program: 3,
+            bank: Some(1 << 7 | 5),
+        };
+        assert_eq!(
+            program.to_midi1(),
+            vec![
+                MidiMessage::ControlChange {
+                    channel: 9,
+                    controller: 0,
+                    value: 1
+                },
+                MidiMessage::ControlChange {
+                    channel: 9,
+                    controller: 32,
+                    value: 5
+                },
+                MidiMessage::ProgramChange {
+                    channel: 9,
+                    program: 3
+                }
+            ]
+        );
+
+        let per_note = Midi2ChannelVoice::PerNotePitchBend {
+            channel: 0,
+            note: 60,
+            value: 0,
+        };
+        assert!(per_note.to_midi1().is_empty());
+    }
+
+    #[test]
+    fn test_midi1_round_trips_through_midi2() {
+        for value in 0..128u8 {
+            let messages = [
+                MidiMessage::NoteOff {
+                    channel: 15,
+                    note: 127 - value,
+                    velocity: value,
+                },
+                MidiMessage::PolyPressure {
+                    channel: 1,
+                    note: value,
+                    pressure: value,
+                },
+                MidiMessage::ControlChange {
+                    channel: 2,
+                    controller: value,
+                    value,
+                },
+                MidiMessage::ProgramChange {
+                    channel: 3,
+                    program: value,
+                },
+                MidiMessage::ChannelPressure {
+                    channel: 4,
+                    pressure: value,
+                },
+                MidiMessage::PitchBend {
+                    channel: 5,
+                    value: (value as i16) * 128 - 8192,
+                },
+            ];
+            for message in messages {
+                let translated = Ump::from_midi1_translated(0, &message);
+                let words: Vec<u32> = translated.iter().flat_map(Ump::to_words).collect();
+                let parsed = Ump::parse_all(&words).unwrap();
+                assert_eq!(parsed[0].to_midi1(), vec![message.clone()]);
+
+                let packed = Ump::from_midi1(0, &message);
+                assert_eq!(packed[0].to_midi1(), vec![message]);
+            }
+        }
+        for velocity in 1..128u8 {
+            let message = MidiMessage::NoteOn {
+                channel: 0,
+                note: 60,
+                velocity,
+            };
+            let translated = Midi2ChannelVoice::from_midi1(&message).unwrap();
+            assert_eq!(translated.to_midi1(), vec![message]);
+        }
+    }
+}
@@ -1,4 +1,8 @@
-use midi_backend;
+use std::sync::Mutex;
+use tauri::Manager;
+
+/// The MIDI backend server and the runtime it runs on, stopped on exit.
+struct Backend(Mutex<Option<(tokio::runtime::Runtime, midi_backend::ServerHandle)>>);
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -12,18 +16,38 @@ pub fn run() {
         )?;
       }
 
-      // Start the MIDI backend server in a separate thread
-      std::thread::spawn(|| {
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(async {
-          if let Err(e) = midi_backend::start_server().await {
-            eprintln!("Failed to start MIDI server: {}", e);
-          }
-        });
-      });
+      log::info!(
+        "Starting MIDI backend (wire protocol v{})",
+        midi_protocol::PROTOCOL_VERSION
+      );
+
+      // The server runs on its own runtime, kept until the app exits
+      let runtime = tokio::runtime::Runtime::new()?;
+      // The desktop app has no flags of its own; the file and environment still apply
+      let result = match midi_backend::ServerConfig::load(std::iter::empty()) {
+        Ok(config) => runtime.block_on(midi_backend::start_server(config)),
+        Err(e) => Err(e),
+      };
+      match result {
+        Ok(server) => {
+          app.manage(Backend(Mutex::new(Some((runtime, server)))));
+        }
+        Err(e) => eprintln!("Failed to start MIDI server: {}", e),
+      }
 
       Ok(())
     })
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    .build(tauri::generate_context!())
+    .expect("error while building tauri application")
+    .run(|app, event| {
+      if let tauri::RunEvent::Exit = event {
+        if let Some(backend) = app.try_state::<Backend>() {
+          if let Some((runtime, server)) = backend.0.lock().unwrap().take() {
+            if let Err(e) = runtime.block_on(server.shutdown()) {
+              eprintln!("Failed to stop MIDI server: {}", e);
+            }
+          }
+        }
+      }
+    });
 }